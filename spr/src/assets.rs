@@ -0,0 +1,249 @@
+/*
+ * Copyright (c) Radical HQ Limited
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Uploading images that a commit message refers to by local path.
+//!
+//! An image like `![](docs/shot.png)` renders fine in a local Markdown
+//! viewer, but not in a Pull Request on GitHub. With `spr.uploadAssets` set,
+//! the referenced files are put into a commit of their own, which is pushed
+//! to a branch next to the Pull Request branches, and the links in the Pull
+//! Request body are rewritten to point at the files in that commit.
+//!
+//! The commit is built with a fixed author and date, so the same set of
+//! images always results in the same commit and the same links.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use git2::Oid;
+
+use crate::{
+    config::Config,
+    error::{Error, Result, ResultExt},
+    message::{MessageSection, MessageSectionsMap},
+    utils::run_command,
+};
+
+/// The sections of a commit message that go into the Pull Request body and
+/// may therefore contain images.
+const BODY_SECTIONS: [MessageSection; 2] = [MessageSection::Summary, MessageSection::TestPlan];
+
+/// Local files referenced as Markdown images in `text`, as pairs of the link
+/// as written and the path of the file. Links with a URL scheme and links to
+/// files that don't exist are left out.
+pub fn local_image_paths(text: &str, workdir: &Path) -> Vec<(String, PathBuf)> {
+    let mut result: Vec<(String, PathBuf)> = Vec::new();
+
+    for captures in lazy_regex::regex!(r#"!\[[^\]]*\]\(([^)\s]+)\)"#).captures_iter(text) {
+        let link = &captures[1];
+        if link.contains("://") || link.starts_with("data:") || link.starts_with('#') {
+            continue;
+        }
+        let path = workdir.join(link.trim_start_matches('/'));
+        if path.is_file() && !result.iter().any(|(l, _)| l == link) {
+            result.push((link.to_string(), path));
+        }
+    }
+
+    result
+}
+
+/// Replace the targets of Markdown image links in `text` according to
+/// `urls`. Other links are left alone.
+pub fn rewrite_image_links(text: &str, urls: &HashMap<String, String>) -> String {
+    lazy_regex::regex!(r#"(!\[[^\]]*\]\()([^)\s]+)\)"#)
+        .replace_all(text, |captures: &lazy_regex::Captures| {
+            match urls.get(&captures[2]) {
+                Some(url) => format!("{}{})", &captures[1], url),
+                None => captures[0].to_string(),
+            }
+        })
+        .into_owned()
+}
+
+/// Prepare the upload of the local images referenced in the body sections of
+/// `message`. The image commit is created in the local repository; it still
+/// has to be pushed with [`push_images`]. Returns the message as it should
+/// appear on GitHub together with that commit, or `None` if the message has
+/// no local images.
+pub fn prepare_images(
+    git_repo: &git2::Repository,
+    config: &Config,
+    message: &MessageSectionsMap,
+) -> Result<Option<(MessageSectionsMap, Oid)>> {
+    let workdir = git_repo
+        .workdir()
+        .ok_or_else(|| Error::new("Repository must have a working directory"))?;
+
+    let mut images = Vec::new();
+    for section in BODY_SECTIONS {
+        if let Some(text) = message.get(&section) {
+            for image in local_image_paths(text, workdir) {
+                if !images.contains(&image) {
+                    images.push(image);
+                }
+            }
+        }
+    }
+    if images.is_empty() {
+        return Ok(None);
+    }
+
+    let mut tree = git_repo.treebuilder(None)?;
+    let mut names = Vec::new();
+    for (link, path) in images {
+        let blob = git_repo
+            .blob_path(&path)
+            .reword(format!("Could not read image {}", path.display()))?;
+        let file_name = path
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        // Prefix the name with the blob id, so that different images with
+        // the same file name don't clash.
+        let name = format!("{}-{}", &blob.to_string()[..8], file_name);
+        tree.insert(&name, blob, git2::FileMode::Blob.into())?;
+        names.push((link, name));
+    }
+    let tree = git_repo.find_tree(tree.write()?)?;
+
+    let signature = git2::Signature::new("spr", "spr@localhost", &git2::Time::new(0, 0))?;
+    let commit = git_repo.commit(
+        None,
+        &signature,
+        &signature,
+        "Images for Pull Request descriptions\n",
+        &tree,
+        &[],
+    )?;
+
+    let urls: HashMap<String, String> = names
+        .into_iter()
+        .map(|(link, name)| {
+            let url = format!(
+                "{}/{}/{}/blob/{}/{}?raw=true",
+                config.github_web_url(),
+                config.owner,
+                config.repo,
+                commit,
+                name
+            );
+            (link, url)
+        })
+        .collect();
+
+    let mut github_message = message.clone();
+    for section in BODY_SECTIONS {
+        if let Some(text) = github_message.get_mut(&section) {
+            *text = rewrite_image_links(text, &urls);
+        }
+    }
+
+    Ok(Some((github_message, commit)))
+}
+
+/// Push an image commit made by [`prepare_images`] to GitHub.
+pub async fn push_images(config: &Config, commit: Oid) -> Result<()> {
+    run_command(
+        tokio::process::Command::new("git")
+            .arg("push")
+            .arg("--no-verify")
+            .arg("--")
+            .arg(&config.remote_name)
+            .arg(format!(
+                "{}:refs/heads/{}",
+                commit,
+                images_branch_name(config, commit)
+            )),
+    )
+    .await
+    .reword("git push of images failed".to_string())
+}
+
+fn images_branch_name(config: &Config, commit: Oid) -> String {
+    format!(
+        "{}assets/{}",
+        config.branch_prefix,
+        &commit.to_string()[..12]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    #[test]
+    fn test_local_image_link_is_rewritten() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let git_repo = git2::Repository::init(temp_dir.path()).unwrap();
+        std::fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        std::fs::write(temp_dir.path().join("docs/shot.png"), b"\x89PNG").unwrap();
+
+        let config = test_config();
+
+        let mut message = MessageSectionsMap::new();
+        message.insert(MessageSection::Title, "Shiny button".into());
+        message.insert(
+            MessageSection::Summary,
+            "Before: ![old](https://example.com/old.png)\nAfter: ![new](docs/shot.png)\nGone: ![](docs/missing.png)".into(),
+        );
+        message.insert(MessageSection::TestPlan, "See ![](/docs/shot.png)".into());
+
+        let (github_message, commit) = prepare_images(&git_repo, &config, &message)
+            .unwrap()
+            .unwrap();
+
+        let tree = git_repo.find_commit(commit).unwrap().tree().unwrap();
+        assert_eq!(tree.len(), 1);
+        let name = tree.get(0).unwrap().name().unwrap().to_string();
+        assert!(name.ends_with("-shot.png"));
+
+        let url = format!(
+            "https://github.com/acme/codez/blob/{}/{}?raw=true",
+            commit, name
+        );
+        assert_eq!(
+            github_message[&MessageSection::Summary],
+            format!(
+                "Before: ![old](https://example.com/old.png)\nAfter: ![new]({})\nGone: ![](docs/missing.png)",
+                url
+            )
+        );
+        assert_eq!(
+            github_message[&MessageSection::TestPlan],
+            format!("See ![]({})", url)
+        );
+        assert_eq!(github_message[&MessageSection::Title], "Shiny button");
+
+        // The same images give the same commit, so the links are stable
+        let (_, commit_again) = prepare_images(&git_repo, &config, &message)
+            .unwrap()
+            .unwrap();
+        assert_eq!(commit, commit_again);
+    }
+
+    #[test]
+    fn test_message_without_local_images() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let git_repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let config = test_config();
+
+        let mut message = MessageSectionsMap::new();
+        message.insert(
+            MessageSection::Summary,
+            "![](https://example.com/shot.png)".into(),
+        );
+        assert!(
+            prepare_images(&git_repo, &config, &message)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -5,11 +5,18 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::collections::HashMap;
+
 use crate::{
     error::{Error, Result},
+    github::ReviewStatus,
     jj::PreparedCommit,
-    message::validate_commit_message,
+    message::{
+        LOCAL_SECTIONS, MessageSection, MessageSectionsMap, build_pull_request_message,
+        validate_commit_message,
+    },
     output::{output, write_commit_title},
+    store::MappingStore,
 };
 
 #[derive(Debug, clap::Parser)]
@@ -18,7 +25,8 @@ pub struct AmendOptions {
     #[clap(long, short = 'a')]
     all: bool,
 
-    /// Base revision for --all mode (if not specified, uses trunk)
+    /// Base revision for --all mode (if not specified, the stack starts
+    /// where the revision branched off master)
     #[clap(long)]
     base: Option<String>,
 
@@ -26,6 +34,103 @@ pub struct AmendOptions {
     /// If a range is provided, behaves like --all mode. If not specified, uses '@-'.
     #[clap(short = 'r', long)]
     revision: Option<String>,
+
+    /// If the commit message was changed both locally and on GitHub, take
+    /// the one from GitHub
+    #[clap(long, conflicts_with = "ours")]
+    theirs: bool,
+
+    /// If the commit message was changed both locally and on GitHub, keep
+    /// the local one
+    #[clap(long)]
+    ours: bool,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Resolution {
+    /// Replace the local message with the one from GitHub
+    TakeRemote,
+    /// Leave the local message as it is
+    KeepLocal,
+    /// Both sides changed and no side was chosen
+    Conflict,
+}
+
+/// Decide what to do with a local commit message, given the message on
+/// GitHub and the id of the blob holding the message that was last in sync
+/// with both (see [`MappingStore::message_oid`]).
+fn resolve_message(
+    base_oid: Option<git2::Oid>,
+    local: &str,
+    remote: &str,
+    opts: &AmendOptions,
+) -> Result<Resolution> {
+    if local == remote {
+        return Ok(Resolution::KeepLocal);
+    }
+    let local_oid = git2::Oid::hash_object(git2::ObjectType::Blob, local.as_bytes())?;
+    if base_oid.is_none_or(|base_oid| base_oid == local_oid) {
+        // No local edits since the last sync (or we don't know about the
+        // last sync), so nothing gets lost.
+        return Ok(Resolution::TakeRemote);
+    }
+    let remote_oid = git2::Oid::hash_object(git2::ObjectType::Blob, remote.as_bytes())?;
+    if base_oid == Some(remote_oid) {
+        // Only edited locally: GitHub still has the message of the last sync.
+        return Ok(Resolution::KeepLocal);
+    }
+
+    Ok(if opts.theirs {
+        Resolution::TakeRemote
+    } else if opts.ours {
+        Resolution::KeepLocal
+    } else {
+        Resolution::Conflict
+    })
+}
+
+/// Make the Reviewed By section list exactly the users who approved the Pull
+/// Request, and leave only those who haven't approved (yet) in Reviewers.
+/// Returns whether the message changed.
+fn reconcile_approvals(
+    message: &mut MessageSectionsMap,
+    reviewers: &HashMap<String, ReviewStatus>,
+) -> bool {
+    let mut approvers: Vec<&str> = reviewers
+        .iter()
+        .filter(|(_, status)| **status == ReviewStatus::Approved)
+        .map(|(login, _)| login.as_str())
+        .collect();
+    approvers.sort_unstable();
+
+    let requested = message.get(&MessageSection::Reviewers).map(|text| {
+        text.split(',')
+            .map(str::trim)
+            .filter(|reviewer| {
+                !reviewer.is_empty()
+                    && !approvers
+                        .iter()
+                        .any(|approver| approver.eq_ignore_ascii_case(reviewer))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    });
+
+    let mut changed = false;
+    for (section, text) in [
+        (MessageSection::Reviewers, requested),
+        (MessageSection::ReviewedBy, Some(approvers.join(", "))),
+    ] {
+        let text = text.filter(|text| !text.is_empty());
+        if message.get(&section) != text.as_ref() {
+            match text {
+                Some(text) => message.insert(section, text),
+                None => message.remove(&section),
+            };
+            changed = true;
+        }
+    }
+    changed
 }
 
 pub async fn amend(
@@ -34,19 +139,12 @@ pub async fn amend(
     gh: &mut crate::github::GitHub,
     config: &crate::config::Config,
 ) -> Result<()> {
-    // Determine revision and whether to use range mode
-    let (use_range_mode, base_rev, target_rev, is_inclusive) =
-        crate::revision_utils::parse_revision_and_range(
-            opts.revision.as_deref(),
-            opts.all,
-            opts.base.as_deref(),
-        )?;
-
-    let mut pc = if use_range_mode {
-        jj.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?
-    } else {
-        vec![jj.get_prepared_commit_for_revision(config, &target_rev)?]
-    };
+    let (use_range_mode, mut pc) = jj.get_prepared_commits_for_options(
+        config,
+        opts.revision.as_deref(),
+        opts.all,
+        opts.base.as_deref(),
+    )?;
 
     if pc.is_empty() {
         output("👋", "No commits found - nothing to do. Good bye!")?;
@@ -64,18 +162,181 @@ pub async fn amend(
         })
         .collect();
 
+    let mut store = MappingStore::open(&jj.git_repo)?;
     let mut failure = false;
 
-    for (commit, pull_request) in pc.iter_mut().zip(pull_requests.into_iter()) {
+    for (commit, pull_request) in pc.iter_mut().zip(pull_requests) {
+        // In a stack, commits that have no Pull Request yet have nothing to
+        // be synced from GitHub
+        if use_range_mode && pull_request.is_none() {
+            continue;
+        }
         write_commit_title(commit)?;
         if let Some(pull_request) = pull_request {
             let pull_request = pull_request.await??;
-            commit.message = pull_request.sections;
-            commit.message_changed = true;
+            let reviewers = pull_request.reviewers.clone();
+            let local = build_pull_request_message(&commit.message, config);
+            let remote = build_pull_request_message(&pull_request.sections, config);
+
+            match resolve_message(
+                store.message_oid(pull_request.number),
+                &local,
+                &remote,
+                &opts,
+            )? {
+                Resolution::TakeRemote => {
+                    let local_sections: Vec<_> = LOCAL_SECTIONS
+                        .iter()
+                        .filter_map(|section| Some((*section, commit.message.remove(section)?)))
+                        .collect();
+                    commit.message = pull_request.sections;
+                    commit.message.extend(local_sections);
+                    commit.message_changed = true;
+                    store.set_message(&jj.git_repo, pull_request.number, &remote)?;
+                }
+                Resolution::KeepLocal => {}
+                Resolution::Conflict => {
+                    output(
+                        "💥",
+                        "The commit message was changed both locally and on \
+                         GitHub since the last sync. Run again with --theirs \
+                         to take the message from GitHub, or with --ours to \
+                         keep the local one.",
+                    )?;
+                    let base = store
+                        .message(&jj.git_repo, pull_request.number)
+                        .unwrap_or_else(|| String::from("(not available)\n"));
+                    for (label, text) in [
+                        ("base (last sync)", &base),
+                        ("local", &local),
+                        ("GitHub", &remote),
+                    ] {
+                        output("  ", &format!("--- {}\n{}", label, text))?;
+                    }
+                    failure = true;
+                    continue;
+                }
+            }
+
+            if reconcile_approvals(&mut commit.message, &reviewers) {
+                commit.message_changed = true;
+            }
         }
         failure = validate_commit_message(&commit.message, config).is_err() || failure;
     }
-    jj.rewrite_commit_messages(&mut pc)?;
+    // All changed messages are written in one go, so that each commit of
+    // the stack is described at most once
+    jj.rewrite_commit_messages(config, &mut pc)?;
 
     if failure { Err(Error::empty()) } else { Ok(()) }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amend_options(theirs: bool, ours: bool) -> AmendOptions {
+        AmendOptions {
+            all: false,
+            base: None,
+            revision: None,
+            theirs,
+            ours,
+        }
+    }
+
+    fn blob_oid(text: &str) -> git2::Oid {
+        git2::Oid::hash_object(git2::ObjectType::Blob, text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_resolve_message_without_local_edits() {
+        let base = "Title\n\nSummary\n";
+        let remote = "Title\n\nSummary edited on GitHub\n";
+        let opts = amend_options(false, false);
+
+        assert_eq!(
+            resolve_message(Some(blob_oid(base)), base, remote, &opts).unwrap(),
+            Resolution::TakeRemote
+        );
+        // Without a record of the last sync, GitHub wins as it always did.
+        assert_eq!(
+            resolve_message(None, "Local title\n", remote, &opts).unwrap(),
+            Resolution::TakeRemote
+        );
+        assert_eq!(
+            resolve_message(Some(blob_oid(base)), remote, remote, &opts).unwrap(),
+            Resolution::KeepLocal
+        );
+    }
+
+    #[test]
+    fn test_reconcile_approvals() {
+        let mut message = MessageSectionsMap::new();
+        message.insert(MessageSection::Reviewers, "alice, Bob, #infra".into());
+        message.insert(MessageSection::ReviewedBy, "carol".into());
+        let reviewers = HashMap::from([
+            ("bob".to_string(), ReviewStatus::Approved),
+            ("alice".to_string(), ReviewStatus::Rejected),
+            ("dave".to_string(), ReviewStatus::Approved),
+        ]);
+
+        assert!(reconcile_approvals(&mut message, &reviewers));
+        assert_eq!(
+            message.get(&MessageSection::Reviewers).map(String::as_str),
+            Some("alice, #infra")
+        );
+        assert_eq!(
+            message.get(&MessageSection::ReviewedBy).map(String::as_str),
+            Some("bob, dave")
+        );
+        assert!(!reconcile_approvals(&mut message, &reviewers));
+
+        // Without approvals, nobody is listed as having reviewed
+        assert!(reconcile_approvals(&mut message, &HashMap::new()));
+        assert!(!message.contains_key(&MessageSection::ReviewedBy));
+        assert_eq!(
+            message.get(&MessageSection::Reviewers).map(String::as_str),
+            Some("alice, #infra")
+        );
+    }
+
+    #[test]
+    fn test_resolve_message_with_only_local_edits() {
+        let base = "Title\n\nSummary\n";
+        let local = "Title\n\nSummary edited locally\n";
+
+        for (theirs, ours) in [(false, false), (true, false), (false, true)] {
+            assert_eq!(
+                resolve_message(
+                    Some(blob_oid(base)),
+                    local,
+                    base,
+                    &amend_options(theirs, ours)
+                )
+                .unwrap(),
+                Resolution::KeepLocal
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_message_conflict() {
+        let base = Some(blob_oid("Title\n\nSummary\n"));
+        let local = "Title\n\nSummary edited locally\n";
+        let remote = "Title\n\nSummary edited on GitHub\n";
+
+        assert_eq!(
+            resolve_message(base, local, remote, &amend_options(false, false)).unwrap(),
+            Resolution::Conflict
+        );
+        assert_eq!(
+            resolve_message(base, local, remote, &amend_options(true, false)).unwrap(),
+            Resolution::TakeRemote
+        );
+        assert_eq!(
+            resolve_message(base, local, remote, &amend_options(false, true)).unwrap(),
+            Resolution::KeepLocal
+        );
+    }
+}
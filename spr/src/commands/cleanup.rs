@@ -0,0 +1,198 @@
+/*
+ * Copyright (c) Radical HQ Limited
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use std::{collections::HashSet, io::IsTerminal};
+
+use crate::{
+    error::{Error, Result, add_error},
+    github::{GitHubBranch, PullRequestState},
+    output::output,
+    utils::{AsyncLoggedOutput, run_bounded},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct CleanupOptions {
+    /// Only report the branches that would be deleted
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Delete the branches without asking for confirmation
+    #[clap(long, short = 'y')]
+    yes: bool,
+
+    /// Look up Pull Requests and delete branches on GitHub up to N at a time
+    #[clap(
+        long,
+        value_name = "N",
+        default_value_t = 4,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    parallel: u64,
+}
+
+pub async fn cleanup(
+    opts: CleanupOptions,
+    jj: &crate::jj::Jujutsu,
+    gh: &mut crate::github::GitHub,
+    config: &crate::config::Config,
+) -> Result<()> {
+    let branch_names = spr_branch_names(
+        &jj.get_all_ref_names()?,
+        &config.remote_name,
+        &config.branch_prefix,
+    );
+
+    // Look up the Pull Requests of the branches, a few at a time
+    let gh = &*gh;
+    let pull_requests = run_bounded(
+        branch_names.iter().map(|name| async move {
+            let branch = config.new_github_branch(name);
+            match gh.find_pull_request_for_head(&branch).await? {
+                Some(number) => Ok(Some(gh.clone().get_pull_request(number).await?)),
+                None => Ok::<_, Error>(None),
+            }
+        }),
+        opts.parallel as usize,
+    )
+    .await;
+
+    let mut orphaned = Vec::new();
+    for (name, pull_request) in branch_names.iter().zip(pull_requests) {
+        // Branches without a Pull Request (e.g. the base branches of stacked
+        // Pull Requests) are left alone
+        if let Some(pull_request) = pull_request?
+            && pull_request.state == PullRequestState::Closed
+        {
+            let state = if pull_request.merge_commit.is_some() {
+                "merged"
+            } else {
+                "closed"
+            };
+            output(
+                "🧹",
+                &format!(
+                    "{} (Pull Request #{} is {})",
+                    name, pull_request.number, state
+                ),
+            )?;
+            orphaned.push(pull_request.head);
+        }
+    }
+
+    if orphaned.is_empty() {
+        output("👋", "No branches of closed Pull Requests found. Good bye!")?;
+        return Ok(());
+    }
+    if opts.dry_run {
+        output(
+            "🔍",
+            &format!("(dry run) would delete {} branches", orphaned.len()),
+        )?;
+        return Ok(());
+    }
+
+    // Without a terminal to ask on, only --yes deletes anything
+    if !opts.yes {
+        let count = orphaned.len();
+        let confirmed = std::io::stdin().is_terminal()
+            && tokio::task::spawn_blocking(move || {
+                dialoguer::Confirm::new()
+                    .with_prompt(format!("Delete {} branches on GitHub?", count))
+                    .default(false)
+                    .interact()
+            })
+            .await??;
+        if !confirmed {
+            return Err(Error::new("Cleanup cancelled."));
+        }
+    }
+
+    // Delete each branch on its own, so that one that is already gone does
+    // not keep the others around
+    let results = run_bounded(
+        orphaned.iter().map(|branch| delete_branch(branch, config)),
+        opts.parallel as usize,
+    )
+    .await;
+    let mut result = Ok(());
+    let mut deleted = 0;
+    for deleted_branch in results {
+        if add_error(&mut result, deleted_branch).is_some() {
+            deleted += 1;
+        }
+    }
+    output("✅", &format!("Deleted {} branches", deleted))?;
+
+    result
+}
+
+/// The names of the branches on GitHub, as last fetched from the remote,
+/// that spr created (i.e. that start with the branch prefix), sorted.
+fn spr_branch_names(
+    ref_names: &HashSet<String>,
+    remote_name: &str,
+    branch_prefix: &str,
+) -> Vec<String> {
+    let remote_prefix = format!("refs/remotes/{}/", remote_name);
+    let mut names: Vec<String> = ref_names
+        .iter()
+        .filter_map(|ref_name| ref_name.strip_prefix(&remote_prefix))
+        .filter(|name| name.starts_with(branch_prefix))
+        .map(String::from)
+        .collect();
+    names.sort();
+    names
+}
+
+/// Delete a branch on GitHub. A branch that does not exist there anymore
+/// (e.g. because GitHub deleted it on merge) counts as deleted.
+async fn delete_branch(branch: &GitHubBranch, config: &crate::config::Config) -> Result<()> {
+    let mut command = tokio::process::Command::new("git");
+    command
+        .arg("push")
+        .arg("--no-verify")
+        .arg("--delete")
+        .arg("--")
+        .arg(&config.remote_name)
+        .arg(branch.on_github());
+
+    let output = command.logged_output().await?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() && !stderr.contains("remote ref does not exist") {
+        return Err(Error::new(format!(
+            "Failed to delete branch {}: {}",
+            branch.branch_name(),
+            stderr
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spr_branch_names() {
+        let ref_names: HashSet<String> = [
+            "refs/remotes/origin/spr/alice/fix-the-thing",
+            "refs/remotes/origin/spr/alice/add-a-feature",
+            "refs/remotes/origin/main",
+            "refs/remotes/fork/spr/alice/elsewhere",
+            "refs/heads/spr/alice/local-only",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+
+        assert_eq!(
+            spr_branch_names(&ref_names, "origin", "spr/alice/"),
+            vec!["spr/alice/add-a-feature", "spr/alice/fix-the-thing"]
+        );
+    }
+}
@@ -11,7 +11,7 @@ use indoc::formatdoc;
 
 use crate::{
     error::{Error, Result, add_error},
-    github::{PullRequestState, PullRequestUpdate},
+    github::PullRequestState,
     jj::PreparedCommit,
     message::MessageSection,
     output::{output, write_commit_title},
@@ -23,7 +23,8 @@ pub struct CloseOptions {
     #[clap(long, short = 'a')]
     all: bool,
 
-    /// Base revision for --all mode (if not specified, uses trunk)
+    /// Base revision for --all mode (if not specified, the stack starts
+    /// where the revision branched off master)
     #[clap(long)]
     base: Option<String>,
 
@@ -31,6 +32,20 @@ pub struct CloseOptions {
     /// If a range is provided, behaves like --all mode. If not specified, uses '@-'.
     #[clap(short = 'r', long)]
     revision: Option<String>,
+
+    /// Delete the Pull Request's branch on GitHub (the default)
+    #[clap(long, overrides_with = "no_delete_branch")]
+    delete_branch: bool,
+
+    /// Keep the Pull Request's branch on GitHub, e.g. to reopen the Pull
+    /// Request later
+    #[clap(long, overrides_with = "delete_branch")]
+    no_delete_branch: bool,
+
+    /// Post this comment on the Pull Request before closing it, e.g. to say
+    /// why it was closed
+    #[clap(long, value_name = "TEXT")]
+    comment: Option<String>,
 }
 
 pub async fn close(
@@ -41,19 +56,12 @@ pub async fn close(
 ) -> Result<()> {
     let mut result = Ok(());
 
-    // Determine revision and whether to use range mode
-    let (use_range_mode, base_rev, target_rev, is_inclusive) =
-        crate::revision_utils::parse_revision_and_range(
-            opts.revision.as_deref(),
-            opts.all,
-            opts.base.as_deref(),
-        )?;
-
-    let mut prepared_commits = if use_range_mode {
-        jj.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?
-    } else {
-        vec![jj.get_prepared_commit_for_revision(config, &target_rev)?]
-    };
+    let (_, mut prepared_commits) = jj.get_prepared_commits_for_options(
+        config,
+        opts.revision.as_deref(),
+        opts.all,
+        opts.base.as_deref(),
+    )?;
 
     if prepared_commits.is_empty() {
         output("👋", "No commits found - nothing to do. Good bye!")?;
@@ -71,20 +79,21 @@ pub async fn close(
         // This makes it easier to run the code to update the local commit message
         // with all the changes that the implementation makes at the end, even if
         // the implementation encounters an error or exits early.
-        result = close_impl(gh, config, prepared_commit).await;
+        result = close_impl(&opts, gh, config, prepared_commit).await;
     }
 
     // This updates the commit message in the local Jujutsu repository (if it was
     // changed by the implementation)
     add_error(
         &mut result,
-        jj.rewrite_commit_messages(&mut prepared_commits),
+        jj.rewrite_commit_messages(config, &mut prepared_commits),
     );
 
     result
 }
 
 async fn close_impl(
+    opts: &CloseOptions,
     gh: &mut crate::github::GitHub,
     config: &crate::config::Config,
     prepared_commit: &mut PreparedCommit,
@@ -107,34 +116,31 @@ async fn close_impl(
 
     output("📖", "Getting started...")?;
 
-    let base_is_master = pull_request.base.is_master_branch();
-
-    let result = gh
-        .update_pull_request(
-            pull_request_number,
-            PullRequestUpdate {
-                state: Some(PullRequestState::Closed),
-                ..Default::default()
-            },
-        )
-        .await;
-
-    match result {
-        Ok(()) => (),
-        Err(error) => {
-            output("❌", "GitHub Pull Request close failed")?;
+    if let Err(error) = gh
+        .close_pull_request(pull_request_number, opts.comment.clone())
+        .await
+    {
+        output("❌", "GitHub Pull Request close failed")?;
 
-            return Err(error);
-        }
-    };
+        return Err(error);
+    }
 
+    if opts.comment.is_some() {
+        output("💬", "Posted comment")?;
+    }
     output("📕", "Closed!")?;
 
+    let base_is_master = pull_request.base.is_master_branch();
+
     // Remove sections from commit that are not relevant after closing.
     prepared_commit.message.remove(&MessageSection::PullRequest);
     prepared_commit.message.remove(&MessageSection::ReviewedBy);
     prepared_commit.message_changed = true;
 
+    if opts.no_delete_branch && !opts.delete_branch {
+        return Ok(());
+    }
+
     let mut remove_old_branch_child_process = tokio::process::Command::new("git")
         .arg("push")
         .arg("--no-verify")
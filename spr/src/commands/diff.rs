@@ -5,16 +5,29 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use std::iter::zip;
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    iter::zip,
+    path::{Path, PathBuf},
+};
 
 use crate::{
+    assets,
+    config::parse_label_list,
     error::{Error, Result, ResultExt, add_error},
     github::{
         GitHub, PullRequest, PullRequestRequestReviewers, PullRequestState, PullRequestUpdate,
+        ReviewStatus,
+    },
+    message::{
+        MessageSection, MessageSectionsMap, SectionMerge, build_commit_message,
+        build_pull_request_message, commit_message_problems, merge_remote_section,
+        merge_remote_sections, message_section_label, parse_message, validate_commit_message,
     },
-    message::{MessageSection, validate_commit_message},
     output::{output, write_commit_title},
-    utils::{parse_name_list, remove_all_parens, run_command},
+    store::MappingStore,
+    utils::{open_in_browser, parse_name_list, remove_all_parens, run_bounded, run_command},
 };
 use git2::Oid;
 use indoc::{formatdoc, indoc};
@@ -30,30 +43,464 @@ pub struct DiffOptions {
     #[clap(long)]
     update_message: bool,
 
+    /// Before updating the Pull Request, take changes made to its title and
+    /// description on GitHub into the local commit message. Sections changed
+    /// on both sides keep the local text. Implies --update-message
+    #[clap(long, conflicts_with = "pr_message")]
+    sync_message: bool,
+
+    /// Before updating the Pull Request, take the Summary on GitHub into the
+    /// local commit message, e.g. after it was edited there. A Summary
+    /// changed locally as well keeps the local text. Other sections stay as
+    /// they are locally. Implies --update-message
+    #[clap(long, conflicts_with_all = ["pr_message", "sync_message"])]
+    summary_from_body: bool,
+
+    /// Only update the title, description and other metadata of existing
+    /// Pull Requests, without pushing the commits, e.g. when the branch is
+    /// up to date already. Implies --update-message
+    #[clap(long, conflicts_with_all = ["split_by_path", "cherry_pick"])]
+    no_push: bool,
+
+    /// Do not rewrite the local commit messages, e.g. to add the URL of a new
+    /// Pull Request. spr remembers the Pull Requests of new commits instead,
+    /// but only `spr diff` finds them that way
+    #[clap(long, conflicts_with = "sync_message")]
+    no_update_message: bool,
+
+    /// Overwrite the Pull Request title on GitHub with the local title, even
+    /// if the title was edited on GitHub since spr last set it
+    #[clap(long)]
+    force_title: bool,
+
+    /// Update the Pull Request even if its branch was changed on GitHub
+    /// since spr last pushed it, overwriting those changes
+    #[clap(long)]
+    force: bool,
+
     /// Submit any new Pull Request as a draft
     #[clap(long)]
     draft: bool,
 
+    /// Mark existing draft Pull Requests as ready for review
+    #[clap(long, conflicts_with = "draft")]
+    ready: bool,
+
     /// Message to be used for commits updating existing pull requests (e.g.
     /// 'rebase' or 'review comments')
     #[clap(long, short = 'm')]
     message: Option<String>,
 
+    /// Use this title and description for the Pull Request on GitHub instead
+    /// of the ones in the local commit message, for this run only. The local
+    /// commit message is left as it is
+    #[clap(long, value_name = "MESSAGE", conflicts_with_all = ["all", "split_by_path"])]
+    pr_message: Option<String>,
+
     /// Submit this commit as if it was cherry-picked on master. Do not base it
     /// on any intermediate changes between the master branch and this commit.
+    /// `spr land` then lands it the same way without needing --cherry-pick
     #[clap(long)]
     cherry_pick: bool,
 
-    /// Base revision for --all mode (if not specified, uses trunk)
+    /// Base revision for --all mode (if not specified, the stack starts
+    /// where the revision branched off master)
     #[clap(long)]
     base: Option<String>,
 
+    /// Open the Pull Requests against this branch instead of the master
+    /// branch, e.g. a release branch. The branch must exist on GitHub. It
+    /// is remembered, so that later updates and `spr land` use it, too
+    #[clap(long, value_name = "BRANCH")]
+    base_branch: Option<String>,
+
+    /// In --all mode, only submit the lowest N commits of the stack (counted
+    /// from the master branch) and leave the ones above them local
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    bottom: Option<u64>,
+
+    /// Experimental: split the commit into a stack of Pull Requests by path.
+    /// Takes rules of the form '<glob>=<name>' and can be given multiple
+    /// times. Files matching no rule go into the topmost Pull Request
+    #[clap(
+        long,
+        value_name = "GLOB=NAME",
+        conflicts_with_all = ["all", "base", "bottom"]
+    )]
+    split_by_path: Vec<String>,
+
+    /// In --all mode, send Pull Request updates and reviewer requests to
+    /// GitHub up to N at a time, after all branches have been pushed in order
+    #[clap(long, value_name = "N", value_parser = clap::value_parser!(u64).range(1..))]
+    parallel: Option<u64>,
+
+    /// Request a review from a user, or from a team given as 'org/team'.
+    /// Can be given multiple times
+    #[clap(long, value_name = "USER|ORG/TEAM")]
+    reviewer: Vec<String>,
+
+    /// Add a label to the Pull Request. Can be given multiple times
+    #[clap(long)]
+    label: Vec<String>,
+
+    /// Assign the Pull Request to a user, replacing the Assignees section of
+    /// the commit message. Can be given multiple times
+    #[clap(long, value_name = "USER")]
+    assignee: Vec<String>,
+
+    /// Add the Pull Request to the milestone with this title, replacing the
+    /// Milestone section of the commit message
+    #[clap(long, value_name = "TITLE")]
+    milestone: Option<String>,
+
+    /// Run the pre-push hook when pushing Pull Request branches
+    #[clap(long, overrides_with = "no_verify")]
+    verify: bool,
+
+    /// Skip the pre-push hook when pushing Pull Request branches (the
+    /// default, unless spr.pushNoVerify is false)
+    #[clap(long, overrides_with = "verify")]
+    no_verify: bool,
+
+    /// Open the Pull Request in the browser when done (the topmost one, in
+    /// --all mode)
+    #[clap(long, overrides_with = "no_open")]
+    open: bool,
+
+    /// Do not open the Pull Request in the browser, even if
+    /// spr.openAfterDiff is set
+    #[clap(long, overrides_with = "open")]
+    no_open: bool,
+
+    /// Put the repository's Pull Request template into the Summary of new
+    /// Pull Requests whose commit message has none
+    #[clap(long, overrides_with = "no_template")]
+    template: bool,
+
+    /// Do not use the Pull Request template, even if
+    /// spr.usePullRequestTemplate is set
+    #[clap(long, overrides_with = "template")]
+    no_template: bool,
+
     /// Jujutsu revision(s) to operate on. Can be a single revision like '@' or a range like 'main..@' or 'a::c'.
     /// If a range is provided, behaves like --all mode. If not specified, uses '@-'.
     #[clap(short = 'r', long)]
     revision: Option<String>,
 }
 
+/// Bring the metadata of an existing Pull Request up to date after its
+/// branch was pushed (or, with `--no-push`, without pushing): its draft
+/// state, assignees, milestone and labels, and the reviewers given with
+/// `--reviewer`.
+#[allow(clippy::too_many_arguments)]
+async fn update_pull_request_metadata(
+    opts: &DiffOptions,
+    gh: &mut GitHub,
+    config: &crate::config::Config,
+    store: &mut MappingStore,
+    mut deferred: Option<&mut Vec<MetadataUpdate>>,
+    pull_request: &PullRequest,
+    message: &MessageSectionsMap,
+    flag_reviewers: PullRequestRequestReviewers,
+) -> Result<()> {
+    if opts.ready {
+        gh.mark_ready_for_review(pull_request.number).await?;
+        output(
+            "🚀",
+            &format!("Marked #{} as ready for review", pull_request.number),
+        )?;
+        if let Some(update) = draft_label_update(config, pull_request.number, false, true) {
+            send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+        }
+    }
+
+    if let Some(update) = assignees_update(gh, pull_request.number, message).await? {
+        send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+    }
+    if let Some(update) = milestone_update(gh, pull_request.number, message).await? {
+        send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+    }
+    if let Some(update) = labels_update(
+        gh,
+        config,
+        store,
+        pull_request.number,
+        &pull_request.labels,
+        labels_to_add(config, opts, message),
+    )
+    .await?
+    {
+        send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+    }
+
+    if !opts.reviewer.is_empty() {
+        send_or_defer(
+            MetadataUpdate::RequestReviewers {
+                number: pull_request.number,
+                reviewers: flag_reviewers,
+                re_request: false,
+            },
+            deferred,
+            gh,
+            store,
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+/// Give commits without a Pull Request section the Pull Request that was
+/// created for their change with `--no-update-message`, if any.
+fn add_stored_pull_requests(
+    jj: &crate::jj::Jujutsu,
+    config: &crate::config::Config,
+    store: &MappingStore,
+    prepared_commits: &mut [crate::jj::PreparedCommit],
+) -> Result<()> {
+    let oids: Vec<Oid> = prepared_commits
+        .iter()
+        .filter(|commit| commit.pull_request_number.is_none())
+        .map(|commit| commit.oid)
+        .collect();
+    if oids.is_empty() || !store.has_change_pull_requests() {
+        return Ok(());
+    }
+
+    let change_ids = jj.get_change_ids_for_range(&oids)?;
+    for commit in prepared_commits.iter_mut() {
+        if commit.pull_request_number.is_some() {
+            continue;
+        }
+        if let Some(number) = change_ids
+            .get(&commit.oid)
+            .and_then(|change_id| store.change_pull_request(change_id))
+        {
+            commit.pull_request_number = Some(number);
+            commit
+                .message
+                .insert(MessageSection::PullRequest, config.pull_request_url(number));
+        }
+    }
+
+    Ok(())
+}
+
+/// A change to a Pull Request on GitHub that does not depend on the order of
+/// the stack. With `--parallel`, these are collected while the branches are
+/// pushed one after the other, and sent concurrently afterwards.
+#[derive(Debug)]
+enum MetadataUpdate {
+    Update {
+        number: u64,
+        updates: PullRequestUpdate,
+        /// Only the message changed, which is reported to the user
+        message_only: bool,
+    },
+    RequestReviewers {
+        number: u64,
+        reviewers: PullRequestRequestReviewers,
+        /// Asking previous approvers to review again
+        re_request: bool,
+    },
+    /// Replace the assignees
+    Assignees { number: u64, assignees: Vec<String> },
+    /// Set the milestone
+    Milestone {
+        number: u64,
+        milestone: u64,
+        title: String,
+    },
+    /// Add labels that exist in the repository, and remove ones spr added
+    /// earlier that are not wanted anymore
+    Labels {
+        number: u64,
+        labels: Vec<String>,
+        remove: Vec<String>,
+        /// The labels spr has added once this is done
+        record: Vec<String>,
+    },
+    /// Add or remove the `spr.draftLabel` label
+    DraftLabel {
+        number: u64,
+        label: String,
+        add: bool,
+    },
+}
+
+impl MetadataUpdate {
+    async fn send(&self, gh: &GitHub) -> Result<()> {
+        match self {
+            MetadataUpdate::Update {
+                number, updates, ..
+            } => gh.update_pull_request(*number, updates.clone()).await,
+            MetadataUpdate::RequestReviewers {
+                number, reviewers, ..
+            } => gh.request_reviewers(*number, reviewers.clone()).await,
+            MetadataUpdate::Assignees { number, assignees } => {
+                gh.set_assignees(*number, assignees).await
+            }
+            MetadataUpdate::Milestone {
+                number, milestone, ..
+            } => gh.set_milestone(*number, *milestone).await,
+            MetadataUpdate::Labels {
+                number,
+                labels,
+                remove,
+                ..
+            } => {
+                if !labels.is_empty() {
+                    gh.add_labels(*number, labels).await?;
+                }
+                for label in remove {
+                    gh.remove_label(*number, label).await?;
+                }
+                Ok(())
+            }
+            MetadataUpdate::DraftLabel { number, label, add } => {
+                if *add {
+                    gh.add_labels(*number, std::slice::from_ref(label)).await
+                } else {
+                    gh.remove_label(*number, label).await
+                }
+            }
+        }
+    }
+
+    /// Report the result of [`MetadataUpdate::send`]. Failing to request
+    /// reviewers or to change labels is only a warning.
+    fn finish(&self, result: Result<()>, store: &mut MappingStore) -> Result<()> {
+        match self {
+            MetadataUpdate::Update {
+                number,
+                updates,
+                message_only,
+            } => {
+                result?;
+                if *message_only {
+                    output("✍", "Updated commit message on GitHub")?;
+                }
+                if let Some(title) = &updates.title {
+                    store.set_title(*number, title)?;
+                }
+            }
+            MetadataUpdate::RequestReviewers {
+                number,
+                reviewers,
+                re_request,
+            } => match result {
+                Ok(()) if *re_request => output(
+                    "👀",
+                    &format!(
+                        "Re-requested review of #{} from {}",
+                        number,
+                        reviewers.reviewers.join(", ")
+                    ),
+                )?,
+                Ok(()) => (),
+                Err(error) => {
+                    output(
+                        "⚠️",
+                        &format!(
+                            "{} reviewers of #{} failed",
+                            if *re_request {
+                                "Re-requesting"
+                            } else {
+                                "Requesting"
+                            },
+                            number
+                        ),
+                    )?;
+                    for message in error.messages() {
+                        output("  ", message)?;
+                    }
+                }
+            },
+            MetadataUpdate::Assignees { number, assignees } => {
+                if let Err(error) = result {
+                    output(
+                        "⚠️",
+                        &format!("Assigning #{} to {} failed", number, assignees.join(", ")),
+                    )?;
+                    for message in error.messages() {
+                        output("  ", message)?;
+                    }
+                }
+            }
+            MetadataUpdate::Milestone { number, title, .. } => {
+                if let Err(error) = result {
+                    output(
+                        "⚠️",
+                        &format!("Setting the milestone of #{} to {} failed", number, title),
+                    )?;
+                    for message in error.messages() {
+                        output("  ", message)?;
+                    }
+                }
+            }
+            MetadataUpdate::Labels {
+                number,
+                remove,
+                record,
+                ..
+            } => match result {
+                Ok(()) => {
+                    if !remove.is_empty() {
+                        output(
+                            "🏷",
+                            &format!("Removed labels {} from #{}", remove.join(", "), number),
+                        )?;
+                    }
+                    store.set_labels(*number, record)?;
+                }
+                Err(error) => {
+                    output("⚠️", &format!("Updating labels of #{} failed", number))?;
+                    for message in error.messages() {
+                        output("  ", message)?;
+                    }
+                }
+            },
+            MetadataUpdate::DraftLabel { number, label, add } => {
+                if let Err(error) = result {
+                    output(
+                        "⚠️",
+                        &format!(
+                            "{} label '{}' {} #{} failed",
+                            if *add { "Adding" } else { "Removing" },
+                            label,
+                            if *add { "to" } else { "from" },
+                            number
+                        ),
+                    )?;
+                    for message in error.messages() {
+                        output("  ", message)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Send a metadata update right away, or queue it if `deferred` is given.
+async fn send_or_defer(
+    update: MetadataUpdate,
+    deferred: Option<&mut Vec<MetadataUpdate>>,
+    gh: &GitHub,
+    store: &mut MappingStore,
+) -> Result<()> {
+    match deferred {
+        Some(deferred) => {
+            deferred.push(update);
+            Ok(())
+        }
+        None => {
+            let result = update.send(gh).await;
+            update.finish(result, store)
+        }
+    }
+}
+
 pub async fn diff(
     opts: DiffOptions,
     jj: &crate::jj::Jujutsu,
@@ -65,21 +512,97 @@ pub async fn diff(
 
     let mut result = Ok(());
 
-    // Determine revision and whether to use range mode
-    let (use_range_mode, base_rev, target_rev, is_inclusive) =
-        crate::revision_utils::parse_revision_and_range(
-            opts.revision.as_deref(),
-            opts.all,
-            opts.base.as_deref(),
-        )?;
+    let (use_range_mode, mut prepared_commits) = jj.get_prepared_commits_for_options(
+        config,
+        opts.revision.as_deref(),
+        opts.all,
+        opts.base.as_deref(),
+    )?;
 
-    // Get commits to process
-    let mut prepared_commits = if use_range_mode {
-        // Get range of commits from base to target
-        jj.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?
-    } else {
-        // Just get the single specified revision
-        vec![jj.get_prepared_commit_for_revision(config, &target_rev)?]
+    if opts.pr_message.is_some() && use_range_mode {
+        return Err(Error::new(
+            "--pr-message can only be used with a single revision",
+        ));
+    }
+
+    if !opts.split_by_path.is_empty() {
+        if use_range_mode {
+            return Err(Error::new(
+                "--split-by-path can only be used with a single revision",
+            ));
+        }
+
+        let commit = prepared_commits.remove(0);
+        prepared_commits = split_commit_by_path(jj, config, &opts.split_by_path, commit)?;
+    }
+
+    if let Some(bottom) = opts.bottom {
+        if !use_range_mode {
+            return Err(Error::new(
+                "--bottom can only be used with --all or a revision range",
+            ));
+        }
+
+        let total = prepared_commits.len();
+        let skipped = keep_bottom_commits(&mut prepared_commits, bottom as usize);
+        if skipped > 0 {
+            output(
+                "📚",
+                &format!(
+                    "Submitting the bottom {} of {} commits, leaving {} local",
+                    prepared_commits.len(),
+                    total,
+                    skipped
+                ),
+            )?;
+        }
+    }
+
+    let mut store = MappingStore::open(&jj.git_repo)?;
+    add_stored_pull_requests(jj, config, &store, &mut prepared_commits)?;
+
+    if opts.no_push
+        && let Some(commit) = prepared_commits
+            .iter()
+            .find(|commit| commit.pull_request_number.is_none())
+    {
+        write_commit_title(commit)?;
+        return Err(Error::new(
+            "This commit has no Pull Request yet. Run `spr diff` without \
+             --no-push to create one.",
+        ));
+    }
+
+    // With a base branch other than master (given now, or when the Pull
+    // Requests were created), that branch takes the place of master
+    let mut base_branch = opts.base_branch.clone().or_else(|| {
+        prepared_commits
+            .iter()
+            .find_map(|commit| store.base_branch(commit.pull_request_number?))
+    });
+    // With a revset of trunks, the stack goes to the trunk it is based on
+    if base_branch.is_none()
+        && let Some(top) = prepared_commits.last()
+    {
+        base_branch = jj.get_trunk_branch_for_commit(config, top.oid)?;
+    }
+    let base_branch_config;
+    let mut base_branch_gh;
+    let (config, gh) = match &base_branch {
+        Some(branch) if branch != config.master_ref.branch_name() => {
+            let remote_ref = format!("refs/remotes/{}/{}", config.remote_name, branch);
+            if !jj.get_all_ref_names()?.contains(&remote_ref) {
+                return Err(Error::new(format!(
+                    "Branch '{}' does not exist on remote '{}'. Push it, or run \
+                     `jj git fetch` if it was just created.",
+                    branch, config.remote_name
+                )));
+            }
+            base_branch_config = config.with_master_branch(branch);
+            base_branch_gh = gh.with_config(base_branch_config.clone());
+            (&base_branch_config, &mut base_branch_gh)
+        }
+        _ => (config, gh),
     };
 
     // Determine the master base OID - this is the commit on master that the stack is based on
@@ -96,6 +619,43 @@ pub async fn diff(
         return result;
     };
 
+    // Start new Pull Requests without a summary from the repository's
+    // template, which then ends up in the local commit message, too
+    if use_pull_request_template(&opts, config)
+        && let Some(template) = jj.git_repo.workdir().and_then(read_pull_request_template)
+    {
+        for prepared_commit in prepared_commits.iter_mut() {
+            if prepared_commit.pull_request_number.is_none()
+                && insert_pull_request_template(&mut prepared_commit.message, &template)
+            {
+                prepared_commit.message_changed = true;
+            }
+        }
+    }
+
+    // Check all commit messages before anything is pushed, so that a bad
+    // message further up the stack doesn't leave the stack half submitted.
+    // (With --sync-message the message is checked once it was merged with
+    // the Pull Request's.)
+    let mut messages_valid = true;
+    for prepared_commit in prepared_commits.iter() {
+        if prepared_commit.pull_request_number.is_some() && !opts.update_message {
+            continue;
+        }
+        let check_config = message_check_config(jj, config, prepared_commit.oid)?;
+        let problems = commit_message_problems(&prepared_commit.message, &check_config);
+        if !problems.is_empty() {
+            write_commit_title(prepared_commit)?;
+            for problem in &problems {
+                output("💔", problem)?;
+            }
+            messages_valid = false;
+        }
+    }
+    if !messages_valid {
+        return Err(Error::empty());
+    }
+
     #[allow(clippy::needless_collect)]
     let pull_request_tasks: Vec<_> = prepared_commits
         .iter()
@@ -106,9 +666,9 @@ pub async fn diff(
         .collect();
 
     let mut message_on_prompt = "".to_string();
+    let mut deferred = Vec::new();
 
-    for (prepared_commit, pull_request_task) in
-        zip(prepared_commits.iter_mut(), pull_request_tasks.into_iter())
+    for (prepared_commit, pull_request_task) in zip(prepared_commits.iter_mut(), pull_request_tasks)
     {
         if result.is_err() {
             break;
@@ -132,6 +692,8 @@ pub async fn diff(
             jj,
             gh,
             config,
+            &mut store,
+            opts.parallel.is_some().then_some(&mut deferred),
             prepared_commit,
             master_base_oid,
             pull_request,
@@ -139,12 +701,53 @@ pub async fn diff(
         .await;
     }
 
+    if let Some(parallel) = opts.parallel
+        && !deferred.is_empty()
+    {
+        // All branches are pushed (or we stopped at an error). Send what we
+        // collected for the Pull Requests that got that far.
+        let results = run_bounded(
+            deferred.iter().map(|update| update.send(gh)),
+            parallel as usize,
+        )
+        .await;
+        for (update, update_result) in zip(&deferred, results) {
+            add_error(&mut result, update.finish(update_result, &mut store));
+        }
+    }
+
     // This updates the commit message in the local Jujutsu repository (if it was
     // changed by the implementation)
-    add_error(
-        &mut result,
-        jj.rewrite_commit_messages(prepared_commits.as_mut_slice()),
-    );
+    if !opts.no_update_message {
+        add_error(
+            &mut result,
+            jj.rewrite_commit_messages(config, prepared_commits.as_mut_slice()),
+        );
+    }
+
+    if let Some(branch) = &base_branch {
+        for commit in prepared_commits.iter() {
+            if let Some(number) = commit
+                .message
+                .get(&MessageSection::PullRequest)
+                .and_then(|url| config.parse_pull_request_field(url))
+            {
+                add_error(&mut result, store.set_base_branch(number, branch));
+            }
+        }
+    }
+
+    if result.is_ok()
+        && open_after_diff(&opts, config)
+        && let Some(number) = prepared_commits.iter().rev().find_map(|commit| {
+            commit
+                .message
+                .get(&MessageSection::PullRequest)
+                .and_then(|url| config.parse_pull_request_field(url))
+        })
+    {
+        open_in_browser(&config.pull_request_url(number));
+    }
 
     result
 }
@@ -156,6 +759,8 @@ async fn diff_impl(
     jj: &crate::jj::Jujutsu,
     gh: &mut crate::github::GitHub,
     config: &crate::config::Config,
+    store: &mut MappingStore,
+    mut deferred: Option<&mut Vec<MetadataUpdate>>,
     local_commit: &mut crate::jj::PreparedCommit,
     master_base_oid: Oid,
     pull_request: Option<PullRequest>,
@@ -211,10 +816,96 @@ async fn diff_impl(
         )?;
     }
 
-    if local_commit.pull_request_number.is_none() || opts.update_message {
-        validate_commit_message(message, config)?;
+    if opts.sync_message
+        && let Some(pull_request) = &pull_request
+    {
+        let base = store
+            .message(&jj.git_repo, pull_request.number)
+            .map(|base| parse_message(&base, MessageSection::Title, config));
+        let before = message.clone();
+        let conflicts = merge_remote_sections(base.as_ref(), message, &pull_request.sections);
+        if *message != before {
+            output("⬇️", "Took changes made on GitHub into the commit message")?;
+            local_commit.message_changed = true;
+        }
+        for section in conflicts {
+            output(
+                "⚠️",
+                &format!(
+                    "The {} section was changed both locally and on GitHub. \
+                     Keeping the local one.",
+                    message_section_label(&section, config)
+                ),
+            )?;
+        }
     }
 
+    if opts.summary_from_body
+        && let Some(pull_request) = &pull_request
+    {
+        let base = store
+            .message(&jj.git_repo, pull_request.number)
+            .map(|base| parse_message(&base, MessageSection::Title, config));
+        match merge_remote_section(
+            base.as_ref(),
+            message,
+            &pull_request.sections,
+            MessageSection::Summary,
+        ) {
+            SectionMerge::TookRemote => {
+                output("⬇️", "Took the Summary from GitHub into the commit message")?;
+                local_commit.message_changed = true;
+            }
+            SectionMerge::Conflict => output(
+                "⚠️",
+                "The Summary section was changed both locally and on GitHub. \
+                 Keeping the local one.",
+            )?,
+            SectionMerge::Unchanged => (),
+        }
+    }
+
+    // A message given with --pr-message goes to GitHub, so it always updates
+    // an existing Pull Request. The mapping store keeps the last local
+    // message, so that `spr amend` later sees the change made on GitHub.
+    let update_message = opts.update_message
+        || opts.sync_message
+        || opts.summary_from_body
+        || opts.no_push
+        || opts.pr_message.is_some();
+    let record_message = update_message && opts.pr_message.is_none();
+
+    if local_commit.pull_request_number.is_none()
+        || opts.update_message
+        || opts.sync_message
+        || opts.summary_from_body
+        || opts.no_push
+    {
+        let check_config = message_check_config(jj, config, local_commit.oid)?;
+        validate_commit_message(message, &check_config)?;
+    }
+
+    // The message as it goes to GitHub. With spr.uploadAssets, links to
+    // local images point at copies uploaded next to the Pull Request branch.
+    let images = if config.upload_assets {
+        assets::prepare_images(&jj.git_repo, config, message)?
+    } else {
+        None
+    };
+    let github_message = match images {
+        Some((github_message, images_commit)) => {
+            if local_commit.pull_request_number.is_none() || update_message {
+                assets::push_images(config, images_commit).await?;
+            }
+            github_message
+        }
+        None => message.clone(),
+    };
+    let github_message = match &opts.pr_message {
+        Some(text) => override_github_message(&github_message, text, config),
+        None => github_message,
+    };
+
     if let Some(ref pull_request) = pull_request {
         if pull_request.state == PullRequestState::Closed {
             return Err(Error::new(formatdoc!(
@@ -223,9 +914,9 @@ async fn diff_impl(
             )));
         }
 
-        if !opts.update_message {
+        if !update_message {
             let mut pull_request_updates: PullRequestUpdate = Default::default();
-            pull_request_updates.update_message(pull_request, message);
+            pull_request_updates.update_message(pull_request, &github_message, config);
 
             if !pull_request_updates.is_empty() {
                 output(
@@ -243,6 +934,63 @@ async fn diff_impl(
         }
     }
 
+    // Reviewers given with --reviewer are requested for new and existing
+    // Pull Requests alike
+    let flag_reviewers = reviewers_from_flags(&opts.reviewer, &config.owner)?;
+
+    if opts.no_push
+        && let Some(pull_request) = &pull_request
+    {
+        output(
+            "📝",
+            &format!(
+                "Updating Pull Request #{} without pushing",
+                pull_request.number
+            ),
+        )?;
+
+        let mut pull_request_updates: PullRequestUpdate = Default::default();
+        pull_request_updates.update_message(pull_request, &github_message, config);
+        keep_title_edited_on_github(
+            &mut pull_request_updates,
+            pull_request,
+            store,
+            opts.force_title,
+        )?;
+        if !pull_request_updates.is_empty() {
+            send_or_defer(
+                MetadataUpdate::Update {
+                    number: pull_request.number,
+                    updates: pull_request_updates,
+                    message_only: true,
+                },
+                deferred.as_deref_mut(),
+                gh,
+                store,
+            )
+            .await?;
+        }
+        if record_message {
+            store.set_message(
+                &jj.git_repo,
+                pull_request.number,
+                &build_pull_request_message(message, config),
+            )?;
+        }
+
+        return update_pull_request_metadata(
+            opts,
+            gh,
+            config,
+            store,
+            deferred,
+            pull_request,
+            message,
+            flag_reviewers,
+        )
+        .await;
+    }
+
     // Parse "Reviewers" section, if this is a new Pull Request
     let mut requested_reviewers = PullRequestRequestReviewers::default();
 
@@ -291,6 +1039,22 @@ async fn diff_impl(
         local_commit.message_changed = true;
     }
 
+    // Assignees given with --assignee replace the ones in the commit message
+    if let Some(assignees) = assignees_from_flags(&opts.assignee)
+        && message.get(&MessageSection::Assignees) != Some(&assignees)
+    {
+        message.insert(MessageSection::Assignees, assignees);
+        local_commit.message_changed = true;
+    }
+    // So does the milestone given with --milestone
+    if let Some(milestone) = opts.milestone.as_deref().map(str::trim)
+        && !milestone.is_empty()
+        && message.get(&MessageSection::Milestone).map(String::as_str) != Some(milestone)
+    {
+        message.insert(MessageSection::Milestone, milestone.to_string());
+        local_commit.message_changed = true;
+    }
+
     // Get the name of the existing Pull Request branch, or constuct one if
     // there is none yet.
 
@@ -299,10 +1063,56 @@ async fn diff_impl(
         .map(|t| &t[..])
         .unwrap_or("");
 
-    let pull_request_branch = match &pull_request {
-        Some(pr) => pr.head.clone(),
-        None => {
-            config.new_github_branch(&config.get_new_branch_name(&jj.get_all_ref_names()?, title))
+    // A bookmark that is already on the remote is left alone: spr pushes its
+    // own commits to the branch, which are no fast-forward of the bookmark.
+    let mut existing_bookmark = None;
+    if pull_request.is_none() && config.prefer_existing_bookmark {
+        let ref_names = jj.get_all_ref_names()?;
+        for name in jj.get_bookmarks_for_commit(local_commit.oid)? {
+            if name.starts_with(&config.branch_prefix) || name == config.master_ref.branch_name() {
+                continue;
+            }
+            if ref_names.contains(&format!("refs/remotes/{}/{}", config.remote_name, name)) {
+                output(
+                    "⚠️",
+                    &format!(
+                        "Bookmark {} is already on {}, so it can't be the branch of the \
+                         Pull Request. Using a new branch instead.",
+                        name, config.remote_name
+                    ),
+                )?;
+                continue;
+            }
+            existing_bookmark = Some(name);
+            break;
+        }
+    }
+
+    let pull_request_branch = match (&pull_request, existing_bookmark) {
+        (Some(pr), _) => pr.head.clone(),
+        (None, Some(bookmark)) => {
+            output(
+                "🔖",
+                &format!(
+                    "Using bookmark {} as the branch. spr manages it from now on, and \
+                     deletes it on GitHub after landing.",
+                    bookmark
+                ),
+            )?;
+            config.new_github_branch(&bookmark)
+        }
+        (None, None) => {
+            let change_id = match &config.branch_name_template {
+                Some(template) if template.contains("{change-id}") => {
+                    jj.get_change_id_for_commit(local_commit.oid)?
+                }
+                _ => String::new(),
+            };
+            config.new_github_branch(&config.get_new_branch_name(
+                &jj.get_all_ref_names()?,
+                title,
+                &change_id,
+            ))
         }
     };
 
@@ -347,21 +1157,43 @@ async fn diff_impl(
         // So there is an existing Pull Request...
         if !needs_merging_master && pr_head_tree == new_head_tree && pr_base_tree == new_base_tree {
             // ...and it does not need a rebase, and the trees of both Pull
-            // Request branch and base are all the right ones.
-            output("✅", "No update necessary")?;
+            // Request branch and base are all the right ones. Pushing a new
+            // commit anyway would only notify the reviewers for nothing.
+            output("✅", "No changes to push - the Pull Request is up to date")?;
 
-            if opts.update_message {
+            if update_message {
                 // However, the user requested to update the commit message on
                 // GitHub
 
                 let mut pull_request_updates: PullRequestUpdate = Default::default();
-                pull_request_updates.update_message(pull_request, message);
+                pull_request_updates.update_message(pull_request, &github_message, config);
+                keep_title_edited_on_github(
+                    &mut pull_request_updates,
+                    pull_request,
+                    store,
+                    opts.force_title,
+                )?;
 
                 if !pull_request_updates.is_empty() {
                     // ...and there are actual changes to the message
-                    gh.update_pull_request(pull_request.number, pull_request_updates)
-                        .await?;
-                    output("✍", "Updated commit message on GitHub")?;
+                    send_or_defer(
+                        MetadataUpdate::Update {
+                            number: pull_request.number,
+                            updates: pull_request_updates,
+                            message_only: true,
+                        },
+                        deferred,
+                        gh,
+                        store,
+                    )
+                    .await?;
+                }
+                if record_message {
+                    store.set_message(
+                        &jj.git_repo,
+                        pull_request.number,
+                        &build_pull_request_message(message, config),
+                    )?;
                 }
             }
 
@@ -369,6 +1201,12 @@ async fn diff_impl(
         }
     }
 
+    // Don't overwrite commits that someone else pushed to the Pull Request
+    // branch
+    if let Some(ref pull_request) = pull_request {
+        check_head_unchanged(pull_request, store.head(pull_request.number), opts.force)?;
+    }
+
     // Check if there is a base branch on GitHub already. That's the case when
     // there is an existing Pull Request, and its base is not the master branch.
     let base_branch = if let Some(ref pr) = pull_request {
@@ -443,8 +1281,21 @@ async fn diff_impl(
             parents.push(master_base_oid);
         }
 
+        // The Pull Request of the parent commit, if the local commit is
+        // stacked on top of another one
+        let base_pull_request_number = match local_commit.base_oid {
+            Some(base_oid) => jj.get_pull_request_number_for_commit(config, base_oid)?,
+            None => None,
+        };
+
         let mut message = if pull_request.is_some() {
             "Changes introduced through rebase".to_string()
+        } else if let Some(number) = base_pull_request_number {
+            output("📚", &format!("Stacked on Pull Request #{}", number))?;
+            format!(
+                "Changes from Pull Request #{} this commit is based on",
+                number
+            )
         } else {
             format!(
                 "Changes to {} this commit is based on",
@@ -468,11 +1319,24 @@ async fn diff_impl(
             &message,
             new_base_tree,
             &parents[..],
+            false,
         )?;
 
         // If `base_branch` is `None` (which means a base branch does not exist
         // yet), then make a `GitHubBranch` with a new name for a base branch
         let base_branch = if let Some(base_branch) = base_branch {
+            // The new commit is pushed to the base branch below. Only do that
+            // to a branch spr made, never to someone else's.
+            if !base_branch.branch_name().starts_with(&config.branch_prefix) {
+                return Err(Error::new(format!(
+                    "The Pull Request is open against '{}', not '{}', and spr \
+                     would have to push to that branch. Pass --base-branch {} \
+                     to keep it there.",
+                    base_branch.branch_name(),
+                    config.master_ref.branch_name(),
+                    base_branch.branch_name(),
+                )));
+            }
             base_branch
         } else {
             config.new_github_branch(&config.get_base_branch_name(&jj.get_all_ref_names()?, title))
@@ -541,15 +1405,20 @@ async fn diff_impl(
         &commit_message,
         new_head_tree,
         &pr_commit_parents[..],
+        false,
     )?;
 
     let mut cmd = tokio::process::Command::new("git");
-    cmd.arg("push")
-        .arg("--atomic")
-        .arg("--no-verify")
-        .arg("--")
-        .arg(&config.remote_name)
-        .arg(format!("{}:{}", pr_commit, pull_request_branch.on_github()));
+    cmd.arg("push").arg("--atomic");
+    if push_no_verify(opts, config) {
+        cmd.arg("--no-verify");
+    }
+    cmd.arg("--").arg(&config.remote_name).arg(format!(
+        "{}{}:{}",
+        if opts.force { "+" } else { "" },
+        pr_commit,
+        pull_request_branch.on_github()
+    ));
 
     if let Some(pull_request) = pull_request {
         // We are updating an existing Pull Request
@@ -575,8 +1444,14 @@ async fn diff_impl(
         // Things we want to update in the Pull Request on GitHub
         let mut pull_request_updates: PullRequestUpdate = Default::default();
 
-        if opts.update_message {
-            pull_request_updates.update_message(&pull_request, message);
+        if update_message {
+            pull_request_updates.update_message(&pull_request, &github_message, config);
+            keep_title_edited_on_github(
+                &mut pull_request_updates,
+                &pull_request,
+                store,
+                opts.force_title,
+            )?;
         }
 
         if let Some(base_branch) = base_branch {
@@ -610,11 +1485,63 @@ async fn diff_impl(
                 .await
                 .reword("git push failed".to_string())?;
         }
+        store.set_head(pull_request.number, pr_commit)?;
+        store.set_cherry_pick(pull_request.number, opts.cherry_pick)?;
 
         if !pull_request_updates.is_empty() {
-            gh.update_pull_request(pull_request.number, pull_request_updates)
+            send_or_defer(
+                MetadataUpdate::Update {
+                    number: pull_request.number,
+                    updates: pull_request_updates,
+                    message_only: false,
+                },
+                deferred.as_deref_mut(),
+                gh,
+                store,
+            )
+            .await?;
+        }
+        if record_message {
+            store.set_message(
+                &jj.git_repo,
+                pull_request.number,
+                &build_pull_request_message(message, config),
+            )?;
+        }
+
+        if config.re_request_on_update {
+            // Pushing an update may have dismissed earlier approvals, so ask
+            // the approvers to take another look.
+            let approvers = previous_approvers(&pull_request.reviewers);
+            if !approvers.is_empty() {
+                send_or_defer(
+                    MetadataUpdate::RequestReviewers {
+                        number: pull_request.number,
+                        reviewers: PullRequestRequestReviewers {
+                            reviewers: approvers,
+                            team_reviewers: Vec::new(),
+                        },
+                        re_request: true,
+                    },
+                    deferred.as_deref_mut(),
+                    gh,
+                    store,
+                )
                 .await?;
+            }
         }
+
+        update_pull_request_metadata(
+            opts,
+            gh,
+            config,
+            store,
+            deferred,
+            &pull_request,
+            message,
+            flag_reviewers,
+        )
+        .await?;
     } else {
         // We are creating a new Pull Request.
 
@@ -634,7 +1561,7 @@ async fn diff_impl(
         // Then call GitHub to create the Pull Request.
         let pull_request_number = gh
             .create_pull_request(
-                message,
+                &github_message,
                 base_branch
                     .as_ref()
                     .unwrap_or(&config.master_ref)
@@ -645,6 +1572,23 @@ async fn diff_impl(
             )
             .await?;
 
+        if let Some(title) = github_message.get(&MessageSection::Title) {
+            store.set_title(pull_request_number, title)?;
+        }
+        store.set_message(
+            &jj.git_repo,
+            pull_request_number,
+            &build_pull_request_message(message, config),
+        )?;
+        store.set_head(pull_request_number, pr_commit)?;
+        store.set_cherry_pick(pull_request_number, opts.cherry_pick)?;
+        if opts.no_update_message {
+            store.set_change_pull_request(
+                &jj.get_change_id_for_commit(local_commit.oid)?,
+                pull_request_number,
+            )?;
+        }
+
         let pull_request_url = config.pull_request_url(pull_request_number);
 
         output(
@@ -658,44 +1602,626 @@ async fn diff_impl(
         message.insert(MessageSection::PullRequest, pull_request_url);
         local_commit.message_changed = true;
 
-        let result = gh
-            .request_reviewers(pull_request_number, requested_reviewers)
-            .await;
-        match result {
-            Ok(()) => (),
-            Err(error) => {
-                output("⚠️", "Requesting reviewers failed")?;
-                for message in error.messages() {
-                    output("  ", message)?;
-                }
+        if let Some(update) = draft_label_update(config, pull_request_number, opts.draft, false) {
+            send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+        }
+        if let Some(update) = assignees_update(gh, pull_request_number, message).await? {
+            send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+        }
+        if let Some(update) = milestone_update(gh, pull_request_number, message).await? {
+            send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+        }
+        if let Some(update) = labels_update(
+            gh,
+            config,
+            store,
+            pull_request_number,
+            &[],
+            labels_to_add(config, opts, message),
+        )
+        .await?
+        {
+            send_or_defer(update, deferred.as_deref_mut(), gh, store).await?;
+        }
+
+        for reviewer in flag_reviewers.reviewers {
+            if !requested_reviewers.reviewers.contains(&reviewer) {
+                requested_reviewers.reviewers.push(reviewer);
+            }
+        }
+        for team in flag_reviewers.team_reviewers {
+            if !requested_reviewers.team_reviewers.contains(&team) {
+                requested_reviewers.team_reviewers.push(team);
             }
         }
+
+        send_or_defer(
+            MetadataUpdate::RequestReviewers {
+                number: pull_request_number,
+                reviewers: requested_reviewers,
+                re_request: false,
+            },
+            deferred,
+            gh,
+            store,
+        )
+        .await?;
     }
 
     Ok(())
 }
 
+/// The config to check a commit message with. A commit that only changes
+/// paths in spr.testPlanExemptPaths doesn't need a test plan.
+fn message_check_config<'a>(
+    jj: &crate::jj::Jujutsu,
+    config: &'a crate::config::Config,
+    commit_oid: Oid,
+) -> Result<Cow<'a, crate::config::Config>> {
+    if config.require_test_plan
+        && !config.test_plan_exempt_paths.is_empty()
+        && config.is_test_plan_exempt(&jj.get_commit_diff(commit_oid)?)
+    {
+        Ok(Cow::Owned(crate::config::Config {
+            require_test_plan: false,
+            ..config.clone()
+        }))
+    } else {
+        Ok(Cow::Borrowed(config))
+    }
+}
+
+/// Whether to open the Pull Request in the browser after a successful run.
+fn open_after_diff(opts: &DiffOptions, config: &crate::config::Config) -> bool {
+    if opts.open {
+        true
+    } else if opts.no_open {
+        false
+    } else {
+        config.open_after_diff
+    }
+}
+
+/// Whether to fill in the Pull Request template: `--template` and
+/// `--no-template` win over `spr.usePullRequestTemplate`.
+fn use_pull_request_template(opts: &DiffOptions, config: &crate::config::Config) -> bool {
+    if opts.template {
+        true
+    } else if opts.no_template {
+        false
+    } else {
+        config.use_pull_request_template
+    }
+}
+
+/// The places GitHub looks for a Pull Request template, in order.
+const PULL_REQUEST_TEMPLATE_PATHS: &[&str] = &[
+    ".github/pull_request_template.md",
+    ".github/PULL_REQUEST_TEMPLATE.md",
+    "pull_request_template.md",
+    "PULL_REQUEST_TEMPLATE.md",
+    "docs/pull_request_template.md",
+    "docs/PULL_REQUEST_TEMPLATE.md",
+];
+
+/// The Pull Request template in the working copy at `workdir`, if any.
+fn read_pull_request_template(workdir: &Path) -> Option<String> {
+    PULL_REQUEST_TEMPLATE_PATHS.iter().find_map(|path| {
+        let template = std::fs::read_to_string(workdir.join(path)).ok()?;
+        let template = template.trim();
+        (!template.is_empty()).then(|| template.to_string())
+    })
+}
+
+/// Use the template as the Summary of the message, if it has none. Returns
+/// whether the message changed.
+fn insert_pull_request_template(message: &mut MessageSectionsMap, template: &str) -> bool {
+    if message
+        .get(&MessageSection::Summary)
+        .is_some_and(|summary| !summary.trim().is_empty())
+    {
+        return false;
+    }
+    message.insert(MessageSection::Summary, template.to_string());
+    true
+}
+
+/// Whether to skip the pre-push hook: `--verify` and `--no-verify` win over
+/// `spr.pushNoVerify`.
+fn push_no_verify(opts: &DiffOptions, config: &crate::config::Config) -> bool {
+    if opts.verify {
+        false
+    } else if opts.no_verify {
+        true
+    } else {
+        config.push_no_verify
+    }
+}
+
+/// The Assignees section for the users given with `--assignee`, if any.
+fn assignees_from_flags(flags: &[String]) -> Option<String> {
+    let mut assignees: Vec<&str> = Vec::new();
+    for flag in flags {
+        let assignee = flag.trim().trim_start_matches('@');
+        if !assignee.is_empty() && !assignees.contains(&assignee) {
+            assignees.push(assignee);
+        }
+    }
+    (!assignees.is_empty()).then(|| assignees.join(", "))
+}
+
+/// Assigning Pull Request `number` to the users in the Assignees section of
+/// `message`. Users that can't be assigned are left out with a warning.
+async fn assignees_update(
+    gh: &GitHub,
+    number: u64,
+    message: &MessageSectionsMap,
+) -> Result<Option<MetadataUpdate>> {
+    let Some(assignees) = message.get(&MessageSection::Assignees) else {
+        return Ok(None);
+    };
+    let mut assignees = parse_name_list(assignees);
+    if assignees.is_empty() {
+        return Ok(None);
+    }
+    let unassignable = gh.unassignable_users(&assignees).await?;
+    for login in &unassignable {
+        output(
+            "⚠️",
+            &format!("'{}' can't be assigned in this repository, skipping", login),
+        )?;
+    }
+    assignees.retain(|login| !unassignable.contains(login));
+
+    Ok((!assignees.is_empty()).then_some(MetadataUpdate::Assignees { number, assignees }))
+}
+
+/// Setting the milestone of Pull Request `number` to the one in the
+/// Milestone section of `message`. A milestone the repository doesn't have
+/// (open) is skipped with a warning.
+async fn milestone_update(
+    gh: &GitHub,
+    number: u64,
+    message: &MessageSectionsMap,
+) -> Result<Option<MetadataUpdate>> {
+    let Some(title) = message
+        .get(&MessageSection::Milestone)
+        .map(|title| title.trim())
+        .filter(|title| !title.is_empty())
+    else {
+        return Ok(None);
+    };
+    let Some(milestone) = gh.find_milestone(title).await? else {
+        output(
+            "⚠️",
+            &format!(
+                "There is no open milestone '{}' in this repository, skipping",
+                title
+            ),
+        )?;
+        return Ok(None);
+    };
+
+    Ok(Some(MetadataUpdate::Milestone {
+        number,
+        milestone,
+        title: title.to_string(),
+    }))
+}
+
+/// The labels to add to a Pull Request: `spr.defaultLabels`, those in the
+/// Labels section of the commit message and those given with `--label`.
+fn labels_to_add(
+    config: &crate::config::Config,
+    opts: &DiffOptions,
+    message: &MessageSectionsMap,
+) -> Vec<String> {
+    let message_labels = message
+        .get(&MessageSection::Labels)
+        .map(|labels| parse_label_list(labels))
+        .unwrap_or_default();
+    let mut labels: Vec<String> = Vec::new();
+    for label in config
+        .default_labels
+        .iter()
+        .chain(&message_labels)
+        .chain(&opts.label)
+    {
+        let label = label.trim();
+        if !label.is_empty() && !labels.iter().any(|l| l == label) {
+            labels.push(label.to_string());
+        }
+    }
+    labels
+}
+
+/// Adding `labels` to Pull Request `number`, which has the labels `current`
+/// on GitHub. GitHub would create labels that don't exist yet, so those are
+/// left out with a warning instead. With `spr.authoritativeLabels`, labels
+/// that spr added earlier but that are not in `labels` anymore are removed.
+async fn labels_update(
+    gh: &GitHub,
+    config: &crate::config::Config,
+    store: &MappingStore,
+    number: u64,
+    current: &[String],
+    mut labels: Vec<String>,
+) -> Result<Option<MetadataUpdate>> {
+    let previous = store.labels(number);
+    if labels.is_empty() && (!config.authoritative_labels || previous.is_empty()) {
+        return Ok(None);
+    }
+    // Labels the Pull Request has exist in the repository
+    let new_labels: Vec<String> = labels
+        .iter()
+        .filter(|label| !current.contains(label))
+        .cloned()
+        .collect();
+    let missing = gh.missing_labels(&new_labels).await?;
+    for label in &missing {
+        output(
+            "⚠️",
+            &format!(
+                "Label '{}' does not exist in the repository, not adding it",
+                label
+            ),
+        )?;
+    }
+    labels.retain(|label| !missing.contains(label));
+
+    let (remove, record) = reconcile_labels(&previous, &labels, config.authoritative_labels);
+    let (labels, remove) = label_changes(current, labels, remove);
+    // With nothing to send, the labels spr added may still change
+    if labels.is_empty() && remove.is_empty() && record == previous {
+        return Ok(None);
+    }
+
+    Ok(Some(MetadataUpdate::Labels {
+        number,
+        labels,
+        remove,
+        record,
+    }))
+}
+
+/// The labels to remove from a Pull Request that spr earlier added
+/// `previous` to and is now adding `labels` to, and the labels spr has added
+/// after that. Labels added on GitHub are never in `previous`, so they are
+/// left alone.
+fn reconcile_labels(
+    previous: &[String],
+    labels: &[String],
+    authoritative: bool,
+) -> (Vec<String>, Vec<String>) {
+    if authoritative {
+        let remove = previous
+            .iter()
+            .filter(|label| !labels.contains(label))
+            .cloned()
+            .collect();
+        (remove, labels.to_vec())
+    } else {
+        let mut record = previous.to_vec();
+        for label in labels {
+            if !record.contains(label) {
+                record.push(label.clone());
+            }
+        }
+        (Vec::new(), record)
+    }
+}
+
+/// Of the labels to add to and remove from a Pull Request that has the
+/// labels `current`, the ones that make a difference. Sending only those
+/// keeps GitHub from recording a label event on every update.
+fn label_changes(
+    current: &[String],
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> (Vec<String>, Vec<String>) {
+    (
+        add.into_iter()
+            .filter(|label| !current.contains(label))
+            .collect(),
+        remove
+            .into_iter()
+            .filter(|label| current.contains(label))
+            .collect(),
+    )
+}
+
+/// The change to the `spr.draftLabel` label, if any, after a Pull Request
+/// was created as a draft or marked as ready for review.
+fn draft_label_update(
+    config: &crate::config::Config,
+    number: u64,
+    created_draft: bool,
+    marked_ready: bool,
+) -> Option<MetadataUpdate> {
+    let label = config.draft_label.clone()?;
+    if created_draft {
+        Some(MetadataUpdate::DraftLabel {
+            number,
+            label,
+            add: true,
+        })
+    } else if marked_ready {
+        Some(MetadataUpdate::DraftLabel {
+            number,
+            label,
+            add: false,
+        })
+    } else {
+        None
+    }
+}
+
+/// Sort the values of `--reviewer` into users and teams. Teams are given as
+/// `org/team` and must belong to the organization that owns the repository.
+fn reviewers_from_flags(flags: &[String], owner: &str) -> Result<PullRequestRequestReviewers> {
+    let mut result = PullRequestRequestReviewers::default();
+
+    for flag in flags {
+        let flag = flag.trim().trim_start_matches('@');
+        if let Some((org, team)) = flag.split_once('/') {
+            if !org.eq_ignore_ascii_case(owner) || team.is_empty() {
+                return Err(Error::new(format!(
+                    "--reviewer {}: team reviewers must be given as '{}/team'",
+                    flag, owner
+                )));
+            }
+            if !result.team_reviewers.iter().any(|t| t == team) {
+                result.team_reviewers.push(team.to_string());
+            }
+        } else if !flag.is_empty() && !result.reviewers.iter().any(|r| r == flag) {
+            result.reviewers.push(flag.to_string());
+        }
+    }
+
+    Ok(result)
+}
+
+/// A `--split-by-path` rule: files matching `pattern` go into the Pull
+/// Request called `name`.
+#[derive(Debug)]
+struct SplitRule {
+    pattern: glob::Pattern,
+    name: String,
+}
+
+fn parse_split_rule(rule: &str) -> Result<SplitRule> {
+    let (pattern, name) = rule
+        .rsplit_once('=')
+        .filter(|(pattern, name)| !pattern.trim().is_empty() && !name.trim().is_empty())
+        .ok_or_else(|| {
+            Error::new(format!(
+                "Invalid --split-by-path rule '{}', expected '<glob>=<name>'",
+                rule
+            ))
+        })?;
+
+    Ok(SplitRule {
+        pattern: glob::Pattern::new(pattern.trim()).map_err(|e| {
+            Error::new(format!(
+                "Invalid glob '{}' in --split-by-path: {}",
+                pattern, e
+            ))
+        })?,
+        name: name.trim().to_string(),
+    })
+}
+
+/// Group changed paths by the first rule they match, in the order the rules
+/// were given. Paths matching no rule go into a final "default" group. Empty
+/// groups are left out.
+fn partition_paths(rules: &[SplitRule], paths: &[PathBuf]) -> Vec<(String, Vec<PathBuf>)> {
+    let mut groups: Vec<(String, Vec<PathBuf>)> = rules
+        .iter()
+        .map(|rule| (rule.name.clone(), Vec::new()))
+        .collect();
+    let mut unmatched = Vec::new();
+
+    for path in paths {
+        match rules
+            .iter()
+            .position(|rule| rule.pattern.matches_path(path))
+        {
+            Some(index) => groups[index].1.push(path.clone()),
+            None => unmatched.push(path.clone()),
+        }
+    }
+    groups.push(("default".to_string(), unmatched));
+
+    // Rules can share a name, in which case their paths go into one group
+    let mut merged: Vec<(String, Vec<PathBuf>)> = Vec::new();
+    for (name, paths) in groups {
+        if paths.is_empty() {
+            continue;
+        }
+        match merged.iter_mut().find(|(existing, _)| *existing == name) {
+            Some((_, existing_paths)) => existing_paths.extend(paths),
+            None => merged.push((name, paths)),
+        }
+    }
+    merged
+}
+
+/// Experimental `--split-by-path`: split the commit into a stack with one
+/// commit per group of paths, bottom to top in the order of the rules. The
+/// split-off commits get the original message with the group name added to
+/// the title; the topmost commit keeps the original message (including an
+/// existing Pull Request link).
+fn split_commit_by_path(
+    jj: &crate::jj::Jujutsu,
+    config: &crate::config::Config,
+    rules: &[String],
+    commit: crate::jj::PreparedCommit,
+) -> Result<Vec<crate::jj::PreparedCommit>> {
+    let rules = rules
+        .iter()
+        .map(|rule| parse_split_rule(rule))
+        .collect::<Result<Vec<_>>>()?;
+    let groups = partition_paths(&rules, &jj.get_commit_diff(commit.oid)?);
+    if groups.len() < 2 {
+        return Ok(vec![commit]);
+    }
+
+    output(
+        "🧪",
+        &format!(
+            "Experimental: splitting commit into {} Pull Requests by path ({})",
+            groups.len(),
+            groups
+                .iter()
+                .map(|(name, _)| name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    )?;
+
+    let title = commit
+        .message
+        .get(&MessageSection::Title)
+        .cloned()
+        .unwrap_or_default();
+    let split_off: Vec<(String, Vec<PathBuf>)> = groups[..groups.len() - 1]
+        .iter()
+        .map(|(name, paths)| {
+            let mut message = commit.message.clone();
+            message.remove(&MessageSection::PullRequest);
+            message.insert(MessageSection::Title, format!("{} ({})", title, name));
+            (build_commit_message(&message, config), paths.clone())
+        })
+        .collect();
+
+    jj.split_commit_by_paths(commit.oid, &split_off)?
+        .into_iter()
+        .map(|oid| jj.get_prepared_commit_for_revision(config, &oid.to_string()))
+        .collect()
+}
+
+/// Keep only the lowest `count` commits of a stack (ordered bottom to top).
+/// Returns the number of commits dropped from the top.
+fn keep_bottom_commits(commits: &mut Vec<crate::jj::PreparedCommit>, count: usize) -> usize {
+    let skipped = commits.len().saturating_sub(count);
+    commits.truncate(count);
+    skipped
+}
+
+/// The message for GitHub with the title and description taken from `text`,
+/// as given with `--pr-message`. Sections that are not part of the Pull
+/// Request's title and description are kept from `message`.
+fn override_github_message(
+    message: &MessageSectionsMap,
+    text: &str,
+    config: &crate::config::Config,
+) -> MessageSectionsMap {
+    let mut overrides = parse_message(text, MessageSection::Title, config);
+    let mut message = message.clone();
+    for section in [
+        MessageSection::Title,
+        MessageSection::Summary,
+        MessageSection::TestPlan,
+    ] {
+        match overrides.remove(&section) {
+            Some(text) => message.insert(section, text),
+            None => message.remove(&section),
+        };
+    }
+    message
+}
+
+/// Fail if the Pull Request branch was changed on GitHub since spr last
+/// pushed it, unless `force` is given. Without a record of what spr pushed
+/// (e.g. for Pull Requests created by an older version), there is nothing
+/// to compare with.
+fn check_head_unchanged(
+    pull_request: &PullRequest,
+    recorded_head: Option<Oid>,
+    force: bool,
+) -> Result<()> {
+    match recorded_head {
+        Some(recorded_head) if !force && recorded_head != pull_request.head_oid => {
+            Err(Error::new(formatdoc!(
+                "The branch of Pull Request #{number} was changed on GitHub \
+                 since spr last pushed it (expected {expected}, found \
+                 {found}). Take the changes into your local commit, or run \
+                 `spr diff --force` to overwrite them.",
+                number = pull_request.number,
+                expected = recorded_head,
+                found = pull_request.head_oid,
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Drop the title from `updates` if it would overwrite a title that was edited
+/// on GitHub since spr last set it, unless `force_title` is given.
+fn keep_title_edited_on_github(
+    updates: &mut PullRequestUpdate,
+    pull_request: &PullRequest,
+    store: &MappingStore,
+    force_title: bool,
+) -> Result<()> {
+    let Some(local_title) = updates.title.as_deref() else {
+        return Ok(());
+    };
+
+    if !force_title
+        && title_edited_on_github(
+            store.title(pull_request.number).as_deref(),
+            &pull_request.title,
+            local_title,
+        )
+    {
+        output(
+            "⚠️",
+            indoc!(
+                "The Pull Request's title was edited on GitHub since spr \
+                 last set it. Keeping the title on GitHub; use \
+                 `spr diff --update-message --force-title` to overwrite it \
+                 with the local title, or `spr amend` to pull it into the \
+                 local commit message."
+            ),
+        )?;
+        updates.title = None;
+    }
+
+    Ok(())
+}
+
+/// Three-way comparison of the title spr last set on GitHub, the title
+/// currently on GitHub and the local title. Returns true if the title on
+/// GitHub was changed by someone else and differs from the local one, i.e.
+/// overwriting it would lose that edit. If spr has no record of the title it
+/// set, the title on GitHub is not considered edited.
+fn title_edited_on_github(
+    last_set_title: Option<&str>,
+    github_title: &str,
+    local_title: &str,
+) -> bool {
+    github_title != local_title && last_set_title.is_some_and(|title| title != github_title)
+}
+
+/// The users whose latest review on the Pull Request was an approval.
+fn previous_approvers(reviewers: &HashMap<String, ReviewStatus>) -> Vec<String> {
+    let mut approvers: Vec<String> = reviewers
+        .iter()
+        .filter(|(_, status)| **status == ReviewStatus::Approved)
+        .map(|(login, _)| login.clone())
+        .collect();
+    approvers.sort();
+    approvers
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::test_config;
     use std::fs;
     use tempfile::TempDir;
 
-    fn create_test_config() -> crate::config::Config {
-        crate::config::Config::new(
-            "test_owner".into(),
-            "test_repo".into(),
-            "origin".into(),
-            "main".into(),
-            "spr/test/".into(),
-            false,
-            false,
-            false,
-            false,
-            false,
-        )
-    }
-
     #[allow(dead_code)]
     fn create_test_git_repo() -> (TempDir, git2::Repository) {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
@@ -772,6 +2298,28 @@ mod tests {
             cherry_pick: false,
             base: None,
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         assert!(!opts.all);
@@ -792,6 +2340,28 @@ mod tests {
             cherry_pick: false,
             base: Some("main".to_string()),
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         assert_eq!(opts.base, Some("main".to_string()));
@@ -801,8 +2371,8 @@ mod tests {
     #[test]
     fn test_jujutsu_integration() {
         // Test configuration for jj-spr
-        let config = create_test_config();
-        assert_eq!(config.owner, "test_owner");
+        let config = test_config();
+        assert_eq!(config.owner, "acme");
         assert_eq!(config.remote_name, "origin");
     }
 
@@ -817,6 +2387,28 @@ mod tests {
             cherry_pick: false,
             base: Some("main".to_string()),
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         assert_eq!(opts_with_base.base.as_deref(), Some("main"));
@@ -830,6 +2422,28 @@ mod tests {
             cherry_pick: false,
             base: Some("trunk()".to_string()),
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         assert_eq!(opts_with_trunk.base.as_deref(), Some("trunk()"));
@@ -845,6 +2459,28 @@ mod tests {
             cherry_pick: false,
             base: Some("trunk()".to_string()),
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         // When --all is specified, it should work with base revisions
@@ -863,6 +2499,28 @@ mod tests {
             cherry_pick: false,
             base: Some("trunk()".to_string()),
             revision: None,
+            force_title: false,
+            bottom: None,
+            split_by_path: Vec::new(),
+            parallel: None,
+            reviewer: Vec::new(),
+            ready: false,
+            label: Vec::new(),
+            assignee: Vec::new(),
+            milestone: None,
+            verify: false,
+            no_verify: false,
+            pr_message: None,
+            sync_message: false,
+            summary_from_body: false,
+            no_push: false,
+            no_update_message: false,
+            open: false,
+            no_open: false,
+            template: false,
+            no_template: false,
+            force: false,
+            base_branch: None,
         };
 
         assert!(opts.all);
@@ -873,6 +2531,338 @@ mod tests {
         assert_eq!(opts.base.as_deref(), Some("trunk()"));
     }
 
+    #[test]
+    fn test_keep_bottom_commits() {
+        // A stack of five commits, bottom to top, each based on the previous
+        let mut parent_oid = Oid::zero();
+        let mut stack: Vec<crate::jj::PreparedCommit> = (1..=5u8)
+            .map(|i| {
+                let oid = Oid::from_bytes(&[i; 20]).unwrap();
+                let commit = crate::jj::PreparedCommit {
+                    oid,
+                    short_id: format!("{:.7}", oid),
+                    parent_oid,
+                    base_oid: None,
+                    message: Default::default(),
+                    pull_request_number: None,
+                    message_changed: false,
+                };
+                parent_oid = oid;
+                commit
+            })
+            .collect();
+        let first_parent_oid = stack[0].parent_oid;
+
+        assert_eq!(keep_bottom_commits(&mut stack, 2), 3);
+        assert_eq!(stack.len(), 2);
+        // The submitted commits are the two lowest ones, still linked to each
+        // other and to the same master base
+        assert_eq!(stack[0].parent_oid, first_parent_oid);
+        assert_eq!(stack[1].parent_oid, stack[0].oid);
+        assert_eq!(stack[1].oid, Oid::from_bytes(&[2; 20]).unwrap());
+
+        // Asking for more than the stack has keeps everything
+        assert_eq!(keep_bottom_commits(&mut stack, 10), 0);
+        assert_eq!(stack.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_split_rule() {
+        let rule = parse_split_rule("frontend/*=frontend").unwrap();
+        assert_eq!(rule.name, "frontend");
+        assert!(
+            rule.pattern
+                .matches_path(std::path::Path::new("frontend/src/app.ts"))
+        );
+
+        assert!(parse_split_rule("frontend/*").is_err());
+        assert!(parse_split_rule("=frontend").is_err());
+        assert!(parse_split_rule("frontend/*=").is_err());
+        assert!(parse_split_rule("[=frontend").is_err());
+    }
+
+    #[test]
+    fn test_partition_paths() {
+        let rules = vec![
+            parse_split_rule("frontend/*=frontend").unwrap(),
+            parse_split_rule("backend/*=backend").unwrap(),
+            parse_split_rule("*.sql=backend").unwrap(),
+            parse_split_rule("docs/*=docs").unwrap(),
+        ];
+        let paths: Vec<PathBuf> = [
+            "backend/api.rs",
+            "README.md",
+            "frontend/app.ts",
+            "schema.sql",
+            "frontend/style.css",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let groups = partition_paths(&rules, &paths);
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    "frontend".to_string(),
+                    vec![
+                        PathBuf::from("frontend/app.ts"),
+                        PathBuf::from("frontend/style.css")
+                    ]
+                ),
+                (
+                    "backend".to_string(),
+                    vec![PathBuf::from("backend/api.rs"), PathBuf::from("schema.sql")]
+                ),
+                ("default".to_string(), vec![PathBuf::from("README.md")]),
+            ]
+        );
+
+        // Everything matching a single rule leaves nothing to split
+        let groups = partition_paths(&rules, &[PathBuf::from("docs/guide.md")]);
+        assert_eq!(
+            groups,
+            vec![("docs".to_string(), vec![PathBuf::from("docs/guide.md")])]
+        );
+    }
+
+    #[test]
+    fn test_previous_approvers() {
+        let reviewers = HashMap::from([
+            ("carol".to_string(), ReviewStatus::Approved),
+            ("bob".to_string(), ReviewStatus::Rejected),
+            ("alice".to_string(), ReviewStatus::Approved),
+            ("dave".to_string(), ReviewStatus::Requested),
+        ]);
+
+        assert_eq!(
+            previous_approvers(&reviewers),
+            vec!["alice".to_string(), "carol".to_string()]
+        );
+        assert!(previous_approvers(&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_push_no_verify() {
+        use clap::Parser;
+
+        let mut config = test_config();
+        let parse = |args: &[&str]| DiffOptions::try_parse_from(args).unwrap();
+        assert!(push_no_verify(&parse(&["diff"]), &config));
+        assert!(!push_no_verify(&parse(&["diff", "--verify"]), &config));
+
+        config.push_no_verify = false;
+        assert!(!push_no_verify(&parse(&["diff"]), &config));
+        assert!(push_no_verify(&parse(&["diff", "--no-verify"]), &config));
+        assert!(!push_no_verify(
+            &parse(&["diff", "--no-verify", "--verify"]),
+            &config
+        ));
+    }
+
+    #[test]
+    fn test_assignees_from_flags() {
+        assert_eq!(assignees_from_flags(&[]), None);
+        let flags: Vec<String> = ["alice", " @bob", "alice"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert_eq!(assignees_from_flags(&flags).as_deref(), Some("alice, bob"));
+    }
+
+    #[test]
+    fn test_labels_to_add() {
+        use clap::Parser;
+
+        let mut config = test_config();
+        config.default_labels = vec!["team: infra".into(), "bug".into()];
+        let opts =
+            DiffOptions::try_parse_from(["diff", "--label", "bug", "--label", "p1"]).unwrap();
+
+        let mut message = MessageSectionsMap::new();
+        message.insert(MessageSection::Labels, "p1, docs".into());
+
+        assert_eq!(
+            labels_to_add(&config, &opts, &message),
+            vec!["team: infra", "bug", "p1", "docs"]
+        );
+        assert_eq!(
+            labels_to_add(&config, &opts, &MessageSectionsMap::new()),
+            vec!["team: infra", "bug", "p1"]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_labels() {
+        let previous = vec!["bug".to_string(), "p1".to_string()];
+        let labels = vec!["bug".to_string(), "p2".to_string()];
+
+        // p1 was added by spr and is no longer wanted. A label added on
+        // GitHub is not in `previous`, so it can't end up being removed.
+        assert_eq!(
+            reconcile_labels(&previous, &labels, true),
+            (vec!["p1".to_string()], labels.clone())
+        );
+        assert_eq!(
+            reconcile_labels(&previous, &labels, false),
+            (
+                Vec::new(),
+                vec!["bug".to_string(), "p1".to_string(), "p2".to_string()]
+            )
+        );
+        assert_eq!(
+            reconcile_labels(&previous, &[], true),
+            (previous.clone(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_label_changes() {
+        let current = vec!["bug".to_string(), "p1".to_string()];
+
+        assert_eq!(
+            label_changes(
+                &current,
+                vec!["bug".to_string(), "p2".to_string()],
+                vec!["p1".to_string(), "p3".to_string()]
+            ),
+            (vec!["p2".to_string()], vec!["p1".to_string()])
+        );
+        assert_eq!(
+            label_changes(&current, current.clone(), Vec::new()),
+            (Vec::new(), Vec::new())
+        );
+    }
+
+    #[test]
+    fn test_draft_label_update() {
+        let mut config = test_config();
+        assert!(draft_label_update(&config, 1, true, false).is_none());
+
+        config.draft_label = Some("wip".into());
+        assert!(matches!(
+            draft_label_update(&config, 1, true, false),
+            Some(MetadataUpdate::DraftLabel { number: 1, ref label, add: true }) if label == "wip"
+        ));
+        assert!(matches!(
+            draft_label_update(&config, 2, false, true),
+            Some(MetadataUpdate::DraftLabel { number: 2, ref label, add: false }) if label == "wip"
+        ));
+        // A Pull Request created ready for review never had the label
+        assert!(draft_label_update(&config, 3, false, false).is_none());
+    }
+
+    #[test]
+    fn test_reviewers_from_flags() {
+        let flags: Vec<String> = ["alice", "acme/core", "@bob", "Acme/core", "alice"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let reviewers = reviewers_from_flags(&flags, "acme").unwrap();
+        assert_eq!(reviewers.reviewers, vec!["alice", "bob"]);
+        assert_eq!(reviewers.team_reviewers, vec!["core"]);
+
+        assert!(reviewers_from_flags(&["other/core".to_string()], "acme").is_err());
+        assert!(reviewers_from_flags(&["acme/".to_string()], "acme").is_err());
+    }
+
+    #[test]
+    fn test_override_github_message() {
+        let message = parse_message(
+            "Local title\n\nLocal summary\n\nTest Plan: ran it\n\nReviewers: alice",
+            MessageSection::Title,
+            &test_config(),
+        );
+        let github_message =
+            override_github_message(&message, "Quick fix\n\nShort body", &test_config());
+
+        assert_eq!(github_message[&MessageSection::Title], "Quick fix");
+        assert_eq!(github_message[&MessageSection::Summary], "Short body");
+        assert!(!github_message.contains_key(&MessageSection::TestPlan));
+        assert_eq!(github_message[&MessageSection::Reviewers], "alice");
+    }
+
+    #[test]
+    fn test_title_edited_on_github() {
+        // Nobody touched the title on GitHub
+        assert!(!title_edited_on_github(Some("Old"), "Old", "New"));
+        // Edited on GitHub, and the local title differs
+        assert!(title_edited_on_github(Some("Old"), "Edited", "New"));
+        // Edited on GitHub to exactly the local title
+        assert!(!title_edited_on_github(Some("Old"), "New", "New"));
+        // No record of what spr last set
+        assert!(!title_edited_on_github(None, "Edited", "New"));
+    }
+
+    fn mock_pull_request(number: u64, title: &str) -> PullRequest {
+        let branch =
+            crate::github::GitHubBranch::new_from_branch_name("spr/test/branch", "origin", "main");
+        PullRequest {
+            number,
+            state: PullRequestState::Open,
+            title: title.to_string(),
+            body: None,
+            sections: Default::default(),
+            base: crate::github::GitHubBranch::new_from_branch_name("main", "origin", "main"),
+            head: branch,
+            base_oid: Oid::zero(),
+            head_oid: Oid::zero(),
+            merge_commit: None,
+            reviewers: HashMap::new(),
+            labels: Vec::new(),
+            review_status: None,
+            checks_status: None,
+        }
+    }
+
+    #[test]
+    fn test_keep_title_edited_on_github() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut store = MappingStore::open_path(&temp_dir.path().join("spr-mapping")).unwrap();
+        store.set_title(7, "Title set by spr").unwrap();
+
+        let title_update = || PullRequestUpdate {
+            title: Some("Local title".to_string()),
+            ..Default::default()
+        };
+
+        // Title on GitHub is still the one spr set: overwrite it
+        let pull_request = mock_pull_request(7, "Title set by spr");
+        let mut updates = title_update();
+        keep_title_edited_on_github(&mut updates, &pull_request, &store, false).unwrap();
+        assert_eq!(updates.title.as_deref(), Some("Local title"));
+
+        // Title was edited on GitHub: keep it...
+        let pull_request = mock_pull_request(7, "Edited on GitHub");
+        let mut updates = title_update();
+        keep_title_edited_on_github(&mut updates, &pull_request, &store, false).unwrap();
+        assert_eq!(updates.title, None);
+
+        // ...unless forced
+        let mut updates = title_update();
+        keep_title_edited_on_github(&mut updates, &pull_request, &store, true).unwrap();
+        assert_eq!(updates.title.as_deref(), Some("Local title"));
+    }
+
+    #[test]
+    fn test_check_head_unchanged() {
+        let pushed = Oid::from_str("8f1c2a5e0b7d4c3a9e6f1b2d3c4a5e6f7a8b9c0d").unwrap();
+        let mut pull_request = mock_pull_request(7, "Title");
+        pull_request.head_oid = pushed;
+
+        assert!(check_head_unchanged(&pull_request, Some(pushed), false).is_ok());
+        // Nothing recorded, nothing to compare with
+        assert!(check_head_unchanged(&pull_request, None, false).is_ok());
+
+        // Someone pushed to the branch on GitHub
+        pull_request.head_oid = Oid::zero();
+        let error = check_head_unchanged(&pull_request, Some(pushed), false).unwrap_err();
+        assert!(error.to_string().contains("#7 was changed on GitHub"));
+        assert!(error.to_string().contains("--force"));
+        assert!(check_head_unchanged(&pull_request, Some(pushed), true).is_ok());
+    }
+
     // Integration tests would require more complex setup with actual Git repositories
     // and proper mocking of GitHub API calls. The tests above focus on:
     // 1. Option parsing and validation
@@ -883,4 +2873,110 @@ mod tests {
     // - Mocking GitHub API responses
     // - Creating test repositories with specific commit structures
     // - Testing the interaction between revision specification and commit preparation
+
+    #[tokio::test]
+    async fn test_deferred_updates_are_queued_and_finished_in_stack_order() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut store = MappingStore::open_path(&temp_dir.path().join("spr-mapping")).unwrap();
+        let config = crate::config::Config {
+            // Nothing listens here, so sending an update would fail
+            github_api_url: "http://127.0.0.1:9".into(),
+            github_graphql_url: "http://127.0.0.1:9/graphql".into(),
+            ..test_config()
+        };
+        let gh = GitHub::new(config, reqwest::Client::new());
+
+        let title_update = |number: u64| MetadataUpdate::Update {
+            number,
+            updates: PullRequestUpdate {
+                title: Some(format!("PR {}", number)),
+                ..Default::default()
+            },
+            message_only: false,
+        };
+
+        // With --parallel, updates are queued instead of sent
+        let mut deferred = Vec::new();
+        for number in 1..=3 {
+            send_or_defer(title_update(number), Some(&mut deferred), &gh, &mut store)
+                .await
+                .unwrap();
+        }
+        send_or_defer(
+            MetadataUpdate::Labels {
+                number: 2,
+                labels: vec!["bug".into()],
+                remove: Vec::new(),
+                record: vec!["bug".into()],
+            },
+            Some(&mut deferred),
+            &gh,
+            &mut store,
+        )
+        .await
+        .unwrap();
+        let numbers: Vec<u64> = deferred
+            .iter()
+            .map(|update| match update {
+                MetadataUpdate::Update { number, .. } | MetadataUpdate::Labels { number, .. } => {
+                    *number
+                }
+                _ => unreachable!(),
+            })
+            .collect();
+        assert_eq!(numbers, vec![1, 2, 3, 2]);
+        assert_eq!(store.title(1), None);
+
+        // The results come back in the order of the queue. A failed update
+        // of a Pull Request is an error, a failed label change a warning.
+        let results = vec![
+            Ok(()),
+            Err(Error::new("GitHub said no")),
+            Ok(()),
+            Err(Error::new("GitHub said no")),
+        ];
+        let finished: Vec<Result<()>> = zip(&deferred, results)
+            .map(|(update, result)| update.finish(result, &mut store))
+            .collect();
+        assert!(finished[0].is_ok());
+        assert!(finished[1].is_err());
+        assert!(finished[2].is_ok());
+        assert!(finished[3].is_ok());
+        assert_eq!(store.title(1).as_deref(), Some("PR 1"));
+        assert_eq!(store.title(2), None);
+        assert_eq!(store.title(3).as_deref(), Some("PR 3"));
+        assert!(store.labels(2).is_empty());
+
+        // Without a queue, the update is sent right away
+        assert!(
+            send_or_defer(title_update(4), None, &gh, &mut store)
+                .await
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_pull_request_template() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        assert_eq!(read_pull_request_template(temp_dir.path()), None);
+
+        std::fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        std::fs::write(
+            temp_dir.path().join("docs/PULL_REQUEST_TEMPLATE.md"),
+            "## Why\n\n## How\n",
+        )
+        .unwrap();
+        let template = read_pull_request_template(temp_dir.path()).unwrap();
+        assert_eq!(template, "## Why\n\n## How");
+
+        let mut message = MessageSectionsMap::new();
+        message.insert(MessageSection::Title, "Add a feature".into());
+        assert!(insert_pull_request_template(&mut message, &template));
+        assert_eq!(message[&MessageSection::Summary], template);
+
+        // An existing summary is left alone
+        message.insert(MessageSection::Summary, "Written by hand".into());
+        assert!(!insert_pull_request_template(&mut message, &template));
+        assert_eq!(message[&MessageSection::Summary], "Written by hand");
+    }
 }
@@ -7,7 +7,7 @@
 
 use crate::{
     error::{Error, Result},
-    message::validate_commit_message,
+    message::{build_commit_message, validate_commit_message},
     output::{output, write_commit_title},
 };
 
@@ -17,7 +17,8 @@ pub struct FormatOptions {
     #[clap(long, short = 'a')]
     all: bool,
 
-    /// Base revision for --all mode (if not specified, uses trunk)
+    /// Base revision for --all mode (if not specified, the stack starts
+    /// where the revision branched off master)
     #[clap(long)]
     base: Option<String>,
 
@@ -25,6 +26,11 @@ pub struct FormatOptions {
     /// If a range is provided, behaves like --all mode. If not specified, uses '@-'.
     #[clap(short = 'r', long)]
     revision: Option<String>,
+
+    /// Don't change anything, but fail if a commit message is not in
+    /// canonical form, showing how it would be reformatted
+    #[clap(long)]
+    check: bool,
 }
 
 pub async fn format(
@@ -32,19 +38,12 @@ pub async fn format(
     jj: &crate::jj::Jujutsu,
     config: &crate::config::Config,
 ) -> Result<()> {
-    // Determine revision and whether to use range mode
-    let (use_range_mode, base_rev, target_rev, is_inclusive) =
-        crate::revision_utils::parse_revision_and_range(
-            opts.revision.as_deref(),
-            opts.all,
-            opts.base.as_deref(),
-        )?;
-
-    let mut pc = if use_range_mode {
-        jj.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?
-    } else {
-        vec![jj.get_prepared_commit_for_revision(config, &target_rev)?]
-    };
+    let (_, mut pc) = jj.get_prepared_commits_for_options(
+        config,
+        opts.revision.as_deref(),
+        opts.all,
+        opts.base.as_deref(),
+    )?;
 
     if pc.is_empty() {
         output("👋", "No commits found - nothing to do. Good bye!")?;
@@ -53,11 +52,85 @@ pub async fn format(
 
     let mut failure = false;
 
-    for commit in pc.iter() {
+    for commit in pc.iter_mut() {
         write_commit_title(commit)?;
         failure = validate_commit_message(&commit.message, config).is_err() || failure;
+
+        let raw = jj
+            .git_repo
+            .find_commit(commit.oid)?
+            .message()
+            .unwrap_or("")
+            .to_string();
+        let canonical = build_commit_message(&commit.message, config);
+        if raw.trim_end() == canonical.trim_end() {
+            continue;
+        }
+        if opts.check {
+            output("💔", "Commit message is not formatted:")?;
+            console::Term::stdout().write_line(&line_diff(raw.trim_end(), canonical.trim_end()))?;
+            failure = true;
+        } else {
+            commit.message_changed = true;
+        }
+    }
+    if !opts.check {
+        jj.rewrite_commit_messages(config, &mut pc)?;
     }
-    jj.rewrite_commit_messages(&mut pc)?;
 
     if failure { Err(Error::empty()) } else { Ok(()) }
 }
+
+/// A minimal line-by-line diff from `old` to `new`, with removed lines
+/// prefixed by `-`, added lines by `+`, and unchanged lines by a space.
+fn line_diff(old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Length of the longest common subsequence of old[i..] and new[j..]
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut result = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            result.push(format!(" {}", old[i]));
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            result.push(format!("-{}", old[i]));
+            i += 1;
+        } else {
+            result.push(format!("+{}", new[j]));
+            j += 1;
+        }
+    }
+
+    result.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_diff() {
+        assert_eq!(
+            line_diff(
+                "Title\n\nTest plan:   none\nReviewers: a",
+                "Title\n\nTest Plan: none\n\nReviewers: a"
+            ),
+            " Title\n \n-Test plan:   none\n+Test Plan: none\n+\n Reviewers: a"
+        );
+        assert_eq!(line_diff("a\nb", "a\nb"), " a\n b");
+    }
+}
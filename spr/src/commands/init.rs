@@ -9,12 +9,24 @@ use indoc::formatdoc;
 use lazy_regex::regex;
 
 use crate::{
-    config::{AuthTokenSource, get_auth_token_with_source},
+    config::{
+        AuthTokenSource, DIRECTORY_CONFIG_FILE_NAME, get_auth_token_with_source, get_config_value,
+        parse_github_repository, parse_remote_url,
+    },
     error::{Error, Result, ResultExt},
     output::output,
 };
 
-pub async fn init() -> Result<()> {
+#[derive(Debug, clap::Parser)]
+pub struct InitOptions {
+    /// Start over: suggest the defaults, not the current settings, and
+    /// replace all spr settings in the git config of the repository with
+    /// the answers once every question is answered
+    #[clap(long)]
+    force: bool,
+}
+
+pub async fn init(opts: InitOptions) -> Result<()> {
     output("👋", "Welcome to spr!")?;
 
     let path = std::env::current_dir()?;
@@ -25,6 +37,19 @@ pub async fn init() -> Result<()> {
     ))?;
     let mut config = repo.config()?;
 
+    // The answers are only written at the end, so that aborting init leaves
+    // the settings as they were, even with --force.
+    let mut settings: Vec<(&str, String)> = Vec::new();
+
+    // Existing settings are suggested in the prompts, unless starting over
+    let existing = |config: &git2::Config, key: &str| {
+        if opts.force {
+            None
+        } else {
+            get_config_value(key, config).filter(|value| !value.is_empty())
+        }
+    };
+
     // GitHub Personal Access Token
 
     console::Term::stdout().write_line("")?;
@@ -35,7 +60,7 @@ pub async fn init() -> Result<()> {
     )?;
 
     let github_auth_token = get_auth_token_with_source(&config).and_then(|value| {
-        if value.token().is_empty() {
+        if value.token().is_empty() || (opts.force && matches!(value, AuthTokenSource::Config(_))) {
             None
         } else {
             Some(value)
@@ -93,7 +118,7 @@ pub async fn init() -> Result<()> {
     output("👋", &formatdoc!("Hello {}!", github_user.login))?;
 
     if !reuse_token {
-        config.set_str("spr.githubAuthToken", pat.as_str())?;
+        settings.push(("spr.githubAuthToken", pat));
     }
 
     // Name of remote
@@ -111,70 +136,78 @@ pub async fn init() -> Result<()> {
     let remote = dialoguer::Input::<String>::new()
         .with_prompt("Name of remote for GitHub")
         .with_initial_text(
-            config
-                .get_string("spr.githubRemoteName")
-                .ok()
-                .unwrap_or_else(|| "origin".to_string()),
+            existing(&config, "spr.githubRemoteName").unwrap_or_else(|| "origin".to_string()),
         )
         .interact_text()?;
-    config.set_str("spr.githubRemoteName", &remote)?;
+    settings.push(("spr.githubRemoteName", remote.clone()));
 
     // Name of the GitHub repo
 
     console::Term::stdout().write_line("")?;
 
-    output(
-        "❓",
-        &formatdoc!(
-            "What's the name of the GitHub repository. Please enter \
-             'OWNER/REPOSITORY' (basically the bit that follow \
-             'github.com/' in the address.)"
-        ),
-    )?;
-
+    // Suggest the repository the remote points at, which works for any
+    // GitHub host
     let url = repo.find_remote(&remote)?.url().map(String::from);
-    let regex = lazy_regex::regex!(r#"github\.com[/:]([\w\-\.]+/[\w\-\.]+?)(.git)?$"#);
-    let github_repo = config
-        .get_string("spr.githubRepository")
-        .ok()
-        .and_then(|value| if value.is_empty() { None } else { Some(value) })
-        .or_else(|| {
-            url.as_ref()
-                .and_then(|url| regex.captures(url))
-                .and_then(|caps| caps.get(1))
-                .map(|m| m.as_str().to_string())
-        })
-        .unwrap_or_default();
-
-    let github_repo = dialoguer::Input::<String>::new()
-        .with_prompt("GitHub repository")
-        .with_initial_text(github_repo)
-        .interact_text()?;
-    config.set_str("spr.githubRepository", &github_repo)?;
-
-    // Master branch name (just query GitHub)
-
-    let github_repo_info = octocrab
-        .get::<octocrab::models::Repository, _, _>(format!("repos/{}", &github_repo), None::<&()>)
-        .await?;
+    let suggestion = existing(&config, "spr.githubRepository").or_else(|| {
+        url.as_deref()
+            .and_then(parse_remote_url)
+            .map(|(owner, repo)| format!("{}/{}", owner, repo))
+    });
 
-    config.set_str(
-        "spr.githubMasterBranch",
-        github_repo_info
-            .default_branch
-            .as_ref()
-            .map(|s| &s[..])
-            .unwrap_or("master"),
-    )?;
+    let confirmed = match &suggestion {
+        Some(github_repo) => dialoguer::Confirm::new()
+            .with_prompt(format!("Use the GitHub repository {}?", github_repo))
+            .default(true)
+            .interact()?,
+        None => false,
+    };
+    let github_repo = match suggestion {
+        Some(github_repo) if confirmed => github_repo,
+        suggestion => {
+            output(
+                "❓",
+                &formatdoc!(
+                    "What's the name of the GitHub repository. Please enter \
+                     'OWNER/REPOSITORY' (basically the bit that follow \
+                     'github.com/' in the address.)"
+                ),
+            )?;
+            dialoguer::Input::<String>::new()
+                .with_prompt("GitHub repository")
+                .with_initial_text(suggestion.unwrap_or_default())
+                .validate_with(|input: &String| -> Result<()> {
+                    parse_github_repository(input)
+                        .map(|_| ())
+                        .ok_or_else(|| Error::new("Please enter 'OWNER/REPOSITORY'."))
+                })
+                .interact_text()?
+        }
+    };
+    settings.push(("spr.githubRepository", github_repo.clone()));
+
+    // Master branch name (just query GitHub, unless it's set already)
+
+    if existing(&config, "spr.githubMasterBranch").is_none() {
+        let github_repo_info = octocrab
+            .get::<octocrab::models::Repository, _, _>(
+                format!("repos/{}", &github_repo),
+                None::<&()>,
+            )
+            .await?;
+
+        settings.push((
+            "spr.githubMasterBranch",
+            github_repo_info
+                .default_branch
+                .unwrap_or_else(|| "master".to_string()),
+        ));
+    }
 
     // Pull Request branch prefix
 
     console::Term::stdout().write_line("")?;
 
-    let branch_prefix = config
-        .get_string("spr.branchPrefix")
-        .ok()
-        .and_then(|value| if value.is_empty() { None } else { Some(value) })
+    let branch_prefix = existing(&config, "spr.branchPrefix")
         .unwrap_or_else(|| format!("spr/{}/", &github_user.login));
 
     output(
@@ -196,13 +229,69 @@ pub async fn init() -> Result<()> {
         .validate_with(|input: &String| -> Result<()> { validate_branch_prefix(input) })
         .interact_text()?;
 
-    config.set_str("spr.branchPrefix", &branch_prefix)?;
-    config.set_str("spr.requireApproval", "false")?;
-    config.set_str("spr.requireTestPlan", "false")?;
-    config.set_str("spr.addReviewedBy", "false")?;
-    config.set_str("spr.addSprBannerComment", "false")?;
-    config.set_str("spr.addSkipCiComment", "false")?;
+    settings.push(("spr.branchPrefix", branch_prefix));
+    for key in [
+        "spr.requireApproval",
+        "spr.requireTestPlan",
+        "spr.addReviewedBy",
+        "spr.addSprBannerComment",
+        "spr.addSkipCiComment",
+    ] {
+        if existing(&config, key).is_none() {
+            settings.push((key, "false".to_string()));
+        }
+    }
+
+    if opts.force {
+        remove_spr_settings(&repo)?;
+    }
+    for (key, value) in settings {
+        if opts.force {
+            config.set_str(key, &value)?;
+        } else {
+            set_if_changed(&mut config, key, &value)?;
+        }
+    }
+
+    output(
+        "📁",
+        &formatdoc!(
+            "These settings are stored in the git config of the repository. \
+             To use different settings in part of the repository (e.g. a \
+             team's directory in a monorepo), put them in a `{}` file there. \
+             spr uses the nearest one in the current directory or above it, \
+             before jj and git config.",
+            DIRECTORY_CONFIG_FILE_NAME
+        ),
+    )?;
+
+    Ok(())
+}
 
+/// Write a setting to the git config, unless it has that value already (in
+/// jj or git config).
+fn set_if_changed(config: &mut git2::Config, key: &str, value: &str) -> Result<()> {
+    if get_config_value(key, config).as_deref() != Some(value) {
+        config.set_str(key, value)?;
+    }
+    Ok(())
+}
+
+/// Remove all `spr.*` settings from the repository's own git config (not
+/// the global one).
+fn remove_spr_settings(repo: &git2::Repository) -> Result<()> {
+    let mut config = repo.config()?.open_level(git2::ConfigLevel::Local)?;
+    let mut names = Vec::new();
+    let mut entries = config.entries(Some(r"^spr\."))?;
+    while let Some(entry) = entries.next() {
+        if let Some(name) = entry?.name() {
+            names.push(name.to_string());
+        }
+    }
+    drop(entries);
+    for name in names {
+        config.remove(&name)?;
+    }
     Ok(())
 }
 
@@ -252,7 +341,34 @@ fn validate_branch_prefix(branch_prefix: &str) -> Result<()> {
 
 #[cfg(test)]
 mod tests {
-    use super::validate_branch_prefix;
+    use super::{remove_spr_settings, validate_branch_prefix};
+
+    #[test]
+    fn test_remove_spr_settings() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let mut config = repo
+            .config()
+            .unwrap()
+            .open_level(git2::ConfigLevel::Local)
+            .unwrap();
+        config.set_str("spr.branchPrefix", "spr/me/").unwrap();
+        config
+            .set_str("spr.branchNameTemplate", "{prefix}{change-id}")
+            .unwrap();
+        config.set_str("user.name", "Me").unwrap();
+
+        remove_spr_settings(&repo).unwrap();
+
+        let config = repo
+            .config()
+            .unwrap()
+            .open_level(git2::ConfigLevel::Local)
+            .unwrap();
+        assert!(config.get_string("spr.branchPrefix").is_err());
+        assert!(config.get_string("spr.branchNameTemplate").is_err());
+        assert_eq!(config.get_string("user.name").unwrap(), "Me");
+    }
 
     #[test]
     fn test_branch_prefix_rules() {
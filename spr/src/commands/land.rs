@@ -5,28 +5,81 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use std::{io::Write, process::Stdio, time::Duration};
+use std::{
+    io::{IsTerminal, Write},
+    process::Stdio,
+    time::Duration,
+};
 
 use indoc::formatdoc;
+use octocrab::params::pulls::MergeMethod;
 
 use crate::{
+    config::LandMessageFormat,
     error::{Error, Result, ResultExt},
-    github::{PullRequestState, PullRequestUpdate, ReviewStatus},
-    message::build_github_body_for_merging,
+    github::{
+        ChecksStatus, MergeState, PullRequest, PullRequestState, PullRequestUpdate, ReviewStatus,
+    },
+    message::{append_co_author_trailers, build_github_body_for_merging},
     output::{output, write_commit_title},
-    utils::run_command,
+    store::MappingStore,
+    utils::{AsyncLoggedOutput, run_command},
 };
 
+/// How long `land --wait` waits between asking GitHub about the checks
+const CHECKS_POLL_INTERVAL: Duration = Duration::from_secs(15);
+
 #[derive(Debug, clap::Parser)]
 pub struct LandOptions {
     /// Merge a Pull Request that was created or updated with spr diff
-    /// --cherry-pick
+    /// --cherry-pick. Implied if spr diff --cherry-pick last updated it
+    /// from this repository
     #[clap(long)]
     cherry_pick: bool,
 
-    /// Jujutsu revision to operate on (if not specified, uses '@')
+    /// Jujutsu revision to operate on (if not specified, uses '@'). A range
+    /// like 'A::B' lands every Pull Request in it, bottom to top
     #[clap(short = 'r', long)]
     revision: Option<String>,
+
+    /// Open $EDITOR to edit the squash commit title and message before
+    /// merging
+    #[clap(long)]
+    edit_message: bool,
+
+    /// Add the Pull Request to the merge queue of the master branch instead
+    /// of merging it directly
+    #[clap(long, conflicts_with = "edit_message")]
+    queue: bool,
+
+    /// Run all checks and show what would be merged and deleted, without
+    /// changing anything on GitHub
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Show the merge message, changed files, target branch and approvers,
+    /// and ask for confirmation before merging
+    #[clap(long, short = 'i', conflicts_with = "dry_run")]
+    interactive: bool,
+
+    /// With --interactive, merge without asking for confirmation
+    #[clap(long, short = 'y')]
+    yes: bool,
+
+    /// Before merging, wait until the checks on the Pull Request have
+    /// passed, and abort if one of them fails. Only required checks count,
+    /// unless the repository requires none
+    #[clap(long)]
+    wait: bool,
+
+    /// With --wait, give up after this many seconds
+    #[clap(
+        long,
+        value_name = "SECONDS",
+        default_value_t = 3600,
+        requires = "wait"
+    )]
+    wait_timeout: u64,
 }
 
 pub async fn land(
@@ -36,12 +89,136 @@ pub async fn land(
     gh: &mut crate::github::GitHub,
     config: &crate::config::Config,
 ) -> Result<()> {
-    // jj.check_no_uncommitted_changes()?;
     let revision = opts.revision.as_deref().unwrap_or("@");
-    let mut prepared_commits =
-        jj.get_prepared_commits_from_to(config, "trunk()", revision, false)?;
+    let (use_range_mode, base_rev, target_rev, is_inclusive) =
+        crate::revision_utils::parse_revision_and_range(Some(revision), false, None)?;
+
+    if !use_range_mode {
+        return land_commit(&opts, revision, git, jj, gh, config).await;
+    }
+
+    // Landing a whole stack, e.g. `-r A::B`
+    if opts.queue {
+        return Err(Error::new(
+            "--queue cannot be used to land a range of commits.",
+        ));
+    }
+
+    let prepared_commits =
+        jj.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?;
+    if prepared_commits.is_empty() {
+        output("👋", "No commits found - nothing to do. Good bye!")?;
+        return Ok(());
+    }
+
+    // Check every Pull Request before merging anything, so that we don't end
+    // up with half a stack landed.
+    let mut numbers = Vec::new();
+    for prepared_commit in prepared_commits.iter() {
+        numbers.push(prepared_commit.pull_request_number.ok_or_else(|| {
+            Error::new(format!(
+                "Commit {} does not refer to a Pull Request.",
+                prepared_commit.short_id
+            ))
+        })?);
+    }
+    let pull_requests = gh.get_pull_requests(&numbers).await?;
+    for number in numbers.iter() {
+        let pull_request = pull_requests
+            .get(number)
+            .ok_or_else(|| Error::new(format!("Pull Request #{} not found.", number)))?;
+        check_landable(pull_request, config)
+            .context(format!("Cannot land the stack: Pull Request #{}", number))?;
+    }
+
+    if opts.dry_run {
+        // The bottom Pull Request gets the full dry run. Landing it rebases
+        // the ones above it, so the checks that depend on that can't be run
+        // for the rest of the stack now.
+        let change_id = jj.get_change_id_for_commit(prepared_commits[0].oid)?;
+        land_commit(&opts, &change_id, git, jj, gh, config).await?;
+        for number in numbers.into_iter().skip(1) {
+            output(
+                "🔍",
+                &format!(
+                    "(dry run) would land Pull Request #{} next. Only checked \
+                     that it is open and approved: the other checks need the \
+                     Pull Requests below it landed first.",
+                    number
+                ),
+            )?;
+        }
+        return Ok(());
+    }
+
+    output(
+        "📚",
+        &format!(
+            "Landing {} Pull Requests, bottom to top",
+            prepared_commits.len()
+        ),
+    )?;
+
+    // Land oldest first. Each landing fetches master again and rebases the
+    // rest of the stack onto it, which is why we refer to the commits by
+    // their (stable) change IDs.
+    let oids: Vec<_> = prepared_commits.iter().map(|commit| commit.oid).collect();
+    let mut change_ids = jj.get_change_ids_for_range(&oids)?;
+    for oid in oids {
+        let change_id = change_ids
+            .remove(&oid)
+            .ok_or_else(|| Error::new(format!("Could not find the change ID of commit {}", oid)))?;
+        land_commit(&opts, &change_id, git, jj, gh, config).await?;
+    }
+
+    Ok(())
+}
+
+async fn land_commit(
+    opts: &LandOptions,
+    revision: &str,
+    git: &crate::git::Git,
+    jj: &crate::jj::Jujutsu,
+    gh: &mut crate::github::GitHub,
+    config: &crate::config::Config,
+) -> Result<()> {
+    let prepared_commit = jj.get_prepared_commit_for_revision(config, revision)?;
+    let pull_request_number = prepared_commit.pull_request_number;
+    let store = MappingStore::open(&jj.git_repo).ok();
+
+    // A Pull Request opened with `spr diff --base-branch` lands on that
+    // branch, which then takes the place of master. With a revset of trunks,
+    // so does the trunk the commit is based on.
+    let base_branch =
+        match pull_request_number.and_then(|number| store.as_ref()?.base_branch(number)) {
+            Some(branch) => Some(branch),
+            None => jj.get_trunk_branch_for_commit(config, prepared_commit.oid)?,
+        }
+        .filter(|branch| branch != config.master_ref.branch_name());
+    // A Pull Request last updated with `spr diff --cherry-pick` is landed
+    // the same way, as if --cherry-pick was given here, too
+    let cherry_pick = opts.cherry_pick
+        || pull_request_number
+            .zip(store.as_ref())
+            .is_some_and(|(number, store)| store.cherry_pick(number));
+    let base_branch_config;
+    let mut base_branch_gh;
+    let (config, gh, trunk) = match &base_branch {
+        Some(branch) => {
+            base_branch_config = config.with_master_branch(branch);
+            base_branch_gh = gh.with_config(base_branch_config.clone());
+            (
+                &base_branch_config,
+                &mut base_branch_gh,
+                format!("\"{}\"@{}", branch, config.remote_name),
+            )
+        }
+        None => (config, gh, "trunk()".to_string()),
+    };
+
+    let mut prepared_commits = jj.get_prepared_commits_from_to(config, &trunk, revision, false)?;
     let based_on_unlanded_commits = prepared_commits.len() > 1;
-    if based_on_unlanded_commits && !opts.cherry_pick {
+    if based_on_unlanded_commits && !cherry_pick {
         return Err(Error::new(formatdoc!(
             "Cannot land a commit whose parent is not on {master}. To land \
              this commit, rebase it so that it is a direct child of {master}.
@@ -67,19 +244,41 @@ pub async fn land(
     } else {
         return Err(Error::new("This commit does not refer to a Pull Request."));
     };
-    // Load Pull Request information
-    let pull_request = gh.clone().get_pull_request(pull_request_number).await?;
-    if pull_request.state != PullRequestState::Open {
-        return Err(Error::new(formatdoc!(
-            "This Pull Request is already closed!",
-        )));
+    // Load Pull Request information. GitHub's mergeability check comes with
+    // it, which saves a round-trip in the common case that nothing has to be
+    // pushed before merging.
+    let (pull_request, mergeability) = gh
+        .clone()
+        .get_pull_request_with_mergeability(pull_request_number)
+        .await?;
+    check_landable(&pull_request, config)?;
+
+    // A base other than master may be the head branch of another Pull
+    // Request, which then has to land first.
+    if !pull_request.base.is_master_branch() {
+        let parent = match gh.find_pull_request_for_head(&pull_request.base).await? {
+            Some(number) => Some(gh.clone().get_pull_request(number).await?),
+            None => None,
+        };
+        check_parent_landed(&pull_request, parent.as_ref())?;
     }
-    if config.require_approval && pull_request.review_status != Some(ReviewStatus::Approved) {
+
+    let squash = config.merge_method == MergeMethod::Squash;
+    if opts.edit_message && !squash {
         return Err(Error::new(
-            "This Pull Request has not been approved on GitHub.",
+            "--edit-message can only be used when landing with the 'squash' merge method.",
         ));
     }
 
+    let mut merge_title = pull_request.title.clone();
+    let mut merge_message = squash_commit_message(&pull_request, config);
+    if opts.edit_message {
+        (merge_title, merge_message) = tokio::task::spawn_blocking(move || {
+            edit_merge_message(&dialoguer::Editor::new(), merge_title, merge_message)
+        })
+        .await??;
+    }
+
     output("🛫", "Getting started...")?;
     //
     // Fetch current master from GitHub.
@@ -90,15 +289,16 @@ pub async fn land(
         .arg("--")
         .arg(&config.remote_name)
         .arg(config.master_ref.on_github())
-        .output()
+        .logged_output()
         .await
         .reword("git fetch failed".to_string())?;
 
     let current_master = git.lock_and_resolve_reference(config.master_ref.local())?;
 
     let base_is_master = pull_request.base.is_master_branch();
-    let index = git.lock_and_cherrypick(prepared_commit.oid, current_master)?;
-    if index.has_conflicts() {
+    // This is the tree we are getting from cherrypicking the local commit
+    // on the selected base (master or stacked-on Pull Request).
+    let Some(our_tree_oid) = cherrypick_tree(jj, prepared_commit.oid, current_master)? else {
         return Err(Error::new(formatdoc!(
             "This commit cannot be applied on top of the '{master}' branch.
              Please rebase this commit.{unlanded}",
@@ -109,29 +309,13 @@ pub async fn land(
                 ""
             },
         )));
-    }
-
-    // This is the tree we are getting from cherrypicking the local commit
-    // on the selected base (master or stacked-on Pull Request).
-    let our_tree_oid = git.lock_and_write_index(index)?;
+    };
 
     // Now let's predict what merging the PR into the master branch would
-    // produce.
-    let merge_index = {
-        let repo = git.lock_repo();
-        let current_master = repo.find_commit(current_master)?;
-        let pr_head = repo.find_commit(pull_request.head_oid)?;
-        repo.merge_commits(&current_master, &pr_head)
-    }?;
-
-    // let merge_has_conflicts = merge_index.has_conflicts();
-
-    let merge_matches_cherrypick = if merge_index.has_conflicts() {
-        false
-    } else {
-        let merge_tree_oid = git.lock_and_write_index(merge_index)?;
-        merge_tree_oid == our_tree_oid
-    };
+    // produce. If that's not what we get from cherry-picking the local commit,
+    // the Pull Request is out of date (or conflicts with master).
+    let merge_matches_cherrypick =
+        merge_tree(jj, current_master, pull_request.head_oid)? == Some(our_tree_oid);
 
     if !merge_matches_cherrypick {
         return Err(Error::new(formatdoc!(
@@ -141,12 +325,74 @@ pub async fn land(
         )));
     }
 
+    let master_tree_oid = git.lock_and_get_tree_oid_for_commit(current_master)?;
+    let diff_stats = git.lock_and_get_tree_diff_stats(master_tree_oid, our_tree_oid)?;
+    output("📊", &diff_stats.to_string())?;
+
+    if opts.interactive {
+        output(
+            "🔎",
+            &land_preview(
+                &pull_request,
+                config,
+                &merge_title,
+                &merge_message,
+                &diff_stats.per_file,
+            ),
+        )?;
+        // Without a terminal to ask on, showing the preview is all we can do
+        if !opts.yes && std::io::stdin().is_terminal() {
+            let confirmed = tokio::task::spawn_blocking(|| {
+                dialoguer::Confirm::new()
+                    .with_prompt("Merge this Pull Request?")
+                    .default(false)
+                    .interact()
+            })
+            .await??;
+            if !confirmed {
+                return Err(Error::new("Landing cancelled."));
+            }
+        }
+    }
+
+    if config.record_provenance && opts.dry_run {
+        output(
+            "🔍",
+            "(dry run) would add a provenance note to the Pull Request body",
+        )?;
+    } else if config.record_provenance {
+        let change_id = jj.get_change_id_for_commit(prepared_commit.oid)?;
+        if let Some(body) = add_provenance_note(
+            pull_request.body.as_deref().unwrap_or_default(),
+            &provenance_note(&change_id),
+        ) {
+            gh.update_pull_request(
+                pull_request_number,
+                PullRequestUpdate {
+                    body: Some(body),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        }
+    }
+
     // Okay, we are confident now that the PR can be merged and the result of
     // that merge would be a master commit with the same tree as if we
     // cherry-picked the commit onto master.
     let mut pr_head_oid = pull_request.head_oid;
 
-    if !base_is_master {
+    if !base_is_master && opts.dry_run {
+        output(
+            "🔍",
+            &format!(
+                "(dry run) would change the base of Pull Request #{} from {} to {}",
+                pull_request_number,
+                pull_request.base.branch_name(),
+                config.master_ref.branch_name()
+            ),
+        )?;
+    } else if !base_is_master {
         // The base of the Pull Request on GitHub is not set to master. This
         // means the Pull Request uses a base branch. We tested above that
         // merging the Pull Request branch into the master branch produces the
@@ -184,15 +430,6 @@ pub async fn land(
         let pr_master_base = git.lock_and_get_merge_base(pr_base_oid, current_master)?;
         let pr_master_base_tree = git.lock_and_get_tree_oid_for_commit(pr_master_base)?;
 
-        gh.update_pull_request(
-            pull_request_number,
-            PullRequestUpdate {
-                base: Some(config.master_ref.branch_name().to_string()),
-                ..Default::default()
-            },
-        )
-        .await?;
-
         if pr_base_tree != pr_master_base_tree {
             // So the current file contents of the base branch are not the same
             // as those of the master branch commit that the base branch is
@@ -206,21 +443,8 @@ pub async fn land(
             // Here comes the additional merge-in-master commit on the Pull
             // Request branch that achieves that!
 
-            let message = if config.add_spr_banner_comment {
-                format!(
-                    "[spr] landed version\n\nCreated using spr {}",
-                    env!("CARGO_PKG_VERSION")
-                )
-            } else {
-                "landed version".to_string()
-            };
-
-            pr_head_oid = git.lock_and_create_derived_commit(
-                pr_head_oid,
-                &message,
-                our_tree_oid,
-                &[pr_head_oid, current_master],
-            )?;
+            pr_head_oid =
+                create_merge_master_commit(jj, config, pr_head_oid, current_master, our_tree_oid)?;
 
             let mut cmd = tokio::process::Command::new("git");
             cmd.arg("push")
@@ -233,6 +457,10 @@ pub async fn land(
                 .await
                 .reword("git push failed".to_string())?;
         }
+
+        // Only now that the Pull Request branch contains current master can
+        // we retarget it, so GitHub never shows the already-landed base
+        // branch changes as part of this Pull Request.
         gh.update_pull_request(
             pull_request_number,
             PullRequestUpdate {
@@ -243,16 +471,36 @@ pub async fn land(
         .await?;
     }
 
+    if opts.wait {
+        wait_for_checks(
+            gh,
+            pull_request_number,
+            pr_head_oid,
+            Duration::from_secs(opts.wait_timeout),
+        )
+        .await?;
+    }
+
     // // Check whether GitHub says this PR is mergeable. This happens in a
     // // retry-loop because recent changes to the Pull Request can mean that
     // // GitHub has not finished the mergeability check yet.
     let mut attempts = 0;
+    // The mergeability loaded with the Pull Request is only good for the
+    // first attempt if we haven't pushed or retargeted anything since
+    let mut prefetched_mergeability = Some(mergeability).filter(|mergeability| {
+        mergeability.head_oid == pr_head_oid
+            && (mergeability.base.is_master_branch() || opts.dry_run)
+    });
     let result = loop {
         attempts += 1;
 
-        let mergeability = gh
-            .get_pull_request_mergeability(pull_request_number)
-            .await?;
+        let mergeability = match prefetched_mergeability.take() {
+            Some(mergeability) => mergeability,
+            None => {
+                gh.get_pull_request_mergeability(pull_request_number)
+                    .await?
+            }
+        };
 
         if mergeability.head_oid != pr_head_oid {
             break Err(Error::new(formatdoc!(
@@ -261,7 +509,11 @@ pub async fn land(
             )));
         }
 
-        if mergeability.base.is_master_branch() && mergeability.mergeable.is_some() {
+        // In a dry run, the base of a stacked Pull Request was left alone, so
+        // GitHub's check is still against the base branch.
+        if (mergeability.base.is_master_branch() || opts.dry_run)
+            && mergeability.mergeable.is_some()
+        {
             if mergeability.mergeable != Some(true) {
                 break Err(Error::new(formatdoc!(
                     "GitHub concluded the Pull Request is not mergeable at \
@@ -269,7 +521,18 @@ pub async fn land(
                 )));
             }
 
-            if let Some(merge_commit) = mergeability.merge_commit {
+            // The merge queue takes care of branch protection itself
+            if mergeability.base.is_master_branch()
+                && !opts.queue
+                && let Some(error) =
+                    merge_state_error(mergeability.merge_state, config.master_ref.branch_name())
+            {
+                break Err(error);
+            }
+
+            if mergeability.base.is_master_branch()
+                && let Some(merge_commit) = mergeability.merge_commit
+            {
                 git.lock_and_fetch_commits_from_remote(&[merge_commit], &config.remote_name)
                     .await?;
 
@@ -285,52 +548,131 @@ pub async fn land(
             break Ok(());
         }
 
-        if attempts >= 10 {
-            // After ten failed attempts we give up.
-            break Err(Error::new(
-                "GitHub Pull Request did not update. Please try again!",
-            ));
+        if attempts >= config.land_poll_attempts {
+            // After the configured number of failed attempts we give up.
+            break Err(Error::new(format!(
+                "GitHub Pull Request did not update after {} attempts. Please \
+                 try again, or raise spr.landPollAttempts!",
+                attempts
+            )));
         }
 
-        // Wait one second before retrying
-        tokio::time::sleep(Duration::from_secs(1)).await;
+        tokio::time::sleep(Duration::from_millis(config.land_poll_interval_ms)).await;
     };
 
+    if opts.dry_run {
+        result?;
+        output(
+            "🔍",
+            &if opts.queue {
+                format!(
+                    "(dry run) would add Pull Request #{} to the merge queue",
+                    pull_request_number
+                )
+            } else {
+                format!(
+                    "(dry run) would merge Pull Request #{} into {} ({}): {}",
+                    pull_request_number,
+                    config.master_ref.branch_name(),
+                    format!("{:?}", config.merge_method).to_lowercase(),
+                    if squash {
+                        &merge_title
+                    } else {
+                        &pull_request.title
+                    }
+                )
+            },
+        )?;
+        if !opts.queue && !config.keep_branch_after_land {
+            output(
+                "🔍",
+                &format!(
+                    "(dry run) would delete branch {}",
+                    pull_request.head.branch_name()
+                ),
+            )?;
+            if !base_is_master {
+                output(
+                    "🔍",
+                    &format!(
+                        "(dry run) would delete base branch {}",
+                        pull_request.base.branch_name()
+                    ),
+                )?;
+            }
+        }
+        return Ok(());
+    }
+
     let result = match result {
+        Ok(()) if opts.queue => {
+            // The merge queue merges the Pull Request once its checks have
+            // passed, so there's nothing to clean up locally yet.
+            gh.enqueue_pull_request(pull_request_number, pr_head_oid)
+                .await
+                .and_then(|entry| {
+                    output(
+                        "🚦",
+                        &format!(
+                            "Added to the merge queue at position {} ({}){}",
+                            entry.position,
+                            entry.state,
+                            entry
+                                .estimated_time_to_merge
+                                .map(|seconds| format!(
+                                    ", estimated to merge in {} minute(s)",
+                                    (seconds + 59) / 60
+                                ))
+                                .unwrap_or_default(),
+                        ),
+                    )?;
+                    Ok(None)
+                })
+        }
         Ok(()) => {
             // We have checked that merging the Pull Request branch into the master
             // branch produces the intended result, and that's independent of whether we
             // used a base branch with this Pull Request or not. We have made sure the
             // target of the Pull Request is set to the master branch. So let GitHub do
             // the merge now!
-            octocrab::instance()
-                .pulls(&config.owner, &config.repo)
+            let octocrab = octocrab::instance();
+            let pulls = octocrab.pulls(&config.owner, &config.repo);
+            let mut merge = pulls
                 .merge(pull_request_number)
-                .method(octocrab::params::pulls::MergeMethod::Squash)
-                .title(pull_request.title)
-                .message(build_github_body_for_merging(&pull_request.sections))
-                .sha(format!("{}", pr_head_oid))
-                .send()
-                .await
-                .convert()
-                .and_then(|merge| {
-                    if merge.merged {
-                        Ok(merge)
-                    } else {
-                        Err(Error::new(formatdoc!(
-                            "GitHub Pull Request merge failed: {}",
-                            merge.message.unwrap_or_default()
-                        )))
-                    }
-                })
+                .method(config.merge_method)
+                .sha(format!("{}", pr_head_oid));
+            // Merge commits and rebase merges keep the commit messages from
+            // the Pull Request branch, so title and message only apply when
+            // squashing.
+            if squash {
+                merge = merge.title(merge_title).message(merge_message);
+            }
+            merge.send().await.convert().and_then(|merge| {
+                if merge.merged {
+                    Ok(Some(merge))
+                } else {
+                    Err(Error::new(formatdoc!(
+                        "GitHub Pull Request merge failed: {}",
+                        merge.message.unwrap_or_default()
+                    )))
+                }
+            })
         }
         Err(err) => Err(err),
     };
 
     let merge = match result {
-        Ok(merge) => merge,
+        Ok(Some(merge)) => merge,
+        Ok(None) => return Ok(()),
         Err(mut error) => {
-            output("❌", "GitHub Pull Request merge failed")?;
+            output(
+                "❌",
+                if opts.queue {
+                    "Adding the Pull Request to the merge queue failed"
+                } else {
+                    "GitHub Pull Request merge failed"
+                },
+            )?;
 
             // If we changed the target branch of the Pull Request earlier, then
             // undo this change now.
@@ -355,18 +697,24 @@ pub async fn land(
 
     output("🛬", "Landed!")?;
 
-    let mut remove_old_branch_child_process = tokio::process::Command::new("git")
-        .arg("push")
-        .arg("--no-verify")
-        .arg("--delete")
-        .arg("--")
-        .arg(&config.remote_name)
-        .arg(pull_request.head.on_github())
-        .stdout(Stdio::null())
-        .stderr(Stdio::null())
-        .spawn()?;
+    let remove_old_branch_child_process = if config.keep_branch_after_land {
+        None
+    } else {
+        Some(
+            tokio::process::Command::new("git")
+                .arg("push")
+                .arg("--no-verify")
+                .arg("--delete")
+                .arg("--")
+                .arg(&config.remote_name)
+                .arg(pull_request.head.on_github())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()?,
+        )
+    };
 
-    let remove_old_base_branch_child_process = if base_is_master {
+    let remove_old_base_branch_child_process = if base_is_master || config.keep_branch_after_land {
         None
     } else {
         Some(
@@ -398,7 +746,7 @@ pub async fn land(
                 .arg(&sha)
                 .stdout(Stdio::null())
                 .stderr(Stdio::piped())
-                .output()
+                .logged_output()
                 .await?;
             if git_fetch.status.success() {
                 break;
@@ -408,20 +756,694 @@ pub async fn land(
             }
         }
 
-        // FIXME: put the jj mainline name into configjj
-        output(
-            "⚠️",
-            "Please manually rebase your working copy after landing",
-        )?;
+        // Move everything that was stacked on top of the landed change onto
+        // the new master commit, and drop the landed change itself. When
+        // landing with --cherry-pick on top of unlanded changes, the
+        // descendants stay where they are, on top of those changes.
+        // With spr.abandonAfterLand unset, local changes are left alone.
+        let rebased = if config.abandon_after_land {
+            let new_master = git.lock_and_resolve_reference(config.master_ref.local())?;
+            jj.abandon_landed_commit(
+                prepared_commit.oid,
+                (!based_on_unlanded_commits).then_some(new_master),
+            )?
+        } else {
+            0
+        };
+        if rebased > 0 && !based_on_unlanded_commits {
+            output(
+                "🔁",
+                &format!(
+                    "Rebased {} descendant(s) onto {}",
+                    rebased,
+                    config.master_ref.branch_name()
+                ),
+            )?;
+        }
     }
 
     // Wait for the "git push" to delete the old Pull Request branch to finish,
     // but ignore the result. GitHub may be configured to delete the branch
     // automatically, in which case it's gone already and this command fails.
-    remove_old_branch_child_process.wait().await?;
+    if let Some(mut proc) = remove_old_branch_child_process {
+        proc.wait().await?;
+    }
     if let Some(mut proc) = remove_old_base_branch_child_process {
         proc.wait().await?;
     }
 
     Ok(())
 }
+
+/// Wait until the checks on `head_oid`, the head of the given Pull Request,
+/// have passed. Fails as soon as one of them fails, or when `timeout` is up.
+/// Right after a push GitHub may not know about any checks yet, so a commit
+/// without checks only counts as passed once it stays that way for a poll.
+async fn wait_for_checks(
+    gh: &crate::github::GitHub,
+    number: u64,
+    head_oid: git2::Oid,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let mut waited_for_checks_to_appear = false;
+    loop {
+        let checks = gh.get_check_status(number, head_oid).await?;
+        if checks.checks.is_empty() && !waited_for_checks_to_appear {
+            waited_for_checks_to_appear = true;
+            let now = tokio::time::Instant::now();
+            if now < deadline {
+                output("⏳", "Waiting for checks to be reported")?;
+                tokio::time::sleep(CHECKS_POLL_INTERVAL.min(deadline - now)).await;
+                continue;
+            }
+        }
+        match checks.status() {
+            ChecksStatus::Success => {
+                output("✅", "All checks have passed")?;
+                return Ok(());
+            }
+            ChecksStatus::Failure => {
+                return Err(Error::new(format!(
+                    "Not landing because these checks failed: {}",
+                    checks.names(ChecksStatus::Failure).join(", ")
+                )));
+            }
+            ChecksStatus::Pending => {
+                let pending = checks.names(ChecksStatus::Pending).join(", ");
+                let now = tokio::time::Instant::now();
+                if now >= deadline {
+                    return Err(Error::new(format!(
+                        "Gave up waiting for these checks after {} seconds: {}",
+                        timeout.as_secs(),
+                        pending
+                    )));
+                }
+                output("⏳", &format!("Waiting for checks: {}", pending))?;
+                tokio::time::sleep(CHECKS_POLL_INTERVAL.min(deadline - now)).await;
+            }
+        }
+    }
+}
+
+/// The message of the squash commit below its title, as chosen by
+/// `spr.landMessageFormat`.
+fn squash_commit_message(pull_request: &PullRequest, config: &crate::config::Config) -> String {
+    let mut message = match config.land_message_format {
+        LandMessageFormat::Full => {
+            return build_github_body_for_merging(&pull_request.sections, config);
+        }
+        LandMessageFormat::TitleOnly => String::new(),
+        LandMessageFormat::TitleAndLink => config.pull_request_url(pull_request.number),
+    };
+    // Co-authors are credited whatever the format
+    append_co_author_trailers(&mut message, &pull_request.sections);
+    message
+}
+
+/// Why GitHub won't merge a Pull Request into `master` although it has no
+/// conflicts, if its merge state says so.
+fn merge_state_error(merge_state: MergeState, master: &str) -> Option<Error> {
+    match merge_state {
+        MergeState::Blocked => Some(Error::new(formatdoc!(
+            "Branch protection on {master} blocks merging this Pull Request. \
+             Its required checks may not have passed yet (pass `--wait` to \
+             wait for them), or it may need more approvals."
+        ))),
+        MergeState::Behind => Some(Error::new(formatdoc!(
+            "Branch protection on {master} requires the Pull Request to be up \
+             to date with it. Please rebase your changes onto {master}, run \
+             `jj-spr diff` and then try `jj-spr land` again!"
+        ))),
+        MergeState::Draft => Some(Error::new(
+            "The Pull Request is a draft. Mark it as ready for review with \
+             `jj-spr diff --ready` first.",
+        )),
+        MergeState::Clean | MergeState::Dirty | MergeState::Unknown => None,
+    }
+}
+
+/// Check that a Pull Request is open and, if required, approved.
+fn check_landable(pull_request: &PullRequest, config: &crate::config::Config) -> Result<()> {
+    if pull_request.state != PullRequestState::Open {
+        return Err(Error::new(formatdoc!(
+            "This Pull Request is already closed!",
+        )));
+    }
+    if config.require_approval && pull_request.review_status != Some(ReviewStatus::Approved) {
+        return Err(Error::new(
+            "This Pull Request has not been approved on GitHub.",
+        ));
+    }
+    Ok(())
+}
+
+/// Check that a Pull Request is not stacked on top of another one that has
+/// not landed yet. `parent` is the Pull Request whose head branch is the base
+/// of `pull_request`, if there is one.
+fn check_parent_landed(pull_request: &PullRequest, parent: Option<&PullRequest>) -> Result<()> {
+    match parent {
+        Some(parent) if parent.state == PullRequestState::Open => Err(Error::new(formatdoc!(
+            "Pull Request #{number} is based on #{parent}, which has not \
+             landed yet. Land #{parent} first, or land both together by \
+             passing a range to `spr land -r`.",
+            number = pull_request.number,
+            parent = parent.number,
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// The tree of `commit_oid` cherry-picked onto `onto_oid`, or `None` if that
+/// conflicts.
+fn cherrypick_tree(
+    jj: &crate::jj::Jujutsu,
+    commit_oid: git2::Oid,
+    onto_oid: git2::Oid,
+) -> Result<Option<git2::Oid>> {
+    let index = jj.cherrypick(commit_oid, onto_oid)?;
+    if index.has_conflicts() {
+        Ok(None)
+    } else {
+        jj.write_index(index).map(Some)
+    }
+}
+
+/// What `spr land --interactive` shows before asking to merge.
+fn land_preview(
+    pull_request: &PullRequest,
+    config: &crate::config::Config,
+    merge_title: &str,
+    merge_message: &str,
+    diff_stat: &str,
+) -> String {
+    let mut approvers: Vec<&str> = pull_request
+        .reviewers
+        .iter()
+        .filter(|(_, status)| **status == ReviewStatus::Approved)
+        .map(|(login, _)| &login[..])
+        .collect();
+    approvers.sort();
+
+    let method = match config.merge_method {
+        MergeMethod::Squash => "squash",
+        MergeMethod::Merge => "merge",
+        MergeMethod::Rebase => "rebase",
+        _ => "merge",
+    };
+
+    let mut preview = format!(
+        "Pull Request #{} will be merged into {} ({})\nApproved by: {}\n",
+        pull_request.number,
+        config.master_ref.branch_name(),
+        method,
+        if approvers.is_empty() {
+            "nobody".to_string()
+        } else {
+            approvers.join(", ")
+        },
+    );
+    if config.merge_method == MergeMethod::Squash {
+        preview.push_str(&format!(
+            "\nCommit message:\n{}\n\n{}\n",
+            merge_title,
+            merge_message.trim_end()
+        ));
+    }
+    preview.push_str(&format!("\nChanges:\n{}", diff_stat));
+
+    preview
+}
+
+/// The tree of merging `their_oid` into `our_oid`, or `None` if that
+/// conflicts.
+fn merge_tree(
+    jj: &crate::jj::Jujutsu,
+    our_oid: git2::Oid,
+    their_oid: git2::Oid,
+) -> Result<Option<git2::Oid>> {
+    let ours = jj.git_repo.find_commit(our_oid)?;
+    let theirs = jj.git_repo.find_commit(their_oid)?;
+    let index = jj.git_repo.merge_commits(&ours, &theirs, None)?;
+    if index.has_conflicts() {
+        Ok(None)
+    } else {
+        jj.write_index(index).map(Some)
+    }
+}
+
+/// Create the "landed version" commit that merges current master into the
+/// Pull Request branch. Its tree is the one of the local commit cherry-picked
+/// onto master, so the merge does not change what the Pull Request lands, but
+/// changes from the base branch that are already on master stop showing up as
+/// part of the Pull Request.
+fn create_merge_master_commit(
+    jj: &crate::jj::Jujutsu,
+    config: &crate::config::Config,
+    pr_head_oid: git2::Oid,
+    current_master: git2::Oid,
+    landed_tree_oid: git2::Oid,
+) -> Result<git2::Oid> {
+    let message = if config.add_spr_banner_comment {
+        format!(
+            "[spr] landed version\n\nCreated using spr {}",
+            env!("CARGO_PKG_VERSION")
+        )
+    } else {
+        "landed version".to_string()
+    };
+
+    jj.create_derived_commit(
+        pr_head_oid,
+        &message,
+        landed_tree_oid,
+        &[pr_head_oid, current_master],
+        false,
+    )
+}
+
+fn provenance_note(change_id: &str) -> String {
+    format!(
+        "Landed by spr {} from change {}",
+        env!("CARGO_PKG_VERSION"),
+        change_id
+    )
+}
+
+/// Append the provenance note to a Pull Request body. Returns `None` if the
+/// body already ends with that note, e.g. because an earlier attempt to land
+/// got that far. A note left by an earlier attempt from a different version or
+/// change is replaced.
+fn add_provenance_note(body: &str, note: &str) -> Option<String> {
+    let kept: Vec<&str> = body
+        .trim_end()
+        .lines()
+        .filter(|line| !line.starts_with("Landed by spr "))
+        .collect();
+    let kept = kept.join("\n");
+    let kept = kept.trim_end();
+
+    let new_body = if kept.is_empty() {
+        note.to_string()
+    } else {
+        format!("{}\n\n{}", kept, note)
+    };
+
+    if new_body == body.trim_end() {
+        None
+    } else {
+        Some(new_body)
+    }
+}
+
+/// Let the user edit the squash commit message in their editor. The first line
+/// of the edited text becomes the title, the rest the message. If the editor
+/// was closed without saving, or the text was cleared, the original title and
+/// message are kept.
+fn edit_merge_message(
+    editor: &dialoguer::Editor,
+    title: String,
+    message: String,
+) -> Result<(String, String)> {
+    let edited = editor.edit(&format!("{}\n\n{}", title, message))?;
+
+    match edited.as_deref().and_then(parse_edited_merge_message) {
+        Some((new_title, new_message)) => {
+            if new_title != title || new_message != message {
+                output("✍", "Using edited squash commit message")?;
+            }
+            Ok((new_title, new_message))
+        }
+        None => {
+            output("✍", "Squash commit message left unchanged")?;
+            Ok((title, message))
+        }
+    }
+}
+
+fn parse_edited_merge_message(text: &str) -> Option<(String, String)> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let (title, message) = text.split_once('\n').unwrap_or((text, ""));
+    let title = title.trim();
+    if title.is_empty() {
+        return None;
+    }
+
+    Some((title.to_string(), message.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_parse_edited_merge_message() {
+        assert_eq!(parse_edited_merge_message(""), None);
+        assert_eq!(parse_edited_merge_message("  \n\n "), None);
+        assert_eq!(
+            parse_edited_merge_message("Title"),
+            Some(("Title".to_string(), "".to_string()))
+        );
+        assert_eq!(
+            parse_edited_merge_message("Title\n\nSummary\n\nTest Plan: none\n"),
+            Some((
+                "Title".to_string(),
+                "Summary\n\nTest Plan: none".to_string()
+            ))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_merge_message_uses_edited_text() {
+        let mut editor = dialoguer::Editor::new();
+        editor
+            .executable("sh -c 'printf \"Edited title\\n\\nEdited body\\n\" > \"$0\"'")
+            .require_save(false);
+
+        let (title, message) =
+            edit_merge_message(&editor, "Title".to_string(), "Body".to_string()).unwrap();
+        assert_eq!(title, "Edited title");
+        assert_eq!(message, "Edited body");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_merge_message_keeps_original_when_cleared() {
+        let mut editor = dialoguer::Editor::new();
+        editor.executable("sh -c ': > \"$0\"'").require_save(false);
+
+        let (title, message) =
+            edit_merge_message(&editor, "Title".to_string(), "Body".to_string()).unwrap();
+        assert_eq!(title, "Title");
+        assert_eq!(message, "Body");
+    }
+
+    #[test]
+    fn test_add_provenance_note_once() {
+        let note = "Landed by spr 1.0.0 from change qpvuntsm";
+
+        let body = add_provenance_note("Summary\n\nTest Plan: none\n", note).unwrap();
+        assert_eq!(body, format!("Summary\n\nTest Plan: none\n\n{}", note));
+
+        // Retrying doesn't add the note again...
+        assert_eq!(add_provenance_note(&body, note), None);
+        assert_eq!(body.matches("Landed by spr").count(), 1);
+
+        // ...and a note from an earlier attempt is replaced, not duplicated
+        let other_note = "Landed by spr 1.0.1 from change qpvuntsm";
+        let body = add_provenance_note(&body, other_note).unwrap();
+        assert_eq!(body.matches("Landed by spr").count(), 1);
+        assert!(body.ends_with(other_note));
+
+        assert_eq!(add_provenance_note("", note).as_deref(), Some(note));
+    }
+
+    fn mock_pull_request(number: u64, review_status: Option<ReviewStatus>) -> PullRequest {
+        PullRequest {
+            number,
+            state: PullRequestState::Open,
+            title: format!("PR {}", number),
+            body: None,
+            sections: Default::default(),
+            base: crate::github::GitHubBranch::new_from_branch_name("main", "origin", "main"),
+            head: crate::github::GitHubBranch::new_from_branch_name(
+                &format!("spr/test/pr-{}", number),
+                "origin",
+                "main",
+            ),
+            base_oid: git2::Oid::zero(),
+            head_oid: git2::Oid::zero(),
+            merge_commit: None,
+            reviewers: Default::default(),
+            labels: Vec::new(),
+            review_status,
+            checks_status: None,
+        }
+    }
+
+    #[test]
+    fn test_land_preview() {
+        let config = test_config();
+        let mut pull_request = mock_pull_request(5, Some(ReviewStatus::Approved));
+        pull_request.reviewers = HashMap::from([
+            ("carol".to_string(), ReviewStatus::Approved),
+            ("bob".to_string(), ReviewStatus::Requested),
+            ("alice".to_string(), ReviewStatus::Approved),
+        ]);
+
+        let preview = land_preview(
+            &pull_request,
+            &config,
+            "Add frobnicator",
+            "It frobs.\n\nTest Plan: ran it",
+            " src/frob.rs | 3 +++\n 1 file changed, 3 insertions(+)",
+        );
+        assert!(preview.contains("#5 will be merged into main (squash)"));
+        assert!(preview.contains("Approved by: alice, carol\n"));
+        assert!(preview.contains("Add frobnicator\n\nIt frobs.\n\nTest Plan: ran it"));
+        assert!(preview.contains("src/frob.rs | 3 +++"));
+    }
+
+    #[test]
+    fn test_squash_commit_message() {
+        let mut config = test_config();
+        let mut pull_request = mock_pull_request(5, Some(ReviewStatus::Approved));
+        pull_request.sections.insert(
+            crate::message::MessageSection::Summary,
+            "It frobs.".to_string(),
+        );
+
+        assert!(squash_commit_message(&pull_request, &config).starts_with("It frobs."));
+        config.land_message_format = LandMessageFormat::TitleOnly;
+        assert_eq!(squash_commit_message(&pull_request, &config), "");
+        config.land_message_format = LandMessageFormat::TitleAndLink;
+        assert_eq!(
+            squash_commit_message(&pull_request, &config),
+            config.pull_request_url(5)
+        );
+
+        // Co-authors are kept in every format
+        pull_request.sections.insert(
+            crate::message::MessageSection::CoAuthors,
+            "Jane Doe <jane@example.com>".to_string(),
+        );
+        assert_eq!(
+            squash_commit_message(&pull_request, &config),
+            format!(
+                "{}\n\nCo-authored-by: Jane Doe <jane@example.com>\n",
+                config.pull_request_url(5)
+            )
+        );
+        config.land_message_format = LandMessageFormat::TitleOnly;
+        assert_eq!(
+            squash_commit_message(&pull_request, &config),
+            "Co-authored-by: Jane Doe <jane@example.com>\n"
+        );
+    }
+
+    #[test]
+    fn test_check_landable() {
+        let mut config = test_config();
+        let approved = mock_pull_request(1, Some(ReviewStatus::Approved));
+        let unapproved = mock_pull_request(2, Some(ReviewStatus::Requested));
+        let mut closed = mock_pull_request(3, Some(ReviewStatus::Approved));
+        closed.state = PullRequestState::Closed;
+
+        assert!(check_landable(&approved, &config).is_ok());
+        assert!(check_landable(&unapproved, &config).is_ok());
+        assert!(check_landable(&closed, &config).is_err());
+
+        // With approval required, one unapproved Pull Request in a stack is
+        // enough to refuse landing any of it
+        config.require_approval = true;
+        let stack = [&approved, &unapproved];
+        assert!(check_landable(stack[0], &config).is_ok());
+        assert!(
+            stack
+                .iter()
+                .map(|pull_request| check_landable(pull_request, &config))
+                .collect::<Result<Vec<_>>>()
+                .is_err()
+        );
+    }
+
+    /// Commit `files` on top of `parents`, where the tree is `base_tree` with
+    /// the given files added.
+    fn commit_files(
+        repo: &git2::Repository,
+        base_tree: Option<git2::Oid>,
+        files: &[(&str, &str)],
+        parents: &[git2::Oid],
+        message: &str,
+    ) -> git2::Oid {
+        let base_tree = base_tree.map(|oid| repo.find_tree(oid).unwrap());
+        let mut builder = repo.treebuilder(base_tree.as_ref()).unwrap();
+        for (name, content) in files {
+            let blob = repo.blob(content.as_bytes()).unwrap();
+            builder.insert(name, blob, 0o100644).unwrap();
+        }
+        let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let parents: Vec<_> = parents
+            .iter()
+            .map(|oid| repo.find_commit(*oid).unwrap())
+            .collect();
+        let parent_refs: Vec<_> = parents.iter().collect();
+        repo.commit(None, &signature, &signature, message, &tree, &parent_refs)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_merge_state_error() {
+        let blocked = merge_state_error(MergeState::Blocked, "main").unwrap();
+        assert!(blocked.to_string().contains("required checks"));
+        let behind = merge_state_error(MergeState::Behind, "main").unwrap();
+        assert!(behind.to_string().contains("rebase your changes onto main"));
+        assert!(merge_state_error(MergeState::Draft, "main").is_some());
+
+        assert!(merge_state_error(MergeState::Clean, "main").is_none());
+        assert!(merge_state_error(MergeState::Unknown, "main").is_none());
+    }
+
+    #[test]
+    fn test_cherrypick_validation_detects_stale_pull_request() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        std::fs::create_dir(temp_dir.path().join(".jj")).unwrap();
+        let tree_of = |oid: git2::Oid| repo.find_commit(oid).unwrap().tree_id();
+
+        let master = commit_files(&repo, None, &[("README", "readme")], &[], "Initial");
+        let pr_head = commit_files(
+            &repo,
+            Some(tree_of(master)),
+            &[("feature.txt", "v1")],
+            &[master],
+            "Feature",
+        );
+        // Master moved on with an unrelated change
+        let current_master = commit_files(
+            &repo,
+            Some(tree_of(master)),
+            &[("other.txt", "other")],
+            &[master],
+            "Other",
+        );
+        // The local commit was amended after the Pull Request was pushed
+        let amended = commit_files(
+            &repo,
+            Some(tree_of(master)),
+            &[("feature.txt", "v2")],
+            &[master],
+            "Feature",
+        );
+
+        let jj = crate::jj::Jujutsu::new(git2::Repository::open(temp_dir.path()).unwrap()).unwrap();
+        let merged = merge_tree(&jj, current_master, pr_head).unwrap();
+        assert!(merged.is_some());
+
+        // Up to date: the Pull Request lands what the local commit has
+        let up_to_date = cherrypick_tree(&jj, pr_head, current_master).unwrap();
+        assert_eq!(up_to_date, merged);
+
+        // Stale: landing would not include the local amendment
+        let stale = cherrypick_tree(&jj, amended, current_master).unwrap();
+        assert!(stale.is_some());
+        assert_ne!(stale, merged);
+    }
+
+    #[test]
+    fn test_merge_master_commit_shows_only_top_commit_changes() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        std::fs::create_dir(temp_dir.path().join(".jj")).unwrap();
+        let tree_of = |oid: git2::Oid| repo.find_commit(oid).unwrap().tree_id();
+
+        // master, with a two-commit stack on top: the bottom commit is the
+        // base branch of the top commit's Pull Request
+        let master = commit_files(&repo, None, &[("README", "readme")], &[], "Initial");
+        let bottom = commit_files(
+            &repo,
+            Some(tree_of(master)),
+            &[("bottom.txt", "bottom")],
+            &[master],
+            "Bottom",
+        );
+        let top = commit_files(
+            &repo,
+            Some(tree_of(bottom)),
+            &[("top.txt", "top")],
+            &[bottom],
+            "Top",
+        );
+
+        // The bottom commit got landed, so master moved on
+        let current_master = commit_files(
+            &repo,
+            Some(tree_of(master)),
+            &[("bottom.txt", "bottom")],
+            &[master],
+            "Bottom (#1)",
+        );
+
+        let jj = crate::jj::Jujutsu::new(git2::Repository::open(temp_dir.path()).unwrap()).unwrap();
+        let index = jj.cherrypick(top, current_master).unwrap();
+        assert!(!index.has_conflicts());
+        let landed_tree = jj.write_index(index).unwrap();
+
+        let merge =
+            create_merge_master_commit(&jj, &test_config(), top, current_master, landed_tree)
+                .unwrap();
+
+        let merge_commit = repo.find_commit(merge).unwrap();
+        assert_eq!(
+            merge_commit.parent_ids().collect::<Vec<_>>(),
+            vec![top, current_master]
+        );
+
+        // What GitHub shows as the Pull Request's changes once it targets
+        // master: the diff between the merge base and the head
+        let merge_base = repo.merge_base(merge, current_master).unwrap();
+        assert_eq!(merge_base, current_master);
+        let diff = repo
+            .diff_tree_to_tree(
+                Some(&repo.find_commit(merge_base).unwrap().tree().unwrap()),
+                Some(&merge_commit.tree().unwrap()),
+                None,
+            )
+            .unwrap();
+        let changed: Vec<_> = diff
+            .deltas()
+            .map(|delta| delta.new_file().path().unwrap().to_path_buf())
+            .collect();
+        assert_eq!(changed, vec![std::path::PathBuf::from("top.txt")]);
+    }
+
+    #[test]
+    fn test_check_parent_landed() {
+        let mut pull_request = mock_pull_request(2, None);
+        pull_request.base =
+            crate::github::GitHubBranch::new_from_branch_name("spr/test/pr-1", "origin", "main");
+        let mut parent = mock_pull_request(1, None);
+
+        let error = check_parent_landed(&pull_request, Some(&parent)).unwrap_err();
+        assert!(
+            error
+                .messages()
+                .iter()
+                .any(|message| message.contains("based on #1, which has not landed yet"))
+        );
+
+        parent.state = PullRequestState::Closed;
+        assert!(check_parent_landed(&pull_request, Some(&parent)).is_ok());
+        // A base branch that no Pull Request has as its head, e.g. one made
+        // by `spr diff --cherry-pick`
+        assert!(check_parent_landed(&pull_request, None).is_ok());
+    }
+}
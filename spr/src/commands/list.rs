@@ -20,33 +20,151 @@ type URI = String;
 )]
 pub struct SearchQuery;
 
-pub async fn list(graphql_client: reqwest::Client, config: &crate::config::Config) -> Result<()> {
+#[derive(Debug, clap::Parser)]
+pub struct ListOptions {
+    /// List Pull Requests that are waiting for your review instead of your
+    /// own, most recently updated first
+    #[clap(long)]
+    needs_my_review: bool,
+
+    /// List Pull Requests by this GitHub user instead of your own
+    #[clap(long, value_name = "LOGIN", conflicts_with = "needs_my_review")]
+    author: Option<String>,
+
+    /// Which Pull Requests to list, by state
+    #[clap(long, value_enum, default_value_t = ListState::Open)]
+    state: ListState,
+
+    /// Print the Pull Requests as a JSON array, for use in scripts
+    #[clap(long)]
+    json: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ListState {
+    Open,
+    Closed,
+    Merged,
+    All,
+}
+
+/// A Pull Request as printed by `spr list --json`.
+#[derive(Debug, serde::Serialize)]
+struct PullRequestSummary {
+    number: i64,
+    title: String,
+    url: String,
+    review_decision: Option<String>,
+    state: String,
+    head_branch: String,
+}
+
+pub async fn list(
+    opts: ListOptions,
+    graphql_client: reqwest::Client,
+    config: &crate::config::Config,
+) -> Result<()> {
     let variables = search_query::Variables {
-        query: format!(
-            "repo:{}/{} is:open is:pr author:@me archived:false",
-            config.owner, config.repo
-        ),
+        query: build_search_query(&opts, config),
     };
     let request_body = SearchQuery::build_query(variables);
     let res = graphql_client
-        .post("https://api.github.com/graphql")
+        .post(&config.github_graphql_url)
         .json(&request_body)
         .send()
         .await?;
     let response_body: Response<search_query::ResponseData> = res.json().await?;
+    let pull_requests = pull_requests(response_body, !opts.needs_my_review)
+        .ok_or_else(|| Error::new("unexpected error"))?;
+
+    if opts.json {
+        let summaries = pull_request_summaries(pull_requests);
+        console::Term::stdout().write_line(&serde_json::to_string_pretty(&summaries)?)?;
+        return Ok(());
+    }
 
-    print_pr_info(response_body).ok_or_else(|| Error::new("unexpected error"))
+    print_pr_info(pull_requests).ok_or_else(|| Error::new("unexpected error"))
 }
 
-fn print_pr_info(response_body: Response<search_query::ResponseData>) -> Option<()> {
+type PullRequestNode = search_query::SearchQuerySearchNodesOnPullRequest;
+
+/// The Pull Requests in a search response. Unless GitHub was asked to sort
+/// them (see [`build_search_query`]), they are put in order of their number,
+/// so that the listing is stable and a stack reads from the bottom up.
+fn pull_requests(
+    response_body: Response<search_query::ResponseData>,
+    sort_by_number: bool,
+) -> Option<Vec<PullRequestNode>> {
+    let mut pull_requests: Vec<_> = response_body
+        .data?
+        .search
+        .nodes?
+        .into_iter()
+        .filter_map(|node| match node {
+            Some(search_query::SearchQuerySearchNodes::PullRequest(pr)) => Some(pr),
+            _ => None,
+        })
+        .collect();
+    if sort_by_number {
+        pull_requests.sort_by_key(|pr| pr.number);
+    }
+
+    Some(pull_requests)
+}
+
+fn pull_request_summaries(pull_requests: Vec<PullRequestNode>) -> Vec<PullRequestSummary> {
+    use search_query::{PullRequestReviewDecision, PullRequestState};
+
+    pull_requests
+        .into_iter()
+        .map(|pr| PullRequestSummary {
+            number: pr.number,
+            title: pr.title,
+            url: pr.url,
+            review_decision: pr.review_decision.map(|decision| match decision {
+                PullRequestReviewDecision::APPROVED => "approved".to_string(),
+                PullRequestReviewDecision::CHANGES_REQUESTED => "changes_requested".to_string(),
+                PullRequestReviewDecision::REVIEW_REQUIRED => "review_required".to_string(),
+                PullRequestReviewDecision::Other(other) => other.to_lowercase(),
+            }),
+            state: match pr.state {
+                PullRequestState::OPEN => "open".to_string(),
+                PullRequestState::CLOSED => "closed".to_string(),
+                PullRequestState::MERGED => "merged".to_string(),
+                PullRequestState::Other(other) => other.to_lowercase(),
+            },
+            head_branch: pr.head_ref_name,
+        })
+        .collect()
+}
+
+fn build_search_query(opts: &ListOptions, config: &crate::config::Config) -> String {
+    // The filtering is done by GitHub's search, so that listing stays quick
+    // in repositories with many Pull Requests.
+    let filter = if opts.needs_my_review {
+        "review-requested:@me sort:updated-desc".to_string()
+    } else {
+        match &opts.author {
+            Some(author) => format!("author:{}", author.trim_start_matches('@')),
+            None => "author:@me".to_string(),
+        }
+    };
+    let state = match opts.state {
+        ListState::Open => "is:open ",
+        ListState::Closed => "is:closed is:unmerged ",
+        ListState::Merged => "is:merged ",
+        ListState::All => "",
+    };
+
+    format!(
+        "repo:{}/{} {}is:pr {} archived:false",
+        config.owner, config.repo, state, filter
+    )
+}
+
+fn print_pr_info(pull_requests: Vec<PullRequestNode>) -> Option<()> {
     let term = console::Term::stdout();
-    for pr in response_body.data?.search.nodes? {
-        let pr = match pr {
-            Some(crate::commands::list::search_query::SearchQuerySearchNodes::PullRequest(pr)) => {
-                pr
-            }
-            _ => continue,
-        };
+    for pr in pull_requests {
         let dummy: String;
         let decision = match pr.review_decision {
             Some(search_query::PullRequestReviewDecision::APPROVED) => {
@@ -73,3 +191,133 @@ fn print_pr_info(response_body: Response<search_query::ResponseData>) -> Option<
     }
     Some(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::test_config;
+
+    #[test]
+    fn test_search_query_defaults_to_own_pull_requests() {
+        let opts = ListOptions {
+            needs_my_review: false,
+            json: false,
+            author: None,
+            state: ListState::Open,
+        };
+        let query = build_search_query(&opts, &test_config());
+
+        assert_eq!(
+            query,
+            "repo:acme/codez is:open is:pr author:@me archived:false"
+        );
+    }
+
+    #[test]
+    fn test_search_query_needs_my_review() {
+        let opts = ListOptions {
+            needs_my_review: true,
+            json: false,
+            author: None,
+            state: ListState::Open,
+        };
+        let query = build_search_query(&opts, &test_config());
+
+        assert!(query.contains("review-requested:@me"));
+        assert!(query.contains("sort:updated-desc"));
+        assert!(!query.contains("author:@me"));
+    }
+
+    #[test]
+    fn test_search_query_author_and_state() {
+        let opts = ListOptions {
+            needs_my_review: false,
+            json: false,
+            author: Some("@octocat".into()),
+            state: ListState::Merged,
+        };
+        assert_eq!(
+            build_search_query(&opts, &test_config()),
+            "repo:acme/codez is:merged is:pr author:octocat archived:false"
+        );
+
+        let opts = ListOptions {
+            state: ListState::All,
+            author: None,
+            ..opts
+        };
+        assert_eq!(
+            build_search_query(&opts, &test_config()),
+            "repo:acme/codez is:pr author:@me archived:false"
+        );
+    }
+
+    #[test]
+    fn test_pull_request_summaries_json() {
+        let response_body: Response<search_query::ResponseData> =
+            serde_json::from_value(serde_json::json!({
+                "data": {
+                    "search": {
+                        "nodes": [
+                            {
+                                "__typename": "PullRequest",
+                                "number": 12,
+                                "title": "Add frobnicator",
+                                "url": "https://github.com/acme/codez/pull/12",
+                                "reviewDecision": "APPROVED",
+                                "state": "OPEN",
+                                "headRefName": "spr/test/add-frobnicator"
+                            },
+                            { "__typename": "Issue" }
+                        ]
+                    }
+                }
+            }))
+            .unwrap();
+
+        let summaries = pull_request_summaries(pull_requests(response_body, true).unwrap());
+        assert_eq!(
+            serde_json::to_value(&summaries).unwrap(),
+            serde_json::json!([{
+                "number": 12,
+                "title": "Add frobnicator",
+                "url": "https://github.com/acme/codez/pull/12",
+                "review_decision": "approved",
+                "state": "open",
+                "head_branch": "spr/test/add-frobnicator"
+            }])
+        );
+    }
+
+    #[test]
+    fn test_pull_requests_order() {
+        let node = |number: i64| {
+            serde_json::json!({
+                "__typename": "PullRequest",
+                "number": number,
+                "title": format!("PR {}", number),
+                "url": format!("https://github.com/acme/codez/pull/{}", number),
+                "reviewDecision": null,
+                "state": "OPEN",
+                "headRefName": format!("spr/test/pr-{}", number)
+            })
+        };
+        let response = || -> Response<search_query::ResponseData> {
+            serde_json::from_value(serde_json::json!({
+                "data": { "search": { "nodes": [node(31), node(4), node(17), node(30)] } }
+            }))
+            .unwrap()
+        };
+        let numbers = |sort_by_number| -> Vec<i64> {
+            pull_requests(response(), sort_by_number)
+                .unwrap()
+                .iter()
+                .map(|pr| pr.number)
+                .collect()
+        };
+
+        assert_eq!(numbers(true), vec![4, 17, 30, 31]);
+        // GitHub's order (e.g. most recently updated first) is kept
+        assert_eq!(numbers(false), vec![31, 4, 17, 30]);
+    }
+}
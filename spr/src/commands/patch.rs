@@ -5,37 +5,224 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use crate::error::Result;
+use indoc::formatdoc;
+
+use crate::{
+    error::{Error, Result},
+    github::GitHubBranch,
+    message::build_commit_message,
+    output::output,
+};
 
 #[derive(Debug, clap::Parser)]
 pub struct PatchOptions {
-    /// Pull Request number
-    pull_request: u64,
+    /// Pull Request number, or the URL of the Pull Request on GitHub
+    pull_request: String,
 
-    /// Name of the branch to be created. Defaults to `PR-<number>`
-    #[clap(long)]
-    branch_name: Option<String>,
+    /// Name of the bookmark to be created. Defaults to the name of the Pull
+    /// Request's branch, without the branch prefix
+    #[clap(long, alias = "branch-name")]
+    bookmark: Option<String>,
 
-    /// If given, create new branch but do not check out
+    /// If given, create the bookmark but do not start a new change on top
+    /// of it
     #[clap(long)]
     no_checkout: bool,
+
+    /// Move the bookmark to the Pull Request if it already exists, instead
+    /// of stopping
+    #[clap(long)]
+    force: bool,
+
+    /// Apply the Pull Request's changes on top of this Jujutsu revision (e.g.
+    /// '@' to try them out with your current work) instead of on the commit
+    /// of master it is based on. Only the changes of this Pull Request are
+    /// applied, not those of Pull Requests it is stacked on
+    #[clap(long, value_name = "REVISION")]
+    base_revision: Option<String>,
 }
 
 pub async fn patch(
-    _opts: PatchOptions,
-    _jj: &crate::jj::Jujutsu,
-    _gh: &mut crate::github::GitHub,
-    _config: &crate::config::Config,
+    opts: PatchOptions,
+    jj: &crate::jj::Jujutsu,
+    gh: &mut crate::github::GitHub,
+    config: &crate::config::Config,
 ) -> Result<()> {
-    // TODO: Implement Jujutsu-native patch functionality
-    // This command needs to be completely rewritten for Jujutsu workflow
-    // The current implementation uses complex Git operations that need
-    // to be translated to Jujutsu equivalents
-
-    use crate::error::Error;
-    Err(Error::new(
-        "The patch command is not yet implemented for Jujutsu workflow. \
-         Please use the GitHub web interface to create branches from pull requests for now."
-            .to_string(),
-    ))
+    let number = config.parse_pull_request_argument(&opts.pull_request)?;
+    let pull_request = gh.clone().get_pull_request(number).await?;
+    output(
+        "#️⃣ ",
+        &format!(
+            "Pull Request #{}: {}",
+            pull_request.number, pull_request.title
+        ),
+    )?;
+
+    let bookmark = opts
+        .bookmark
+        .clone()
+        .unwrap_or_else(|| default_bookmark_name(&pull_request.head, &config.branch_prefix));
+    let existed = jj.bookmark_exists(&bookmark)?;
+    if existed && !opts.force {
+        return Err(Error::new(format!(
+            "Bookmark '{}' already exists. Pass --force to move it to the Pull \
+             Request, or choose another name with --bookmark.",
+            bookmark
+        )));
+    }
+
+    // The commit on master that the Pull Request builds on
+    let master_oid = jj.resolve_reference(config.master_ref.local())?;
+    let master_base_oid = jj.git_repo.merge_base(pull_request.head_oid, master_oid)?;
+
+    let parent_oid = if pull_request.base.is_master_branch() {
+        master_base_oid
+    } else {
+        // The Pull Request is stacked on another one. Put the changes of its
+        // base branch in a commit of their own, so that the patched commit
+        // holds only the changes of this Pull Request.
+        jj.create_derived_commit(
+            pull_request.base_oid,
+            &format!("[spr] Base of Pull Request #{}", number),
+            jj.get_tree_oid_for_commit(pull_request.base_oid)?,
+            &[master_base_oid],
+            true,
+        )?
+    };
+
+    // Keep the author date of the Pull Request's head commit, so the patched
+    // commit is attributed to when the work was done
+    let message = build_commit_message(&pull_request.sections, config);
+    let mut commit_oid = jj.create_derived_commit(
+        pull_request.head_oid,
+        &message,
+        jj.get_tree_oid_for_commit(pull_request.head_oid)?,
+        &[parent_oid],
+        true,
+    )?;
+
+    if let Some(base_revision) = &opts.base_revision {
+        let base_oid = jj.resolve_revision_to_commit_id(base_revision)?;
+        let index = jj.cherrypick(commit_oid, base_oid)?;
+        if index.has_conflicts() {
+            return Err(Error::new(formatdoc!(
+                "Pull Request #{number} cannot be applied on top of \
+                 '{base_revision}', because these files conflict:
+                 {paths}
+                 Choose a different --base-revision, or leave it out to apply \
+                 the Pull Request on master.",
+                paths = conflicting_paths(&index)?
+                    .iter()
+                    .map(|path| format!("  {}", path))
+                    .collect::<Vec<_>>()
+                    .join("\n"),
+            )));
+        }
+        commit_oid = jj.create_derived_commit(
+            pull_request.head_oid,
+            &message,
+            jj.write_index(index)?,
+            &[base_oid],
+            true,
+        )?;
+        output("🍒", &format!("Applied on top of '{}'", base_revision))?;
+    }
+
+    jj.set_bookmark(&bookmark, commit_oid, opts.force)?;
+    if existed {
+        output("🔖", &format!("Moved existing bookmark '{}'", bookmark))?;
+    } else {
+        output("🔖", &format!("Created bookmark '{}'", bookmark))?;
+    }
+
+    if !opts.no_checkout {
+        jj.new_change_on(&bookmark)?;
+        output("✅", &format!("Started a new change on '{}'", bookmark))?;
+    }
+
+    Ok(())
+}
+
+/// The paths with conflicts in the index of a failed cherry-pick.
+fn conflicting_paths(index: &git2::Index) -> Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for conflict in index.conflicts()? {
+        let conflict = conflict?;
+        // A file deleted on one side only has entries for the other sides
+        if let Some(entry) = conflict.our.or(conflict.their).or(conflict.ancestor) {
+            let path = String::from_utf8_lossy(&entry.path).into_owned();
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// The bookmark for a patched Pull Request: its branch name, without the
+/// prefix spr puts in front of branch names.
+fn default_bookmark_name(head: &GitHubBranch, branch_prefix: &str) -> String {
+    let branch_name = head.branch_name();
+    branch_name
+        .strip_prefix(branch_prefix)
+        .filter(|name| !name.is_empty())
+        .unwrap_or(branch_name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_bookmark_name() {
+        let branch = |name| GitHubBranch::new_from_branch_name(name, "origin", "main");
+
+        assert_eq!(
+            default_bookmark_name(&branch("spr/alice/fix-the-thing"), "spr/alice/"),
+            "fix-the-thing"
+        );
+        // Pull Requests that were not made with spr keep their branch name
+        assert_eq!(
+            default_bookmark_name(&branch("feature/login"), "spr/alice/"),
+            "feature/login"
+        );
+    }
+
+    #[test]
+    fn test_conflicting_paths() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let repo = git2::Repository::init(temp_dir.path()).unwrap();
+        let commit = |files: &[(&str, &str)], parents: &[git2::Oid]| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for (name, content) in files {
+                let blob = repo.blob(content.as_bytes()).unwrap();
+                builder.insert(name, blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+            let parents: Vec<_> = parents
+                .iter()
+                .map(|oid| repo.find_commit(*oid).unwrap())
+                .collect();
+            let parent_refs: Vec<_> = parents.iter().collect();
+            repo.commit(None, &signature, &signature, "commit", &tree, &parent_refs)
+                .unwrap()
+        };
+
+        let base = commit(&[("a.txt", "a"), ("b.txt", "b")], &[]);
+        let change = commit(&[("a.txt", "change"), ("b.txt", "b")], &[base]);
+        let other = commit(&[("a.txt", "other"), ("b.txt", "b")], &[base]);
+
+        let index = repo
+            .cherrypick_commit(
+                &repo.find_commit(change).unwrap(),
+                &repo.find_commit(other).unwrap(),
+                0,
+                None,
+            )
+            .unwrap();
+        assert!(index.has_conflicts());
+        assert_eq!(conflicting_paths(&index).unwrap(), vec!["a.txt"]);
+    }
 }
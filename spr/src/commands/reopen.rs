@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Radical HQ Limited
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use crate::{
+    error::{Error, Result, ResultExt},
+    github::{GitHubBranch, PullRequest, PullRequestState, PullRequestUpdate},
+    message::MessageSection,
+    output::{output, write_commit_title},
+    utils::{AsyncLoggedOutput, run_command},
+};
+
+#[derive(Debug, clap::Parser)]
+pub struct ReopenOptions {
+    /// Pull Request number, or the URL of the Pull Request on GitHub. Needed
+    /// when the commit no longer links to the Pull Request, as after `jj spr
+    /// close`; the link is then added to the commit message again
+    pull_request: Option<String>,
+
+    /// Jujutsu revision to operate on (if not specified, uses '@-')
+    #[clap(short = 'r', long)]
+    revision: Option<String>,
+}
+
+pub async fn reopen(
+    opts: ReopenOptions,
+    jj: &crate::jj::Jujutsu,
+    gh: &mut crate::github::GitHub,
+    config: &crate::config::Config,
+) -> Result<()> {
+    let revision = opts.revision.as_deref().unwrap_or("@-");
+    let mut prepared_commit = jj.get_prepared_commit_for_revision(config, revision)?;
+    write_commit_title(&prepared_commit)?;
+
+    let pull_request_number = match (&opts.pull_request, prepared_commit.pull_request_number) {
+        (Some(argument), linked) => {
+            let number = config.parse_pull_request_argument(argument)?;
+            if let Some(linked) = linked.filter(|&linked| linked != number) {
+                return Err(Error::new(format!(
+                    "This commit refers to Pull Request #{}, not #{}.",
+                    linked, number
+                )));
+            }
+            number
+        }
+        (None, Some(number)) => number,
+        (None, None) => {
+            return Err(Error::new(
+                "This commit does not refer to a Pull Request. (`jj spr close` \
+                 removes the Pull Request link from the commit message.) Pass \
+                 the number or URL of the Pull Request to reopen it.",
+            ));
+        }
+    };
+    output("#️⃣ ", &format!("Pull Request #{}", pull_request_number))?;
+
+    let pull_request = gh.clone().get_pull_request(pull_request_number).await?;
+    check_reopenable(&pull_request)?;
+
+    // Closing a Pull Request with spr deletes its branches, and GitHub can
+    // only reopen it once they are back where they were.
+    if !pull_request.base.is_master_branch() {
+        restore_branch(config, &pull_request.base, pull_request.base_oid).await?;
+    }
+    restore_branch(config, &pull_request.head, pull_request.head_oid).await?;
+
+    gh.update_pull_request(
+        pull_request_number,
+        PullRequestUpdate {
+            state: Some(PullRequestState::Open),
+            ..Default::default()
+        },
+    )
+    .await
+    .context("GitHub Pull Request reopen failed".to_string())?;
+
+    output("📗", "Reopened!")?;
+
+    if prepared_commit.pull_request_number.is_none() {
+        prepared_commit.message.insert(
+            MessageSection::PullRequest,
+            config.pull_request_url(pull_request_number),
+        );
+        prepared_commit.message_changed = true;
+        jj.rewrite_commit_messages(config, std::slice::from_mut(&mut prepared_commit))?;
+    }
+
+    Ok(())
+}
+
+fn check_reopenable(pull_request: &PullRequest) -> Result<()> {
+    if pull_request.state == PullRequestState::Open {
+        return Err(Error::new("This Pull Request is already open!"));
+    }
+    if pull_request.merge_commit.is_some() {
+        return Err(Error::new(
+            "This Pull Request was merged and cannot be reopened.",
+        ));
+    }
+    Ok(())
+}
+
+/// Push `oid` to `branch` on GitHub, unless the branch is there already.
+async fn restore_branch(
+    config: &crate::config::Config,
+    branch: &GitHubBranch,
+    oid: git2::Oid,
+) -> Result<()> {
+    let ls_remote = tokio::process::Command::new("git")
+        .arg("ls-remote")
+        .arg("--heads")
+        .arg("--")
+        .arg(&config.remote_name)
+        .arg(branch.on_github())
+        .logged_output()
+        .await?;
+    if !ls_remote.status.success() {
+        return Err(Error::new("git ls-remote failed"));
+    }
+    if !ls_remote.stdout.is_empty() {
+        return Ok(());
+    }
+
+    output(
+        "🌱",
+        &format!("Restoring branch {} on GitHub", branch.branch_name()),
+    )?;
+    run_command(
+        tokio::process::Command::new("git")
+            .arg("push")
+            .arg("--no-verify")
+            .arg("--")
+            .arg(&config.remote_name)
+            .arg(format!("{}:{}", oid, branch.on_github())),
+    )
+    .await
+    .reword("git push failed".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_pull_request(state: PullRequestState, merge_commit: Option<git2::Oid>) -> PullRequest {
+        let branch = GitHubBranch::new_from_branch_name("spr/test/pr-1", "origin", "main");
+        PullRequest {
+            number: 1,
+            state,
+            title: "PR 1".into(),
+            body: None,
+            sections: Default::default(),
+            base: branch.clone(),
+            head: branch,
+            base_oid: git2::Oid::zero(),
+            head_oid: git2::Oid::zero(),
+            merge_commit,
+            reviewers: Default::default(),
+            labels: Vec::new(),
+            review_status: None,
+            checks_status: None,
+        }
+    }
+
+    #[test]
+    fn test_check_reopenable() {
+        assert!(check_reopenable(&mock_pull_request(PullRequestState::Closed, None)).is_ok());
+        assert!(check_reopenable(&mock_pull_request(PullRequestState::Open, None)).is_err());
+        assert!(
+            check_reopenable(&mock_pull_request(
+                PullRequestState::Closed,
+                Some(git2::Oid::zero())
+            ))
+            .is_err()
+        );
+    }
+}
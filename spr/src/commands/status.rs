@@ -0,0 +1,179 @@
+/*
+ * Copyright (c) Radical HQ Limited
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+use crate::{
+    error::Result,
+    github::{ChecksStatus, PullRequest, PullRequestState, ReviewStatus},
+    jj::PreparedCommit,
+    message::MessageSection,
+    output::{output, report},
+};
+
+pub async fn status(
+    jj: &crate::jj::Jujutsu,
+    gh: &mut crate::github::GitHub,
+    config: &crate::config::Config,
+) -> Result<()> {
+    // The stack is everything between the point where the working copy
+    // branched off master and the working copy itself.
+    let prepared_commits = jj.get_stack(config, "@")?;
+
+    if prepared_commits.is_empty() {
+        output("👋", "No commits found - nothing to do. Good bye!")?;
+        return Ok(());
+    }
+
+    // Look up the Pull Requests of the whole stack with a single query
+    let numbers: Vec<u64> = prepared_commits
+        .iter()
+        .filter_map(|commit: &PreparedCommit| commit.pull_request_number)
+        .collect();
+    let pull_requests = gh.get_pull_requests(&numbers).await?;
+
+    for commit in prepared_commits.iter() {
+        let pull_request = commit
+            .pull_request_number
+            .and_then(|number| pull_requests.get(&number));
+        let (icon, line) = status_line(commit, pull_request);
+        report(icon, &line)?;
+    }
+
+    Ok(())
+}
+
+/// The line shown for a commit, and the icon to show with it.
+fn status_line(
+    commit: &PreparedCommit,
+    pull_request: Option<&PullRequest>,
+) -> (&'static str, String) {
+    let title = commit
+        .message
+        .get(&MessageSection::Title)
+        .map(|s| &s[..])
+        .unwrap_or("(untitled)");
+    let line = format!("{} {}", commit.short_id, title);
+
+    let Some(pull_request) = pull_request else {
+        return ("📝", format!("{} - not submitted", line));
+    };
+
+    if pull_request.state == PullRequestState::Closed {
+        return ("📕", format!("{} - #{} closed", line, pull_request.number));
+    }
+
+    let review = match pull_request.review_status {
+        Some(ReviewStatus::Approved) => "approved",
+        Some(ReviewStatus::Rejected) => "changes requested",
+        Some(ReviewStatus::Requested) => "review requested",
+        None => "no review decision",
+    };
+    let checks = match pull_request.checks_status {
+        Some(ChecksStatus::Success) => "checks passed",
+        Some(ChecksStatus::Failure) => "checks failed",
+        Some(ChecksStatus::Pending) => "checks pending",
+        None => "no checks",
+    };
+    let icon = if pull_request.review_status == Some(ReviewStatus::Rejected)
+        || pull_request.checks_status == Some(ChecksStatus::Failure)
+    {
+        "❌"
+    } else if pull_request.review_status == Some(ReviewStatus::Approved)
+        && pull_request.checks_status != Some(ChecksStatus::Pending)
+    {
+        "✅"
+    } else {
+        "⏳"
+    };
+
+    (
+        icon,
+        format!("{} - #{} {}, {}", line, pull_request.number, review, checks),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mock_commit(pull_request_number: Option<u64>) -> PreparedCommit {
+        let mut message = crate::message::MessageSectionsMap::new();
+        message.insert(MessageSection::Title, "Add frobnicator".into());
+        PreparedCommit {
+            oid: git2::Oid::zero(),
+            short_id: "abc123".into(),
+            parent_oid: git2::Oid::zero(),
+            base_oid: None,
+            message,
+            pull_request_number,
+            message_changed: false,
+        }
+    }
+
+    fn mock_pull_request(
+        review_status: Option<ReviewStatus>,
+        checks_status: Option<ChecksStatus>,
+    ) -> PullRequest {
+        let branch = crate::github::GitHubBranch::new_from_branch_name("main", "origin", "main");
+        PullRequest {
+            number: 7,
+            state: PullRequestState::Open,
+            title: "Add frobnicator".into(),
+            body: None,
+            sections: Default::default(),
+            base: branch.clone(),
+            head: branch,
+            base_oid: git2::Oid::zero(),
+            head_oid: git2::Oid::zero(),
+            merge_commit: None,
+            reviewers: Default::default(),
+            labels: Vec::new(),
+            review_status,
+            checks_status,
+        }
+    }
+
+    #[test]
+    fn test_status_line() {
+        assert_eq!(
+            status_line(&mock_commit(None), None),
+            ("📝", "abc123 Add frobnicator - not submitted".to_string())
+        );
+
+        let commit = mock_commit(Some(7));
+        assert_eq!(
+            status_line(
+                &commit,
+                Some(&mock_pull_request(
+                    Some(ReviewStatus::Approved),
+                    Some(ChecksStatus::Success)
+                ))
+            ),
+            (
+                "✅",
+                "abc123 Add frobnicator - #7 approved, checks passed".to_string()
+            )
+        );
+        assert_eq!(
+            status_line(
+                &commit,
+                Some(&mock_pull_request(
+                    Some(ReviewStatus::Approved),
+                    Some(ChecksStatus::Failure)
+                ))
+            )
+            .0,
+            "❌"
+        );
+        assert_eq!(
+            status_line(&commit, Some(&mock_pull_request(None, None))),
+            (
+                "⏳",
+                "abc123 Add frobnicator - #7 no review decision, no checks".to_string()
+            )
+        );
+    }
+}
@@ -5,9 +5,18 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use std::collections::HashSet;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use crate::{error::Result, github::GitHubBranch, utils::slugify};
+use octocrab::params::pulls::MergeMethod;
+
+use crate::{
+    error::{Error, Result},
+    github::GitHubBranch,
+    utils::{LoggedOutput, slugify},
+};
 
 #[derive(Clone, Debug)]
 pub struct Config {
@@ -15,28 +24,57 @@ pub struct Config {
     pub repo: String,
     pub remote_name: String,
     pub master_ref: GitHubBranch,
+    /// With a jj revset in `spr.githubMasterBranch`, the trunk commits that
+    /// stacks are based on
+    pub master_revset: Option<String>,
     pub branch_prefix: String,
     pub require_approval: bool,
     pub require_test_plan: bool,
     pub add_reviewed_by: bool,
     pub add_spr_banner_comment: bool,
     pub add_skip_ci_comment: bool,
+    pub re_request_on_update: bool,
+    pub merge_method: MergeMethod,
+    pub record_provenance: bool,
+    pub minimize_rebase_on_describe: bool,
+    pub keep_branch_after_land: bool,
+    pub upload_assets: bool,
+    pub land_poll_attempts: u32,
+    pub land_poll_interval_ms: u64,
+    pub abandon_after_land: bool,
+    pub github_api_url: String,
+    pub github_graphql_url: String,
+    pub draft_label: Option<String>,
+    pub default_labels: Vec<String>,
+    pub authoritative_labels: bool,
+    pub push_no_verify: bool,
+    pub test_plan_section_name: String,
+    pub github_request_attempts: u32,
+    pub open_after_diff: bool,
+    pub require_summary: bool,
+    pub branch_name_template: Option<String>,
+    pub use_pull_request_template: bool,
+    pub land_message_format: LandMessageFormat,
+    pub max_title_length: usize,
+    pub require_title_length: bool,
+    /// Commits that only change paths matching these (spr.testPlanExemptPaths)
+    /// don't need a test plan
+    pub test_plan_exempt_paths: Vec<glob::Pattern>,
+    pub prefer_existing_bookmark: bool,
 }
 
 impl Config {
-    #[allow(clippy::too_many_arguments)]
+    /// A config for the given repository, with every other setting at its
+    /// default. The settings read from config are filled in with struct
+    /// update syntax, `Config { require_approval, ..Config::new(...) }`.
     pub fn new(
         owner: String,
         repo: String,
         remote_name: String,
         master_branch: String,
         branch_prefix: String,
-        require_approval: bool,
-        require_test_plan: bool,
-        add_reviewed_by: bool,
-        add_spr_banner_comment: bool,
-        add_skip_ci_comment: bool,
     ) -> Self {
+        let (master_branch, master_revset) = parse_master_branch(&master_branch);
         let master_ref =
             GitHubBranch::new_from_branch_name(&master_branch, &remote_name, &master_branch);
         Self {
@@ -44,18 +82,53 @@ impl Config {
             repo,
             remote_name,
             master_ref,
+            master_revset,
             branch_prefix,
-            require_approval,
-            require_test_plan,
-            add_reviewed_by,
-            add_spr_banner_comment,
-            add_skip_ci_comment,
+            require_approval: false,
+            require_test_plan: true,
+            add_reviewed_by: false,
+            add_spr_banner_comment: true,
+            add_skip_ci_comment: false,
+            re_request_on_update: false,
+            merge_method: MergeMethod::Squash,
+            record_provenance: false,
+            minimize_rebase_on_describe: false,
+            keep_branch_after_land: false,
+            upload_assets: false,
+            land_poll_attempts: 10,
+            land_poll_interval_ms: 1000,
+            abandon_after_land: true,
+            github_api_url: crate::github::DEFAULT_API_URL.to_string(),
+            github_graphql_url: crate::github::DEFAULT_GRAPHQL_URL.to_string(),
+            draft_label: None,
+            default_labels: Vec::new(),
+            authoritative_labels: false,
+            push_no_verify: true,
+            test_plan_section_name: "Test Plan".to_string(),
+            github_request_attempts: 3,
+            open_after_diff: false,
+            require_summary: true,
+            branch_name_template: None,
+            use_pull_request_template: false,
+            land_message_format: LandMessageFormat::Full,
+            max_title_length: 72,
+            require_title_length: false,
+            test_plan_exempt_paths: Vec::new(),
+            prefer_existing_bookmark: false,
         }
     }
 
+    /// The web address of the GitHub instance, derived from
+    /// spr.githubApiHost: `https://github.com` unless that points at GitHub
+    /// Enterprise Server.
+    pub fn github_web_url(&self) -> String {
+        crate::github::github_web_url(&self.github_api_url)
+    }
+
     pub fn pull_request_url(&self, number: u64) -> String {
         format!(
-            "https://github.com/{owner}/{repo}/pull/{number}",
+            "{web_url}/{owner}/{repo}/pull/{number}",
+            web_url = self.github_web_url(),
             owner = &self.owner,
             repo = &self.repo
         )
@@ -73,21 +146,71 @@ impl Config {
         }
 
         let regex = lazy_regex::regex!(
-            r#"^\s*https?://github.com/([\w\-\.]+)/([\w\-\.]+)/pull/(\d+)([/?#].*)?\s*$"#
+            r#"^\s*https?://([^/\s]+)/([\w\-\.]+)/([\w\-\.]+)/pull/(\d+)([/?#].*)?\s*$"#
         );
         let m = regex.captures(text);
+        let web_url = self.github_web_url();
+        let web_host = web_url
+            .split_once("://")
+            .map_or(&web_url[..], |(_, host)| host);
         if let Some(caps) = m
-            && self.owner == caps.get(1).unwrap().as_str()
-            && self.repo == caps.get(2).unwrap().as_str()
+            && web_host.eq_ignore_ascii_case(caps.get(1).unwrap().as_str())
+            && self.owner == caps.get(2).unwrap().as_str()
+            && self.repo == caps.get(3).unwrap().as_str()
         {
-            return Some(caps.get(3).unwrap().as_str().parse().unwrap());
+            return Some(caps.get(4).unwrap().as_str().parse().unwrap());
         }
 
         None
     }
 
-    pub fn get_new_branch_name(&self, existing_ref_names: &HashSet<String>, title: &str) -> String {
-        self.find_unused_branch_name(existing_ref_names, &slugify(title))
+    /// Parse a Pull Request given on the command line, as a number or as the
+    /// URL of a Pull Request in the configured repository.
+    pub fn parse_pull_request_argument(&self, text: &str) -> Result<u64> {
+        if let Some(number) = self.parse_pull_request_field(text) {
+            return Ok(number);
+        }
+
+        // Any host, so that GitHub Enterprise Server URLs work too
+        let regex = lazy_regex::regex!(r#"^\s*https?://[^/]+/([\w\-\.]+)/([\w\-\.]+)/pull/(\d+)"#);
+        let Some(caps) = regex.captures(text) else {
+            return Err(Error::new(format!(
+                "'{}' is neither a Pull Request number nor the URL of one",
+                text.trim()
+            )));
+        };
+        let (owner, repo) = (caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str());
+        // GitHub treats owner and repository names case-insensitively
+        if !owner.eq_ignore_ascii_case(&self.owner) || !repo.eq_ignore_ascii_case(&self.repo) {
+            return Err(Error::new(format!(
+                "{} is a Pull Request in {}/{}, but this repository is {}/{}",
+                text.trim(),
+                owner,
+                repo,
+                self.owner,
+                self.repo,
+            )));
+        }
+
+        Ok(caps.get(3).unwrap().as_str().parse()?)
+    }
+
+    /// The name for the branch of a new Pull Request. Without
+    /// spr.branchNameTemplate, that's the branch prefix followed by the
+    /// title as a slug.
+    pub fn get_new_branch_name(
+        &self,
+        existing_ref_names: &HashSet<String>,
+        title: &str,
+        change_id: &str,
+    ) -> String {
+        let branch_name = match &self.branch_name_template {
+            Some(template) => {
+                render_branch_name_template(template, &self.branch_prefix, title, change_id)
+            }
+            None => format!("{}{}", self.branch_prefix, slugify(title)),
+        };
+        self.find_unused_branch_name(existing_ref_names, &branch_name)
     }
 
     pub fn get_base_branch_name(
@@ -97,14 +220,20 @@ impl Config {
     ) -> String {
         self.find_unused_branch_name(
             existing_ref_names,
-            &format!("{}.{}", self.master_ref.branch_name(), &slugify(title)),
+            &format!(
+                "{}{}.{}",
+                self.branch_prefix,
+                self.master_ref.branch_name(),
+                &slugify(title)
+            ),
         )
     }
 
-    fn find_unused_branch_name(&self, existing_ref_names: &HashSet<String>, slug: &str) -> String {
+    /// `name`, or if a branch of that name exists on the remote already,
+    /// `name` with the first free numeric suffix.
+    fn find_unused_branch_name(&self, existing_ref_names: &HashSet<String>, name: &str) -> String {
         let remote_name = &self.remote_name;
-        let branch_prefix = &self.branch_prefix;
-        let mut branch_name = format!("{branch_prefix}{slug}");
+        let mut branch_name = name.to_string();
         let mut suffix = 0;
 
         loop {
@@ -115,7 +244,30 @@ impl Config {
             }
 
             suffix += 1;
-            branch_name = format!("{branch_prefix}{slug}-{suffix}");
+            branch_name = format!("{name}-{suffix}");
+        }
+    }
+
+    /// Whether a commit that changes these paths can do without a test plan:
+    /// it only touches files matching spr.testPlanExemptPaths, such as
+    /// lockfiles or snapshots.
+    pub fn is_test_plan_exempt(&self, paths: &[PathBuf]) -> bool {
+        !self.test_plan_exempt_paths.is_empty()
+            && !paths.is_empty()
+            && paths.iter().all(|path| {
+                self.test_plan_exempt_paths
+                    .iter()
+                    .any(|pattern| pattern.matches_path(path))
+            })
+    }
+
+    /// This config, with `branch` as the branch that Pull Requests are
+    /// merged into.
+    pub fn with_master_branch(&self, branch: &str) -> Config {
+        Config {
+            master_ref: GitHubBranch::new_from_branch_name(branch, &self.remote_name, branch),
+            master_revset: None,
+            ..self.clone()
         }
     }
 
@@ -145,6 +297,22 @@ impl AuthTokenSource {
     }
 }
 
+/// Environment variables that may hold a GitHub token, in the order the gh
+/// CLI checks them.
+const AUTH_TOKEN_ENV_VARS: [&str; 2] = ["GH_TOKEN", "GITHUB_TOKEN"];
+
+pub fn get_auth_token_from_env() -> Option<String> {
+    auth_token_from_env_with(|name| std::env::var(name).ok())
+}
+
+fn auth_token_from_env_with(lookup: impl Fn(&str) -> Option<String>) -> Option<String> {
+    AUTH_TOKEN_ENV_VARS
+        .iter()
+        .filter_map(|name| lookup(name))
+        .map(|token| token.trim().to_owned())
+        .find(|token| !token.is_empty())
+}
+
 pub fn get_auth_token(git_config: &git2::Config) -> Option<String> {
     get_auth_token_with_source(git_config).map(|v| v.token().to_owned())
 }
@@ -155,28 +323,211 @@ pub fn get_auth_token_with_source(git_config: &git2::Config) -> Option<AuthToken
         return Some(AuthTokenSource::Config(token));
     }
 
-    // Try to get a token from the gh CLI
+    // Try to get a token from the gh CLI. If gh is not installed or not
+    // logged in, there is no token.
     let output = std::process::Command::new("gh")
         .args(["auth", "token"])
         .stdout(std::process::Stdio::piped())
-        .output()
+        .stderr(std::process::Stdio::null())
+        .logged_output()
         .ok()?;
 
     if output.status.success() {
-        Some(AuthTokenSource::GitHubCLI(
-            String::from_utf8(output.stdout).ok()?.trim().to_owned(),
-        ))
+        let token = String::from_utf8(output.stdout).ok()?.trim().to_owned();
+        (!token.is_empty()).then_some(AuthTokenSource::GitHubCLI(token))
     } else {
         None
     }
 }
 
+/// Parse the value of `spr.landMergeMethod`.
+pub fn parse_merge_method(value: &str) -> Option<MergeMethod> {
+    match value.trim().to_lowercase().as_str() {
+        "squash" => Some(MergeMethod::Squash),
+        "merge" => Some(MergeMethod::Merge),
+        "rebase" => Some(MergeMethod::Rebase),
+        _ => None,
+    }
+}
+
+/// What `spr land` puts in the message of a squash commit, besides the title
+/// of the Pull Request (`spr.landMessageFormat`)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LandMessageFormat {
+    /// The Pull Request description, reviewers and link
+    Full,
+    /// Nothing
+    TitleOnly,
+    /// Only the link to the Pull Request
+    TitleAndLink,
+}
+
+/// Parse the value of `spr.landMessageFormat`.
+pub fn parse_land_message_format(value: &str) -> Option<LandMessageFormat> {
+    match value.trim().to_lowercase().as_str() {
+        "full" => Some(LandMessageFormat::Full),
+        "title-only" => Some(LandMessageFormat::TitleOnly),
+        "title-and-link" => Some(LandMessageFormat::TitleAndLink),
+        _ => None,
+    }
+}
+
+/// The owner and repository of a `spr.githubRepository` value, which has the
+/// form `owner/repo`.
+pub fn parse_github_repository(value: &str) -> Option<(String, String)> {
+    let captures = lazy_regex::regex!(r#"^([\w\-\.]+)/([\w\-\.]+)$"#).captures(value)?;
+    Some((
+        captures.get(1).unwrap().as_str().to_string(),
+        captures.get(2).unwrap().as_str().to_string(),
+    ))
+}
+
+/// The owner and repository named by a git remote URL, e.g.
+/// `git@github.com:acme/codez.git` or `https://github.com/acme/codez`.
+pub fn parse_remote_url(url: &str) -> Option<(String, String)> {
+    let captures =
+        lazy_regex::regex!(r#"[/:]([\w\-\.]+)/([\w\-\.]+?)(\.git)?/?$"#).captures(url.trim())?;
+    Some((
+        captures.get(1).unwrap().as_str().to_string(),
+        captures.get(2).unwrap().as_str().to_string(),
+    ))
+}
+
+/// Check that the URL of the configured remote (or `spr.githubRemoteUrl`,
+/// if set) points at the configured GitHub repository.
+pub fn validate_remote_url(
+    remote_name: &str,
+    url: Option<&str>,
+    owner: &str,
+    repo: &str,
+) -> Result<()> {
+    let url = url.ok_or_else(|| {
+        Error::new(format!(
+            "The git remote '{remote_name}' (spr.githubRemoteName) does not exist \
+             or has no URL"
+        ))
+    })?;
+    let (url_owner, url_repo) = parse_remote_url(url).ok_or_else(|| {
+        Error::new(format!(
+            "Could not tell which GitHub repository the URL '{url}' of the git remote \
+             '{remote_name}' belongs to. Set spr.githubRemoteUrl to the repository's \
+             URL on GitHub."
+        ))
+    })?;
+
+    // GitHub's owner and repository names are case insensitive
+    if !url_owner.eq_ignore_ascii_case(owner) || !url_repo.eq_ignore_ascii_case(repo) {
+        return Err(Error::new(format!(
+            "The git remote '{remote_name}' points at {url_owner}/{url_repo}, but \
+             spr.githubRepository is {owner}/{repo}. Fix one of them, set \
+             spr.githubRemoteName to the remote for {owner}/{repo}, or set \
+             spr.githubRemoteUrl if the remote is a mirror of it."
+        )));
+    }
+
+    Ok(())
+}
+
+/// Parse the comma separated value of `spr.defaultLabels`.
+pub fn parse_label_list(value: &str) -> Vec<String> {
+    let mut labels: Vec<String> = Vec::new();
+    for label in value.split(',').map(str::trim) {
+        if !label.is_empty() && !labels.iter().any(|l| l == label) {
+            labels.push(label.to_string());
+        }
+    }
+    labels
+}
+
+/// Parse a comma separated list of path globs, as in spr.testPlanExemptPaths.
+pub fn parse_path_globs(value: &str) -> Result<Vec<glob::Pattern>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| {
+            glob::Pattern::new(pattern).map_err(|e| {
+                Error::new(format!(
+                    "Invalid glob '{}' in spr.testPlanExemptPaths: {}",
+                    pattern, e
+                ))
+            })
+        })
+        .collect()
+}
+
+/// Name of the file that overrides spr settings for the directory it is in
+/// and everything below it.
+pub const DIRECTORY_CONFIG_FILE_NAME: &str = ".spr.toml";
+
+/// The nearest `.spr.toml` in `dir` or one of the directories above it, up
+/// to and including `root` (the top of the repository).
+pub fn find_directory_config(dir: &Path, root: &Path) -> Option<PathBuf> {
+    for dir in dir.ancestors() {
+        let path = dir.join(DIRECTORY_CONFIG_FILE_NAME);
+        if path.is_file() {
+            return Some(path);
+        }
+        if dir == root {
+            break;
+        }
+    }
+    None
+}
+
+/// Read the settings in a `.spr.toml` file, keyed by their lowercase git
+/// config name (e.g. `spr.branchprefix`). The file has a `[spr]` table with
+/// the same keys as the git config:
+///
+/// ```toml
+/// [spr]
+/// branchPrefix = "spr/payments/"
+/// requireTestPlan = false
+/// ```
+pub fn read_directory_config(path: &Path) -> Result<HashMap<String, String>> {
+    let text = std::fs::read_to_string(path)?;
+    let table: toml::Table = text
+        .parse()
+        .map_err(|error: toml::de::Error| Error::new(error.message().to_string()))?;
+    let mut values = HashMap::new();
+    let Some(spr) = table.get("spr") else {
+        return Ok(values);
+    };
+    let spr = spr
+        .as_table()
+        .ok_or_else(|| Error::new("[spr] must be a table"))?;
+    for (key, value) in spr {
+        let value = match value {
+            toml::Value::String(value) => value.clone(),
+            toml::Value::Boolean(value) => value.to_string(),
+            toml::Value::Integer(value) => value.to_string(),
+            _ => {
+                return Err(Error::new(format!(
+                    "spr.{} must be a string, a number or a boolean",
+                    key
+                )));
+            }
+        };
+        values.insert(format!("spr.{}", key.to_lowercase()), value);
+    }
+    Ok(values)
+}
+
+/// A boolean config value, spelled any way git accepts.
+pub fn parse_config_bool(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Some(true),
+        "false" | "no" | "off" | "0" | "" => Some(false),
+        _ => None,
+    }
+}
+
 // Helper function to get config value from jj first, then git
 pub fn get_config_value(key: &str, git_config: &git2::Config) -> Option<String> {
     // Try jj config first
     if let Ok(output) = std::process::Command::new("jj")
         .args(["config", "get", key])
-        .output()
+        .logged_output()
         && output.status.success()
         && let Ok(value) = String::from_utf8(output.stdout)
     {
@@ -194,7 +545,7 @@ pub fn get_config_bool(key: &str, git_config: &git2::Config) -> Option<bool> {
     // Try jj config first
     if let Ok(output) = std::process::Command::new("jj")
         .args(["config", "get", key])
-        .output()
+        .logged_output()
         && output.status.success()
         && let Ok(value) = String::from_utf8(output.stdout)
     {
@@ -210,29 +561,132 @@ pub fn get_config_bool(key: &str, git_config: &git2::Config) -> Option<bool> {
     git_config.get_bool(key).ok()
 }
 
+/// The placeholders that spr.branchNameTemplate may use.
+const BRANCH_NAME_PLACEHOLDERS: &[&str] = &["{prefix}", "{title-slug}", "{change-id}"];
+
+/// Check that spr.branchNameTemplate only uses known placeholders.
+pub fn validate_branch_name_template(template: &str) -> Result<()> {
+    let unknown: Vec<&str> = lazy_regex::regex!(r"\{[^}]*\}")
+        .find_iter(template)
+        .map(|m| m.as_str())
+        .filter(|placeholder| !BRANCH_NAME_PLACEHOLDERS.contains(placeholder))
+        .collect();
+    if unknown.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::new(format!(
+            "spr.branchNameTemplate uses unknown placeholder(s) {}. Available \
+             are: {}",
+            unknown.join(", "),
+            BRANCH_NAME_PLACEHOLDERS.join(", ")
+        )))
+    }
+}
+
+/// Fill in spr.branchNameTemplate, and turn the result into a valid branch
+/// name. The change ID is shortened to 12 characters, like commit ids in
+/// branch names usually are.
+fn render_branch_name_template(
+    template: &str,
+    prefix: &str,
+    title: &str,
+    change_id: &str,
+) -> String {
+    let rendered = template
+        .replace("{prefix}", prefix)
+        .replace("{title-slug}", &slugify(title))
+        .replace(
+            "{change-id}",
+            &change_id.chars().take(12).collect::<String>(),
+        );
+    sanitize_branch_name(&rendered)
+}
+
+/// Replace or drop whatever git does not allow in a branch name.
+fn sanitize_branch_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_whitespace() { '-' } else { c })
+        .filter(|c| !c.is_control() && !"~^:?*[\\".contains(*c))
+        .collect();
+    let name = lazy_regex::regex!(r"\.{2,}").replace_all(&name, ".");
+    let name = lazy_regex::regex!(r"/{2,}").replace_all(&name, "/");
+    let name = name.replace("@{", "@").replace("/.", "/");
+    let name = name.trim_matches(|c| c == '/' || c == '.');
+    let name = name.strip_suffix(".lock").unwrap_or(name);
+    if name.is_empty() {
+        "spr".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+/// Split the value of `spr.githubMasterBranch` into the branch that Pull
+/// Requests are opened against and, if the value is a jj revset rather than
+/// a plain branch name, the revset. The branch is the first one named in
+/// the revset, e.g. `main` for `trunk() | main@origin`; it is only a fallback,
+/// as stacks use the trunk they are based on (see
+/// [`crate::jj::Jujutsu::get_trunk_branch_for_commit`]).
+fn parse_master_branch(value: &str) -> (String, Option<String>) {
+    let value = value.trim();
+    if lazy_regex::regex_is_match!(r"^[A-Za-z0-9._/-]+$", value) {
+        return (value.to_string(), None);
+    }
+
+    let branch = lazy_regex::regex!(r"([A-Za-z0-9_][A-Za-z0-9._/-]*)(@[A-Za-z0-9._-]+)?(\()?")
+        .captures_iter(value)
+        .find(|captures| captures.get(3).is_none())
+        .map_or_else(|| "main".to_string(), |captures| captures[1].to_string());
+    (branch, Some(value.to_string()))
+}
+
+/// The config tests start from: the repository acme/codez, with every
+/// setting at its default.
+#[cfg(test)]
+pub(crate) fn test_config() -> Config {
+    Config::new(
+        "acme".into(),
+        "codez".into(),
+        "origin".into(),
+        "main".into(),
+        "spr/foo/".into(),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
-    fn config_factory() -> Config {
-        crate::config::Config::new(
-            "acme".into(),
-            "codez".into(),
-            "origin".into(),
-            "master".into(),
-            "spr/foo/".into(),
-            false,
-            true,
-            true,
-            true,
-            true,
-        )
+    #[test]
+    fn test_auth_token_from_env() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(auth_token_from_env_with(env(&[])), None);
+        assert_eq!(
+            auth_token_from_env_with(env(&[("GITHUB_TOKEN", " ghp_abc\n")])),
+            Some("ghp_abc".to_string())
+        );
+        assert_eq!(
+            auth_token_from_env_with(env(&[("GITHUB_TOKEN", "ghp_abc"), ("GH_TOKEN", "gho_def")])),
+            Some("gho_def".to_string())
+        );
+        // An empty variable doesn't count
+        assert_eq!(
+            auth_token_from_env_with(env(&[("GITHUB_TOKEN", "ghp_abc"), ("GH_TOKEN", "  ")])),
+            Some("ghp_abc".to_string())
+        );
     }
 
     #[test]
     fn test_pull_request_url() {
-        let gh = config_factory();
+        let gh = test_config();
 
         assert_eq!(
             &gh.pull_request_url(123),
@@ -240,9 +694,193 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_merge_method() {
+        assert_eq!(parse_merge_method("squash"), Some(MergeMethod::Squash));
+        assert_eq!(parse_merge_method("Merge"), Some(MergeMethod::Merge));
+        assert_eq!(parse_merge_method(" rebase "), Some(MergeMethod::Rebase));
+        assert_eq!(parse_merge_method("fast-forward"), None);
+    }
+
+    #[test]
+    fn test_parse_land_message_format() {
+        assert_eq!(
+            parse_land_message_format("full"),
+            Some(LandMessageFormat::Full)
+        );
+        assert_eq!(
+            parse_land_message_format(" Title-Only "),
+            Some(LandMessageFormat::TitleOnly)
+        );
+        assert_eq!(
+            parse_land_message_format("title-and-link"),
+            Some(LandMessageFormat::TitleAndLink)
+        );
+        assert_eq!(parse_land_message_format("title"), None);
+    }
+
+    #[test]
+    fn test_parse_github_repository() {
+        assert_eq!(
+            parse_github_repository("acme/codez.rs"),
+            Some(("acme".to_string(), "codez.rs".to_string()))
+        );
+        assert_eq!(parse_github_repository("acme"), None);
+        assert_eq!(parse_github_repository("github.com/acme/codez"), None);
+    }
+
+    #[test]
+    fn test_parse_remote_url() {
+        let acme = Some(("acme".to_string(), "codez".to_string()));
+        assert_eq!(parse_remote_url("git@github.com:acme/codez.git"), acme);
+        assert_eq!(parse_remote_url("https://github.com/acme/codez"), acme);
+        assert_eq!(
+            parse_remote_url("ssh://git@github.example.com/acme/codez.git/"),
+            acme
+        );
+        assert_eq!(parse_remote_url("work:acme/codez.git"), acme);
+        assert_eq!(parse_remote_url("../codez"), None);
+    }
+
+    #[test]
+    fn test_validate_remote_url() {
+        let url = Some("git@github.com:Acme/codez.git");
+        assert!(validate_remote_url("origin", url, "acme", "codez").is_ok());
+        assert!(validate_remote_url("origin", url, "acme", "other").is_err());
+        assert!(validate_remote_url("origin", None, "acme", "codez").is_err());
+        assert!(validate_remote_url("origin", Some("/srv/codez"), "acme", "codez").is_err());
+    }
+
+    #[test]
+    fn test_get_new_branch_name() {
+        let existing: HashSet<String> = ["refs/remotes/origin/spr/foo/fix-the-bug".to_string()]
+            .into_iter()
+            .collect();
+
+        let config = test_config();
+        assert_eq!(
+            config.get_new_branch_name(&existing, "Fix the bug", "unused"),
+            "spr/foo/fix-the-bug-1"
+        );
+
+        let config = Config {
+            branch_name_template: Some("{prefix}{change-id}/{title-slug}".into()),
+            ..test_config()
+        };
+        assert_eq!(
+            config.get_new_branch_name(
+                &existing,
+                "Fix the bug",
+                "qpvuntsmwlqtpsluzzsnyyzlmlwvmlnu"
+            ),
+            "spr/foo/qpvuntsmwlqt/fix-the-bug"
+        );
+    }
+
+    #[test]
+    fn test_branch_name_template() {
+        assert!(validate_branch_name_template("{prefix}{title-slug}").is_ok());
+        let error = validate_branch_name_template("{prefix}{pr-number}").unwrap_err();
+        assert!(error.to_string().contains("{pr-number}"));
+
+        assert_eq!(
+            render_branch_name_template("users/Jane Doe/{title-slug}", "", "Add: a thing?", ""),
+            "users/Jane-Doe/add-a-thing"
+        );
+        assert_eq!(
+            render_branch_name_template("{prefix}..{title-slug}.lock", "/spr//", "x", ""),
+            "spr/x"
+        );
+    }
+
+    #[test]
+    fn test_parse_master_branch() {
+        assert_eq!(parse_master_branch("develop"), ("develop".into(), None));
+        assert_eq!(
+            parse_master_branch("release/2.x"),
+            ("release/2.x".into(), None)
+        );
+        assert_eq!(
+            parse_master_branch("trunk() | main@origin"),
+            ("main".into(), Some("trunk() | main@origin".into()))
+        );
+        assert_eq!(
+            parse_master_branch("present(develop) | release"),
+            ("develop".into(), Some("present(develop) | release".into()))
+        );
+
+        let config = test_config();
+        assert_eq!(config.master_ref.branch_name(), "main");
+        assert_eq!(config.master_revset, None);
+    }
+
+    #[test]
+    fn test_parse_label_list() {
+        assert_eq!(
+            parse_label_list("needs review, team: infra,,needs review "),
+            vec!["needs review", "team: infra"]
+        );
+        assert!(parse_label_list(" ").is_empty());
+    }
+
+    #[test]
+    fn test_test_plan_exempt_paths() {
+        let mut config = test_config();
+        let paths = [
+            PathBuf::from("Cargo.lock"),
+            PathBuf::from("snapshots/a.snap"),
+        ];
+        assert!(!config.is_test_plan_exempt(&paths));
+
+        config.test_plan_exempt_paths = parse_path_globs("Cargo.lock, snapshots/**, ").unwrap();
+        assert_eq!(config.test_plan_exempt_paths.len(), 2);
+        assert!(config.is_test_plan_exempt(&paths));
+        assert!(
+            !config
+                .is_test_plan_exempt(&[PathBuf::from("Cargo.lock"), PathBuf::from("src/main.rs")])
+        );
+        assert!(!config.is_test_plan_exempt(&[]));
+
+        assert!(parse_path_globs("a/***").is_err());
+    }
+
+    #[test]
+    fn test_parse_pull_request_argument() {
+        let gh = test_config();
+
+        assert_eq!(gh.parse_pull_request_argument("123").unwrap(), 123);
+        assert_eq!(
+            gh.parse_pull_request_argument("https://github.com/acme/codez/pull/123/files")
+                .unwrap(),
+            123
+        );
+
+        let error = gh
+            .parse_pull_request_argument("https://github.com/acme/other/pull/123")
+            .unwrap_err();
+        assert_eq!(
+            error.messages(),
+            &[
+                "https://github.com/acme/other/pull/123 is a Pull Request in acme/other, but \
+               this repository is acme/codez"
+            ]
+        );
+        assert_eq!(
+            gh.parse_pull_request_argument("https://github.example.com/acme/codez/pull/7")
+                .unwrap(),
+            7
+        );
+        assert!(gh.parse_pull_request_argument("main").is_err());
+        assert_eq!(
+            gh.parse_pull_request_argument("https://github.com/Acme/CodeZ/pull/9")
+                .unwrap(),
+            9
+        );
+    }
+
     #[test]
     fn test_parse_pull_request_field_empty() {
-        let gh = config_factory();
+        let gh = test_config();
 
         assert_eq!(gh.parse_pull_request_field(""), None);
         assert_eq!(gh.parse_pull_request_field("   "), None);
@@ -251,7 +889,7 @@ mod tests {
 
     #[test]
     fn test_parse_pull_request_field_number() {
-        let gh = config_factory();
+        let gh = test_config();
 
         assert_eq!(gh.parse_pull_request_field("123"), Some(123));
         assert_eq!(gh.parse_pull_request_field("   123 "), Some(123));
@@ -261,7 +899,7 @@ mod tests {
 
     #[test]
     fn test_parse_pull_request_field_url() {
-        let gh = config_factory();
+        let gh = test_config();
 
         assert_eq!(
             gh.parse_pull_request_field("https://github.com/acme/codez/pull/123"),
@@ -287,5 +925,69 @@ mod tests {
             gh.parse_pull_request_field("https://github.com/acme/codez/pull/123#abc"),
             Some(123)
         );
+        assert_eq!(
+            gh.parse_pull_request_field("https://github.example.com/acme/codez/pull/123"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_pull_request_url_on_enterprise_server() {
+        let mut gh = test_config();
+        gh.github_api_url = "https://github.example.com/api/v3".into();
+
+        let url = gh.pull_request_url(123);
+        assert_eq!(url, "https://github.example.com/acme/codez/pull/123");
+        assert_eq!(gh.parse_pull_request_field(&url), Some(123));
+        assert_eq!(
+            gh.parse_pull_request_field("https://github.com/acme/codez/pull/123"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_find_and_read_directory_config() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let subtree = root.join("services").join("payments");
+        let nested = subtree.join("src");
+        std::fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(find_directory_config(&nested, root), None);
+
+        std::fs::write(
+            subtree.join(DIRECTORY_CONFIG_FILE_NAME),
+            "# Settings for the payments team\n\
+             [spr]\n\
+             branchPrefix = \"spr/payments/\"\n\
+             requireTestPlan = false\n\
+             landPollAttempts = 5\n",
+        )
+        .unwrap();
+        let path = find_directory_config(&nested, root).unwrap();
+        assert_eq!(path, subtree.join(DIRECTORY_CONFIG_FILE_NAME));
+        assert_eq!(find_directory_config(root, root), None);
+
+        let values = read_directory_config(&path).unwrap();
+        assert_eq!(
+            values.get("spr.branchprefix").map(String::as_str),
+            Some("spr/payments/")
+        );
+        assert_eq!(
+            values
+                .get("spr.requiretestplan")
+                .and_then(|value| parse_config_bool(value)),
+            Some(false)
+        );
+        assert_eq!(
+            values.get("spr.landpollattempts").map(String::as_str),
+            Some("5")
+        );
+        assert_eq!(values.len(), 3);
+
+        std::fs::write(&path, "[spr]\ndefaultLabels = [\"team\"]\n").unwrap();
+        assert!(read_directory_config(&path).is_err());
+        std::fs::write(&path, "[spr\n").unwrap();
+        assert!(read_directory_config(&path).is_err());
     }
 }
@@ -15,21 +15,15 @@ use std::{
 use crate::{
     config::Config,
     error::{Error, Result, ResultExt},
-    git,
     github::GitHubBranch,
     message::{MessageSection, MessageSectionsMap, build_commit_message, parse_message},
+    output::{debug_command, debug_exit},
     utils::run_command,
 };
 use debug_ignore::DebugIgnore;
 use git2::Oid;
 use git2_ext::ops::UserSign;
 
-fn collect_arr<const N: usize, T>(mut i: impl Iterator<Item = T>) -> [T; N] {
-    let arr = std::array::from_fn(|_| i.next().unwrap());
-    assert!(i.next().is_none());
-    arr
-}
-
 #[derive(Debug)]
 pub struct PreparedCommit {
     pub oid: Oid,
@@ -39,6 +33,29 @@ pub struct PreparedCommit {
     pub pull_request_number: Option<u64>,
 }
 
+/// Size of the changes between two trees.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeDiffStats {
+    pub files_changed: usize,
+    pub insertions: usize,
+    pub deletions: usize,
+    /// The changes per file, like `git diff --stat`
+    pub per_file: String,
+}
+
+impl std::fmt::Display for TreeDiffStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "+{} −{} across {} file{}",
+            self.insertions,
+            self.deletions,
+            self.files_changed,
+            if self.files_changed == 1 { "" } else { "s" }
+        )
+    }
+}
+
 #[derive(Clone)]
 pub struct Git {
     repo: std::sync::Arc<std::sync::Mutex<GitRepo>>,
@@ -131,6 +148,7 @@ impl Git {
 
     pub fn lock_and_rewrite_commit_messages(
         &self,
+        config: &Config,
         commits: &mut [PreparedCommit],
         mut limit: Option<usize>,
     ) -> Result<()> {
@@ -141,7 +159,7 @@ impl Git {
         if let Some(jj) = &self.jj {
             // XXX we don't yet support the limit parameter, since that's not currently used by any
             // of the callers
-            return jj.rewrite_commit_messages(commits);
+            return jj.rewrite_commit_messages(config, commits);
         }
 
         let mut parent_oid: Option<Oid> = None;
@@ -153,7 +171,7 @@ impl Git {
         for prepared_commit in commits.iter_mut() {
             let commit = repo.find_commit(prepared_commit.oid)?;
             if limit != Some(0) {
-                message = build_commit_message(&prepared_commit.message);
+                message = build_commit_message(&prepared_commit.message, config);
                 if Some(&message[..]) != commit.message() {
                     updating = true;
                 }
@@ -265,6 +283,61 @@ impl Git {
         Ok(())
     }
 
+    /// Replay the commits reachable from `commit` but not from `new_base` on
+    /// top of `new_base`, oldest first, and return the new tip. Like
+    /// `git rebase`, commits that become empty are dropped. No references or
+    /// files in the worktree are touched. If `commit` already builds on
+    /// `new_base`, it is returned as it is.
+    pub fn lock_and_rebase_onto(&self, commit: Oid, new_base: Oid) -> Result<Oid> {
+        let repo = self.lock_repo();
+
+        if repo.merge_base(commit, new_base)? == new_base {
+            return Ok(commit);
+        }
+
+        let mut walk = repo.revwalk()?;
+        walk.set_sorting(git2::Sort::TOPOLOGICAL | git2::Sort::REVERSE)?;
+        walk.push(commit)?;
+        walk.hide(new_base)?;
+
+        let mut new_parent_oid = new_base;
+        for oid in walk {
+            let commit = repo.find_commit(oid?)?;
+            if commit.parent_count() > 1 {
+                return Err(Error::new(format!(
+                    "Cannot rebase merge commit {}",
+                    commit.id()
+                )));
+            }
+
+            let new_parent_commit = repo.find_commit(new_parent_oid)?;
+            let index = repo.cherrypick_commit(&commit, &new_parent_commit)?;
+            if index.has_conflicts() {
+                return Err(Error::new(format!(
+                    "Rebase failed due to merge conflicts in commit {}",
+                    commit.id()
+                )));
+            }
+
+            let tree_oid = repo.write_index(index)?;
+            if tree_oid == new_parent_commit.tree_id() {
+                continue;
+            }
+            let tree = repo.find_tree(tree_oid)?;
+
+            new_parent_oid = repo.commit(
+                &commit.author(),
+                &commit.committer(),
+                String::from_utf8_lossy(commit.message_bytes()).as_ref(),
+                &tree,
+                &[&new_parent_commit],
+                RunPostRewriteRebaseHooks::No,
+            )?;
+        }
+
+        Ok(new_parent_oid)
+    }
+
     pub fn lock_and_get_head(&self) -> Result<Oid> {
         let oid = self
             .lock_repo()
@@ -369,7 +442,7 @@ impl Git {
         drop(commit);
         drop(repo);
 
-        let mut message = parse_message(&message, MessageSection::Title);
+        let mut message = parse_message(&message, MessageSection::Title, config);
 
         let pull_request_number = message
             .get(&MessageSection::PullRequest)
@@ -436,6 +509,27 @@ impl Git {
         Ok(tree_oid)
     }
 
+    /// How much changes between two trees, like `git diff --shortstat`, and
+    /// in which files.
+    pub fn lock_and_get_tree_diff_stats(
+        &self,
+        base_tree: Oid,
+        head_tree: Oid,
+    ) -> Result<TreeDiffStats> {
+        let repo = self.lock_repo();
+        let base_tree = repo.find_tree(base_tree)?;
+        let head_tree = repo.find_tree(head_tree)?;
+        let stats = repo.diff_stats(&base_tree, &head_tree)?;
+        let per_file = stats.to_buf(git2::DiffStatsFormat::FULL, 72)?;
+
+        Ok(TreeDiffStats {
+            files_changed: stats.files_changed(),
+            insertions: stats.insertions(),
+            deletions: stats.deletions(),
+            per_file: per_file.as_str().unwrap_or_default().trim_end().to_string(),
+        })
+    }
+
     pub fn lock_and_find_master_base(
         &self,
         commit_oid: Oid,
@@ -556,7 +650,7 @@ impl GitRepo {
             .config()
             .context("failed to read repo config".to_owned())?;
         // If commit.gpgsign is set, then attempt to obtain the signing info.
-        let sign = CommitSign::new(&repo, &config);
+        let sign = CommitSign::new(&repo, &config, None);
 
         Ok(Self {
             repo: DebugIgnore(repo),
@@ -590,7 +684,13 @@ impl GitRepo {
         Ok(self.repo.find_tree(oid)?)
     }
 
-    #[allow(dead_code)]
+    fn diff_stats(&self, old_tree: &git2::Tree, new_tree: &git2::Tree) -> Result<git2::DiffStats> {
+        Ok(self
+            .repo
+            .diff_tree_to_tree(Some(old_tree), Some(new_tree), None)?
+            .stats()?)
+    }
+
     pub(crate) fn merge_base(&self, a: Oid, b: Oid) -> Result<Oid> {
         Ok(self.repo.merge_base(a, b)?)
     }
@@ -616,7 +716,7 @@ impl GitRepo {
         parents: &[&git2::Commit<'_>],
         run_post_rewrite_hooks: RunPostRewriteRebaseHooks,
     ) -> Result<Oid> {
-        let sign = self.sign.as_dyn_sign();
+        let sign = self.sign.as_dyn_sign()?;
         let new_oid =
             git2_ext::ops::commit(&self.repo, author, committer, message, tree, parents, sign)?;
 
@@ -670,22 +770,32 @@ impl GitRepo {
     }
 }
 
+/// How commits created by spr are signed, following the repository's
+/// signing setup (`gpg.format`, `user.signingkey` etc.).
 #[derive(Debug)]
-enum CommitSign {
+pub(crate) enum CommitSign {
     Enabled(DebugIgnore<UserSign>),
-    EnabledButError,
+    /// Signing is enabled, but the signing key could not be set up
+    EnabledButError(String),
     Disabled,
 }
 
 impl CommitSign {
-    fn new(repo: &git2::Repository, config: &git2::Config) -> Self {
-        match config.get_bool("commit.gpgsign") {
+    /// Sign commits if `enabled` is true. If `enabled` is `None`, commits are
+    /// signed if `commit.gpgsign` is set.
+    pub(crate) fn new(
+        repo: &git2::Repository,
+        config: &git2::Config,
+        enabled: Option<bool>,
+    ) -> Self {
+        let enabled = match enabled {
+            Some(enabled) => Ok(enabled),
+            None => config.get_bool("commit.gpgsign"),
+        };
+        match enabled {
             Ok(true) => match UserSign::from_config(repo, config) {
                 Ok(sign) => Self::Enabled(DebugIgnore(sign)),
-                Err(err) => {
-                    eprintln!("[spr] unable to obtain signing info: {}", err);
-                    Self::EnabledButError
-                }
+                Err(err) => Self::EnabledButError(err.to_string()),
             },
             Ok(false) => Self::Disabled,
             Err(err) => {
@@ -699,10 +809,17 @@ impl CommitSign {
         }
     }
 
-    fn as_dyn_sign(&self) -> Option<&dyn git2_ext::ops::Sign> {
+    /// The signer to create commits with, or an error if signing is enabled
+    /// but doesn't work: commits must not silently end up unsigned.
+    pub(crate) fn as_dyn_sign(&self) -> Result<Option<&dyn git2_ext::ops::Sign>> {
         match self {
-            Self::Enabled(sign) => Some(&**sign),
-            _ => None,
+            Self::Enabled(sign) => Ok(Some(&**sign)),
+            Self::EnabledButError(err) => Err(Error::new(format!(
+                "Commit signing is enabled, but spr could not set it up: {}. \
+                 Set spr.signCommits to false to create unsigned commits.",
+                err
+            ))),
+            Self::Disabled => Ok(None),
         }
     }
 }
@@ -758,7 +875,7 @@ impl JujutsuRepo {
         Ok(Self { cli })
     }
 
-    fn rewrite_commit_messages(&self, commits: &[PreparedCommit]) -> Result<()> {
+    fn rewrite_commit_messages(&self, config: &Config, commits: &[PreparedCommit]) -> Result<()> {
         // Turn all the commit IDs into change IDs.
         let jj_change_data = self
             .cli
@@ -774,7 +891,7 @@ impl JujutsuRepo {
                 ))
             })?;
 
-            let new_message = build_commit_message(&prepared_commit.message);
+            let new_message = build_commit_message(&prepared_commit.message, config);
             if new_message != change_data.description {
                 let args = &[
                     "describe",
@@ -914,10 +1031,12 @@ impl JujutsuCli {
         // Capture stdout, but let stderr go to the terminal.
         command.stdout(Stdio::piped());
 
+        debug_command(&command);
         let child = command.spawn().context("jj failed to spawn".to_string())?;
         let output = child
             .wait_with_output()
             .context("failed to wait for jj to exit".to_string())?;
+        debug_exit(&command, output.status);
         if output.status.success() {
             let output = String::from_utf8(output.stdout)
                 .context("jujutsu output was not valid UTF-8".to_string())?;
@@ -947,24 +1066,10 @@ fn get_jj_bin() -> PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::test_config;
     use std::fs;
     use tempfile::TempDir;
 
-    fn create_test_config() -> Config {
-        Config::new(
-            "test_owner".into(),
-            "test_repo".into(),
-            "origin".into(),
-            "main".into(),
-            "spr/test/".into(),
-            false,
-            false,
-            false,
-            false,
-            false,
-        )
-    }
-
     fn create_test_git_repo() -> (TempDir, git2::Repository) {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
         let repo = git2::Repository::init(temp_dir.path()).expect("Failed to init git repo");
@@ -1029,11 +1134,96 @@ mod tests {
         .expect("Failed to create commit")
     }
 
+    /// Commit `content` to the file `name` on top of `parent`, without
+    /// touching HEAD or the worktree.
+    fn commit_file(repo: &git2::Repository, parent: Oid, name: &str, content: &str) -> Oid {
+        let signature = git2::Signature::now("Test User", "test@example.com")
+            .expect("Failed to create signature");
+        let parent = repo.find_commit(parent).expect("Failed to find parent");
+        let blob = repo.blob(content.as_bytes()).expect("Failed to write blob");
+
+        let mut builder = repo
+            .treebuilder(Some(&parent.tree().expect("Failed to get tree")))
+            .expect("Failed to create tree builder");
+        builder
+            .insert(name, blob, 0o100644)
+            .expect("Failed to insert file");
+        let tree = repo
+            .find_tree(builder.write().expect("Failed to write tree"))
+            .expect("Failed to find tree");
+
+        repo.commit(None, &signature, &signature, name, &tree, &[&parent])
+            .expect("Failed to create commit")
+    }
+
+    #[test]
+    fn test_lock_and_get_tree_diff_stats() {
+        let (_temp_dir, repo) = create_test_git_repo();
+        let initial = repo.head().unwrap().target().unwrap();
+        let first = commit_file(&repo, initial, "a.txt", "one\ntwo\n");
+        let second = commit_file(&repo, first, "a.txt", "one\nthree\nfour\n");
+        let second = commit_file(&repo, second, "b.txt", "b\n");
+
+        let tree = |oid| repo.find_commit(oid).unwrap().tree_id();
+        let (base_tree, head_tree) = (tree(first), tree(second));
+        let git = Git::new(repo).unwrap();
+        let stats = git
+            .lock_and_get_tree_diff_stats(base_tree, head_tree)
+            .unwrap();
+
+        assert_eq!(
+            (stats.files_changed, stats.insertions, stats.deletions),
+            (2, 3, 1)
+        );
+        assert!(stats.per_file.contains("a.txt"));
+        assert!(stats.per_file.contains("b.txt"));
+        assert_eq!(stats.to_string(), "+3 −1 across 2 files");
+    }
+
+    #[test]
+    fn test_lock_and_rebase_onto() {
+        let (_temp_dir, repo) = create_test_git_repo();
+        let initial = repo.head().unwrap().target().unwrap();
+        let master = commit_file(&repo, initial, "master.txt", "master");
+        let first = commit_file(&repo, initial, "a.txt", "a");
+        let second = commit_file(&repo, first, "b.txt", "b");
+        let third = commit_file(&repo, second, "c.txt", "c");
+
+        let git = Git::new(repo).expect("Failed to create Git instance");
+        let tip = git.lock_and_rebase_onto(third, master).unwrap();
+
+        let repo = git.lock_repo();
+        let mut messages = Vec::new();
+        let mut commit = repo.find_commit(tip).unwrap();
+        while commit.id() != master {
+            messages.push(commit.message().unwrap().to_string());
+            commit = commit.parent(0).unwrap();
+        }
+        assert_eq!(messages, vec!["c.txt", "b.txt", "a.txt"]);
+
+        let tree = repo.find_commit(tip).unwrap().tree().unwrap();
+        for name in ["master.txt", "a.txt", "b.txt", "c.txt"] {
+            assert!(tree.get_name(name).is_some(), "{name} missing");
+        }
+    }
+
+    #[test]
+    fn test_lock_and_rebase_onto_no_op() {
+        let (_temp_dir, repo) = create_test_git_repo();
+        let initial = repo.head().unwrap().target().unwrap();
+        let first = commit_file(&repo, initial, "a.txt", "a");
+        let second = commit_file(&repo, first, "b.txt", "b");
+
+        let git = Git::new(repo).expect("Failed to create Git instance");
+        assert_eq!(git.lock_and_rebase_onto(second, initial).unwrap(), second);
+        assert_eq!(git.lock_and_rebase_onto(second, first).unwrap(), second);
+    }
+
     #[test]
     fn test_lock_and_get_prepared_commits_for_revision_with_no_revision() {
         let (_temp_dir, repo) = create_test_git_repo();
         let git = Git::new(repo).expect("Failed to create Git instance");
-        let config = create_test_config();
+        let config = test_config();
 
         // Test with no revision - should fall back to normal behavior
         let result = git.lock_and_get_prepared_commits_for_revision(&config, None);
@@ -1055,7 +1245,7 @@ mod tests {
     fn test_lock_and_get_prepared_commits_for_revision_without_jujutsu() {
         let (_temp_dir, repo) = create_test_git_repo();
         let git = Git::new(repo).expect("Failed to create Git instance");
-        let config = create_test_config();
+        let config = test_config();
 
         // Test with revision but no Jujutsu - should return error
         let result = git.lock_and_get_prepared_commits_for_revision(&config, Some("test_revision"));
@@ -1103,11 +1293,10 @@ mod tests {
     #[test]
     fn test_prepared_commit_creation() {
         let (_temp_dir, repo) = create_test_git_repo();
-        let config = create_test_config();
+        let config = test_config();
 
         // Create a test commit with SPR metadata before moving repo
-        let commit_message =
-            "Test commit\n\nPull Request: https://github.com/test_owner/test_repo/pull/123";
+        let commit_message = "Test commit\n\nPull Request: https://github.com/acme/codez/pull/123";
         let commit_oid = create_test_commit(&repo, commit_message, "test content");
 
         let git = Git::new(repo).expect("Failed to create Git instance");
@@ -1208,7 +1397,7 @@ mod tests {
         #[test]
         fn test_jujutsu_revision_resolution() {
             let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-            let config = create_test_config();
+            let config = test_config();
 
             // Create some commits
             let _commit1_id = create_jujutsu_commit(&repo_path, "First commit", "content1");
@@ -1245,7 +1434,7 @@ mod tests {
         #[test]
         fn test_jujutsu_revision_with_change_id() {
             let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-            let config = create_test_config();
+            let config = test_config();
 
             // Create a commit and get its change ID
             let change_id = create_jujutsu_commit(&repo_path, "Test commit", "test content");
@@ -1287,7 +1476,7 @@ mod tests {
         #[test]
         fn test_jujutsu_invalid_revision() {
             let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-            let config = create_test_config();
+            let config = test_config();
 
             // Create a commit so we have something in the repo
             let _commit_id = create_jujutsu_commit(&repo_path, "Test commit", "test content");
@@ -1310,7 +1499,7 @@ mod tests {
         #[test]
         fn test_jujutsu_multiple_commits() {
             let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-            let config = create_test_config();
+            let config = test_config();
 
             // Create multiple commits
             let _commit1 = create_jujutsu_commit(&repo_path, "First commit", "content1");
@@ -1351,7 +1540,7 @@ mod tests {
         #[test]
         fn test_jujutsu_fallback_to_normal_behavior() {
             let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-            let config = create_test_config();
+            let config = test_config();
 
             // Create some commits
             let _commit1 = create_jujutsu_commit(&repo_path, "First commit", "content1");
@@ -1384,4 +1573,14 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_broken_commit_signing_is_an_error() {
+        assert!(
+            CommitSign::EnabledButError("no key".into())
+                .as_dyn_sign()
+                .is_err()
+        );
+        assert!(CommitSign::Disabled.as_dyn_sign().unwrap().is_none());
+    }
 }
@@ -11,8 +11,134 @@ use serde::Deserialize;
 use crate::{
     error::{Error, Result, ResultExt},
     message::{MessageSection, MessageSectionsMap, build_github_body, parse_message},
+    output::{debug, is_verbose},
+    utils::AsyncLoggedOutput,
 };
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    time::Duration,
+};
+
+/// The version of GitHub's REST API that spr is known to work with. It can
+/// be changed with `spr.githubApiVersion`.
+pub const DEFAULT_API_VERSION: &str = "2022-11-28";
+
+/// Endpoints of the GitHub API on github.com. For GitHub Enterprise Server,
+/// they are set with `spr.githubApiHost` and `spr.githubGraphqlHost`.
+pub const DEFAULT_API_URL: &str = "https://api.github.com";
+pub const DEFAULT_GRAPHQL_URL: &str = "https://api.github.com/graphql";
+
+/// The endpoint to use given a configured value, which may be a full URL or
+/// just a host name (for which https is assumed).
+pub fn github_endpoint(configured: Option<String>, default: &str) -> String {
+    match configured {
+        Some(value) if value.contains("://") => value.trim_end_matches('/').to_string(),
+        Some(host) => format!("https://{}", host.trim_end_matches('/')),
+        None => default.to_string(),
+    }
+}
+
+/// The web address of the GitHub instance that serves the given REST API
+/// endpoint, where Pull Requests and files are linked to: `api.github.com`
+/// (or `api.<host>`) is served for `<host>`, and GitHub Enterprise Server
+/// serves its API under `/api/v3`.
+pub fn github_web_url(api_url: &str) -> String {
+    let api_url = api_url.trim_end_matches('/');
+    if let Some(web_url) = api_url.strip_suffix("/api/v3") {
+        return web_url.to_string();
+    }
+    match api_url.split_once("://") {
+        Some((scheme, host)) if host.starts_with("api.") => {
+            format!("{}://{}", scheme, &host["api.".len()..])
+        }
+        _ => api_url.to_string(),
+    }
+}
+
+const API_VERSION_HEADER: reqwest::header::HeaderName =
+    reqwest::header::HeaderName::from_static("x-github-api-version");
+
+/// The headers to send with every request to the GitHub API.
+pub fn default_headers(auth_token: &str, api_version: &str) -> Result<reqwest::header::HeaderMap> {
+    use reqwest::header;
+
+    let mut headers = header::HeaderMap::new();
+    headers.insert(header::ACCEPT, "application/json".parse()?);
+    headers.insert(
+        header::USER_AGENT,
+        format!("spr/{}", env!("CARGO_PKG_VERSION")).try_into()?,
+    );
+    headers.insert(
+        header::AUTHORIZATION,
+        format!("Bearer {}", auth_token).parse()?,
+    );
+    headers.insert(API_VERSION_HEADER, api_version.parse()?);
+    Ok(headers)
+}
+
+/// Builder for the global octocrab instance, which is used for the REST API.
+pub fn octocrab_builder(
+    auth_token: String,
+    api_version: &str,
+    api_url: &str,
+) -> Result<octocrab::OctocrabBuilder> {
+    Ok(octocrab::Octocrab::builder()
+        .personal_token(auth_token)
+        .add_header(API_VERSION_HEADER, api_version.to_string())
+        .base_url(api_url)?)
+}
+
+/// How long to wait before retrying a failed request for the first time. The
+/// wait doubles with every further retry, unless GitHub says how long to wait.
+const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Whether GitHub may well answer the same request successfully a little
+/// later: server errors (like a 502 from a busy GitHub) and rate limits.
+fn is_retryable(status: reqwest::StatusCode, headers: &reqwest::header::HeaderMap) -> bool {
+    status.is_server_error()
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+        // Secondary rate limits are reported as 403 with a Retry-After header
+        || (status == reqwest::StatusCode::FORBIDDEN
+            && headers.contains_key(reqwest::header::RETRY_AFTER))
+}
+
+/// How long to wait before the given retry (counting from 1). A Retry-After
+/// header with a number of seconds takes precedence over the backoff.
+fn retry_delay(headers: &reqwest::header::HeaderMap, retry: u32) -> Duration {
+    headers
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| RETRY_BASE_DELAY * 2u32.saturating_pow(retry.saturating_sub(1)))
+}
+
+/// The error for a response that failed because the rate limit is used up,
+/// saying when it resets. `now` is the current Unix time in seconds.
+fn rate_limit_error(
+    status: reqwest::StatusCode,
+    headers: &reqwest::header::HeaderMap,
+    now: u64,
+) -> Option<Error> {
+    let header = |name: &str| headers.get(name)?.to_str().ok()?.trim().parse::<u64>().ok();
+
+    if !(status == reqwest::StatusCode::FORBIDDEN
+        || status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+        || header("x-ratelimit-remaining") != Some(0)
+    {
+        return None;
+    }
+
+    Some(match header("x-ratelimit-reset") {
+        Some(reset) => Error::new(format!(
+            "GitHub API rate limited, resets at {:02}:{:02} UTC (in {} minutes)",
+            reset % 86400 / 3600,
+            reset % 3600 / 60,
+            reset.saturating_sub(now).div_ceil(60),
+        )),
+        None => Error::new("GitHub API rate limited"),
+    })
+}
 
 #[derive(Clone)]
 pub struct GitHub {
@@ -34,6 +160,9 @@ pub struct PullRequest {
     pub merge_commit: Option<git2::Oid>,
     pub reviewers: HashMap<String, ReviewStatus>,
     pub review_status: Option<ReviewStatus>,
+    pub checks_status: Option<ChecksStatus>,
+    /// The labels the Pull Request has on GitHub
+    pub labels: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -43,7 +172,76 @@ pub enum ReviewStatus {
     Rejected,
 }
 
-#[derive(serde::Serialize, Default, Debug)]
+/// Combined state of the status checks on the head commit of a Pull Request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksStatus {
+    Pending,
+    Success,
+    Failure,
+}
+
+/// The state of a single status check or check run on a commit
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    pub name: String,
+    pub status: ChecksStatus,
+    pub required: bool,
+}
+
+/// The checks on the head commit of a Pull Request
+#[derive(Debug, Clone, Default)]
+pub struct CheckStatus {
+    pub checks: Vec<Check>,
+}
+
+impl CheckStatus {
+    /// The checks that have to pass before merging: the required ones, or
+    /// all of them if the repository doesn't require any.
+    pub fn relevant_checks(&self) -> impl Iterator<Item = &Check> {
+        let any_required = self.checks.iter().any(|check| check.required);
+        self.checks
+            .iter()
+            .filter(move |check| check.required || !any_required)
+    }
+
+    /// Combined state of the relevant checks. A commit without any checks
+    /// counts as successful.
+    pub fn status(&self) -> ChecksStatus {
+        let mut status = ChecksStatus::Success;
+        for check in self.relevant_checks() {
+            match check.status {
+                ChecksStatus::Failure => return ChecksStatus::Failure,
+                ChecksStatus::Pending => status = ChecksStatus::Pending,
+                ChecksStatus::Success => (),
+            }
+        }
+        status
+    }
+
+    /// Count required status checks that haven't reported on the commit yet
+    /// as pending.
+    pub fn add_missing_required(&mut self, contexts: impl IntoIterator<Item = String>) {
+        for name in contexts {
+            if !self.checks.iter().any(|check| check.name == name) {
+                self.checks.push(Check {
+                    name,
+                    status: ChecksStatus::Pending,
+                    required: true,
+                });
+            }
+        }
+    }
+
+    /// Names of the relevant checks in the given state
+    pub fn names(&self, status: ChecksStatus) -> Vec<&str> {
+        self.relevant_checks()
+            .filter(|check| check.status == status)
+            .map(|check| check.name.as_str())
+            .collect()
+    }
+}
+
+#[derive(serde::Serialize, Default, Debug, Clone)]
 pub struct PullRequestUpdate {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub title: Option<String>,
@@ -60,20 +258,25 @@ impl PullRequestUpdate {
         self.title.is_none() && self.body.is_none() && self.base.is_none() && self.state.is_none()
     }
 
-    pub fn update_message(&mut self, pull_request: &PullRequest, message: &MessageSectionsMap) {
+    pub fn update_message(
+        &mut self,
+        pull_request: &PullRequest,
+        message: &MessageSectionsMap,
+        config: &crate::config::Config,
+    ) {
         let title = message.get(&MessageSection::Title);
         if title.is_some() && title != Some(&pull_request.title) {
             self.title = title.cloned();
         }
 
-        let body = build_github_body(message);
+        let body = build_github_body(message, config);
         if pull_request.body.as_ref() != Some(&body) {
             self.body = Some(body);
         }
     }
 }
 
-#[derive(serde::Serialize, Default, Debug)]
+#[derive(serde::Serialize, Default, Debug, Clone)]
 pub struct PullRequestRequestReviewers {
     pub reviewers: Vec<String>,
     pub team_reviewers: Vec<String>,
@@ -99,9 +302,36 @@ pub struct PullRequestMergeability {
     pub base: GitHubBranch,
     pub head_oid: git2::Oid,
     pub mergeable: Option<bool>,
+    pub merge_state: MergeState,
     pub merge_commit: Option<git2::Oid>,
 }
 
+/// GitHub's view of what stands between a Pull Request and merging it (the
+/// `mergeStateStatus` of the Pull Request)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeState {
+    /// Mergeable, possibly with failing checks that are not required
+    Clean,
+    /// The head branch is not up to date with the base branch, which branch
+    /// protection requires
+    Behind,
+    /// Blocked by branch protection, e.g. by required checks or reviews
+    Blocked,
+    /// Merging would conflict
+    Dirty,
+    Draft,
+    /// GitHub hasn't worked it out yet
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeQueueEntry {
+    pub position: i64,
+    pub state: String,
+    /// Estimated time until the Pull Request is merged, in seconds
+    pub estimated_time_to_merge: Option<i64>,
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/gql/schema.docs.graphql",
@@ -111,6 +341,273 @@ pub struct PullRequestMergeability {
 pub struct PullRequestQuery;
 type GitObjectID = String;
 
+/// The response to [`pull_requests_query`]: the Pull Requests by alias.
+#[derive(Debug, Deserialize)]
+struct PullRequestsResponseData {
+    repository: Option<HashMap<String, Option<pull_request_query::PullRequestFields>>>,
+}
+
+/// A query for several Pull Requests, with one aliased `pullRequest` field
+/// each, selecting the same fields as [`PullRequestQuery`].
+fn pull_requests_query(numbers: &[u64]) -> String {
+    let query = pull_request_query::QUERY;
+    let fragment = query.find("fragment ").map_or("", |index| &query[index..]);
+    let fields: String = numbers
+        .iter()
+        .map(|number| {
+            format!(
+                "    pr{number}: pullRequest(number: {number}) {{\n      ...PullRequestFields\n    }}\n"
+            )
+        })
+        .collect();
+
+    format!(
+        "query PullRequestsQuery($name: String!, $owner: String!) {{\n  \
+         repository(owner: $owner, name: $name) {{\n{fields}  }}\n}}\n\n{fragment}"
+    )
+}
+
+/// Fetch the given branches from GitHub with a single `git fetch`, so that
+/// their commits are available locally. Failures are left to the lookups of
+/// the branches afterwards.
+async fn fetch_branches(config: &crate::config::Config, branches: &[&GitHubBranch]) {
+    let mut refspecs: Vec<String> = Vec::new();
+    for branch in branches {
+        let refspec = format!("{}:{}", branch.on_github(), branch.local());
+        if !refspecs.contains(&refspec) {
+            refspecs.push(refspec);
+        }
+    }
+
+    if fetch_refspecs(config, &refspecs).await || refspecs.len() < 2 {
+        return;
+    }
+    // A single branch that is gone (e.g. the head branch of a merged Pull
+    // Request) fails the whole fetch, so get the others one by one
+    for refspec in refspecs {
+        fetch_refspecs(config, std::slice::from_ref(&refspec)).await;
+    }
+}
+
+/// Run `git fetch` for `refspecs`, and return whether it succeeded.
+async fn fetch_refspecs(config: &crate::config::Config, refspecs: &[String]) -> bool {
+    // Fetch refs from remote using git (since we're in a colocated repo)
+    tokio::process::Command::new("git")
+        .args([
+            "fetch",
+            "--no-write-fetch-head",
+            "--no-tags",
+            &config.remote_name,
+        ])
+        .args(refspecs)
+        .logged_output()
+        .await
+        .is_ok_and(|output| output.status.success())
+}
+
+/// The commit the local copy of a fetched branch points to, or the zero oid
+/// if there is none.
+async fn local_branch_oid(branch: &GitHubBranch) -> git2::Oid {
+    match tokio::process::Command::new("git")
+        .args(["rev-parse", branch.local()])
+        .logged_output()
+        .await
+    {
+        Ok(output) if output.status.success() => {
+            let oid_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
+            git2::Oid::from_str(&oid_str).unwrap_or(git2::Oid::zero())
+        }
+        _ => git2::Oid::zero(),
+    }
+}
+
+/// Turn the fields GitHub returned for a Pull Request, whose branches were
+/// fetched already, into a [`PullRequest`] and its mergeability.
+async fn pull_request_from_fields(
+    config: &crate::config::Config,
+    pr: pull_request_query::PullRequestFields,
+    base: GitHubBranch,
+    head: GitHubBranch,
+) -> Result<(PullRequest, PullRequestMergeability)> {
+    let base_oid = local_branch_oid(&base).await;
+    let head_oid = local_branch_oid(&head).await;
+
+    let mut sections = parse_message(&pr.body, MessageSection::Summary, config);
+
+    let title = pr.title.trim().to_string();
+    sections.insert(
+        MessageSection::Title,
+        if title.is_empty() {
+            String::from("(untitled)")
+        } else {
+            title
+        },
+    );
+
+    sections.insert(
+        MessageSection::PullRequest,
+        config.pull_request_url(pr.number as u64),
+    );
+
+    let reviewers: HashMap<String, ReviewStatus> = pr
+        .latest_opinionated_reviews
+        .iter()
+        .flat_map(|all_reviews| &all_reviews.nodes)
+        .flatten()
+        .flatten()
+        .flat_map(|review| {
+            let user_name = review.author.as_ref()?.login.clone();
+            let status = match review.state {
+                pull_request_query::PullRequestReviewState::APPROVED => ReviewStatus::Approved,
+                pull_request_query::PullRequestReviewState::CHANGES_REQUESTED => {
+                    ReviewStatus::Rejected
+                }
+                _ => ReviewStatus::Requested,
+            };
+            Some((user_name, status))
+        })
+        .collect();
+
+    let review_status = match pr.review_decision {
+        Some(pull_request_query::PullRequestReviewDecision::APPROVED) => {
+            Some(ReviewStatus::Approved)
+        }
+        Some(pull_request_query::PullRequestReviewDecision::CHANGES_REQUESTED) => {
+            Some(ReviewStatus::Rejected)
+        }
+        Some(pull_request_query::PullRequestReviewDecision::REVIEW_REQUIRED) => {
+            Some(ReviewStatus::Requested)
+        }
+        _ => None,
+    };
+
+    let requested_reviewers: Vec<String> = pr
+        .review_requests
+        .iter()
+        .flat_map(|x| &x.nodes)
+        .flatten()
+        .flatten()
+        .flat_map(|x| &x.requested_reviewer)
+        .flat_map(|reviewer| {
+            type UserType =
+                pull_request_query::PullRequestFieldsReviewRequestsNodesRequestedReviewer;
+            match reviewer {
+                UserType::User(user) => Some(user.login.clone()),
+                UserType::Team(team) => Some(format!("#{}", team.slug)),
+                _ => None,
+            }
+        })
+        .chain(reviewers.keys().cloned())
+        .collect::<HashSet<String>>() // de-duplicate
+        .into_iter()
+        .collect();
+
+    sections.insert(
+        MessageSection::Reviewers,
+        requested_reviewers.iter().fold(String::new(), |out, slug| {
+            if out.is_empty() {
+                slug.to_string()
+            } else {
+                format!("{}, {}", out, slug)
+            }
+        }),
+    );
+
+    if review_status == Some(ReviewStatus::Approved) {
+        sections.insert(
+            MessageSection::ReviewedBy,
+            reviewers
+                .iter()
+                .filter_map(|(k, v)| {
+                    if v == &ReviewStatus::Approved {
+                        Some(k)
+                    } else {
+                        None
+                    }
+                })
+                .fold(String::new(), |out, slug| {
+                    if out.is_empty() {
+                        slug.to_string()
+                    } else {
+                        format!("{}, {}", out, slug)
+                    }
+                }),
+        );
+    }
+
+    let checks_status =
+        pr.commits
+            .nodes
+            .iter()
+            .flatten()
+            .flatten()
+            .last()
+            .and_then(|node| node.commit.status_check_rollup.as_ref())
+            .and_then(|rollup| match rollup.state {
+                pull_request_query::StatusState::SUCCESS => Some(ChecksStatus::Success),
+                pull_request_query::StatusState::PENDING
+                | pull_request_query::StatusState::EXPECTED => Some(ChecksStatus::Pending),
+                pull_request_query::StatusState::ERROR
+                | pull_request_query::StatusState::FAILURE => Some(ChecksStatus::Failure),
+                _ => None,
+            });
+
+    let mergeability = PullRequestMergeability {
+        base: base.clone(),
+        head_oid: git2::Oid::from_str(&pr.head_ref_oid)?,
+        mergeable: match pr.mergeable {
+            pull_request_query::MergeableState::CONFLICTING => Some(false),
+            pull_request_query::MergeableState::MERGEABLE => Some(true),
+            _ => None,
+        },
+        merge_state: match pr.merge_state_status {
+            pull_request_query::MergeStateStatus::CLEAN
+            | pull_request_query::MergeStateStatus::HAS_HOOKS
+            | pull_request_query::MergeStateStatus::UNSTABLE => MergeState::Clean,
+            pull_request_query::MergeStateStatus::BEHIND => MergeState::Behind,
+            pull_request_query::MergeStateStatus::BLOCKED => MergeState::Blocked,
+            pull_request_query::MergeStateStatus::DIRTY => MergeState::Dirty,
+            pull_request_query::MergeStateStatus::DRAFT => MergeState::Draft,
+            _ => MergeState::Unknown,
+        },
+        merge_commit: pr
+            .merge_commit
+            .as_ref()
+            .and_then(|sha| git2::Oid::from_str(&sha.oid).ok()),
+    };
+
+    let labels = pr
+        .labels
+        .iter()
+        .flat_map(|labels| &labels.nodes)
+        .flatten()
+        .flatten()
+        .map(|label| label.name.clone())
+        .collect();
+
+    let pull_request = PullRequest {
+        number: pr.number as u64,
+        state: match pr.state {
+            pull_request_query::PullRequestState::OPEN => PullRequestState::Open,
+            _ => PullRequestState::Closed,
+        },
+        title: pr.title,
+        body: Some(pr.body),
+        sections,
+        base,
+        head,
+        base_oid,
+        head_oid,
+        reviewers,
+        review_status,
+        checks_status,
+        labels,
+        merge_commit: mergeability.merge_commit,
+    };
+
+    Ok((pull_request, mergeability))
+}
+
 #[derive(GraphQLQuery)]
 #[graphql(
     schema_path = "src/gql/schema.docs.graphql",
@@ -119,6 +616,38 @@ type GitObjectID = String;
 )]
 pub struct PullRequestMergeabilityQuery;
 
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/gql/schema.docs.graphql",
+    query_path = "src/gql/merge_queue_query.graphql",
+    response_derives = "Debug"
+)]
+pub struct MergeQueueQuery;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/gql/schema.docs.graphql",
+    query_path = "src/gql/enqueue_pullrequest_mutation.graphql",
+    response_derives = "Debug"
+)]
+pub struct EnqueuePullRequestMutation;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/gql/schema.docs.graphql",
+    query_path = "src/gql/mark_ready_for_review_mutation.graphql",
+    response_derives = "Debug"
+)]
+pub struct MarkReadyForReviewMutation;
+
+#[derive(GraphQLQuery)]
+#[graphql(
+    schema_path = "src/gql/schema.docs.graphql",
+    query_path = "src/gql/commit_checks_query.graphql",
+    response_derives = "Debug"
+)]
+pub struct CommitChecksQuery;
+
 impl GitHub {
     pub fn new(config: crate::config::Config, graphql_client: reqwest::Client) -> Self {
         Self {
@@ -127,6 +656,69 @@ impl GitHub {
         }
     }
 
+    /// A client for the same GitHub session, using `config` instead.
+    pub fn with_config(&self, config: crate::config::Config) -> Self {
+        Self {
+            config,
+            graphql_client: self.graphql_client.clone(),
+        }
+    }
+
+    /// Send a request to the GitHub API. Server errors and rate limits are
+    /// retried with exponential backoff, making at most
+    /// `spr.githubRequestAttempts` attempts in total. The last response is
+    /// returned whatever its status, unless the rate limit is used up.
+    async fn send_with_retry(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response> {
+        let mut retry = 0;
+        loop {
+            let response = request
+                .try_clone()
+                .ok_or_else(|| Error::new("request to GitHub cannot be retried"))?
+                .send()
+                .await?;
+            retry += 1;
+            if retry >= self.config.github_request_attempts
+                || !is_retryable(response.status(), response.headers())
+            {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map_or(0, |now| now.as_secs());
+                return match rate_limit_error(response.status(), response.headers(), now) {
+                    Some(error) => Err(error),
+                    None => Ok(response),
+                };
+            }
+            tokio::time::sleep(retry_delay(response.headers(), retry)).await;
+        }
+    }
+
+    /// Send a GraphQL query or mutation, retrying as described in
+    /// [`GitHub::send_with_retry`].
+    async fn graphql<B: serde::Serialize, R: serde::de::DeserializeOwned>(
+        &self,
+        request_body: &B,
+    ) -> Result<Response<R>> {
+        // mergeStateStatus is still a preview feature of the GraphQL API
+        let request = self
+            .graphql_client
+            .post(&self.config.github_graphql_url)
+            .header(
+                reqwest::header::ACCEPT,
+                "application/vnd.github.merge-info-preview+json",
+            )
+            .json(request_body);
+        if is_verbose() {
+            debug(&format!(
+                "GraphQL request: {}",
+                serde_json::to_string(request_body)?
+            ));
+        }
+        let res = self.send_with_retry(request).await?.error_for_status()?;
+        let text = res.text().await?;
+        debug(&format!("GraphQL response: {}", text));
+        Ok(serde_json::from_str(&text)?)
+    }
+
     pub async fn get_github_user(login: String) -> Result<UserWithName> {
         octocrab::instance()
             .get::<UserWithName, _, _>(format!("users/{}", login), None::<&()>)
@@ -146,10 +738,17 @@ impl GitHub {
     }
 
     pub async fn get_pull_request(self, number: u64) -> Result<PullRequest> {
-        let GitHub {
-            config,
-            graphql_client,
-        } = self;
+        Ok(self.get_pull_request_with_mergeability(number).await?.0)
+    }
+
+    /// Like [`GitHub::get_pull_request`], together with what
+    /// [`GitHub::get_pull_request_mergeability`] would return, from the same
+    /// query.
+    pub async fn get_pull_request_with_mergeability(
+        self,
+        number: u64,
+    ) -> Result<(PullRequest, PullRequestMergeability)> {
+        let config = &self.config;
 
         let variables = pull_request_query::Variables {
             name: config.repo.clone(),
@@ -157,12 +756,8 @@ impl GitHub {
             number: number as i64,
         };
         let request_body = PullRequestQuery::build_query(variables);
-        let res = graphql_client
-            .post("https://api.github.com/graphql")
-            .json(&request_body)
-            .send()
-            .await?;
-        let response_body: Response<pull_request_query::ResponseData> = res.json().await?;
+        let response_body: Response<pull_request_query::ResponseData> =
+            self.graphql(&request_body).await?;
 
         if let Some(errors) = response_body.errors {
             let error = Err(Error::new(format!("fetching PR #{number} failed")));
@@ -181,168 +776,67 @@ impl GitHub {
 
         let base = config.new_github_branch_from_ref(&pr.base_ref_name)?;
         let head = config.new_github_branch_from_ref(&pr.head_ref_name)?;
+        fetch_branches(config, &[&base, &head]).await;
 
-        // Fetch refs from remote using git (since we're in a colocated repo)
-        let _fetch_result = tokio::process::Command::new("git")
-            .args([
-                "fetch",
-                "--no-write-fetch-head",
-                "--no-tags",
-                &config.remote_name,
-                &format!("{}:{}", head.on_github(), head.local()),
-                &format!("{}:{}", base.on_github(), base.local()),
-            ])
-            .output()
-            .await;
-
-        // Convert branch refs to OIDs
-        let base_oid = if let Ok(output) = tokio::process::Command::new("git")
-            .args(["rev-parse", base.local()])
-            .output()
-            .await
-        {
-            if output.status.success() {
-                let oid_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                git2::Oid::from_str(&oid_str).unwrap_or(git2::Oid::zero())
-            } else {
-                git2::Oid::zero()
-            }
-        } else {
-            git2::Oid::zero()
-        };
-
-        let head_oid = if let Ok(output) = tokio::process::Command::new("git")
-            .args(["rev-parse", head.local()])
-            .output()
-            .await
-        {
-            if output.status.success() {
-                let oid_str = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                git2::Oid::from_str(&oid_str).unwrap_or(git2::Oid::zero())
-            } else {
-                git2::Oid::zero()
-            }
-        } else {
-            git2::Oid::zero()
-        };
+        pull_request_from_fields(config, pr, base, head).await
+    }
 
-        let mut sections = parse_message(&pr.body, MessageSection::Summary);
+    /// Like [`GitHub::get_pull_request`] for several Pull Requests at once,
+    /// e.g. those of a stack, with a single query. Pull Requests that don't
+    /// exist are missing from the result.
+    pub async fn get_pull_requests(&self, numbers: &[u64]) -> Result<HashMap<u64, PullRequest>> {
+        let config = &self.config;
+        if numbers.is_empty() {
+            return Ok(HashMap::new());
+        }
 
-        let title = pr.title.trim().to_string();
-        sections.insert(
-            MessageSection::Title,
-            if title.is_empty() {
-                String::from("(untitled)")
-            } else {
-                title
+        let request_body = serde_json::json!({
+            "query": pull_requests_query(numbers),
+            "variables": {
+                "name": config.repo,
+                "owner": config.owner,
             },
-        );
-
-        sections.insert(MessageSection::PullRequest, config.pull_request_url(number));
-
-        let reviewers: HashMap<String, ReviewStatus> = pr
-            .latest_opinionated_reviews
-            .iter()
-            .flat_map(|all_reviews| &all_reviews.nodes)
-            .flatten()
-            .flatten()
-            .flat_map(|review| {
-                let user_name = review.author.as_ref()?.login.clone();
-                let status = match review.state {
-                    pull_request_query::PullRequestReviewState::APPROVED => ReviewStatus::Approved,
-                    pull_request_query::PullRequestReviewState::CHANGES_REQUESTED => {
-                        ReviewStatus::Rejected
-                    }
-                    _ => ReviewStatus::Requested,
-                };
-                Some((user_name, status))
-            })
-            .collect();
+        });
+        let response_body: Response<PullRequestsResponseData> = self.graphql(&request_body).await?;
 
-        let review_status = match pr.review_decision {
-            Some(pull_request_query::PullRequestReviewDecision::APPROVED) => {
-                Some(ReviewStatus::Approved)
-            }
-            Some(pull_request_query::PullRequestReviewDecision::CHANGES_REQUESTED) => {
-                Some(ReviewStatus::Rejected)
-            }
-            Some(pull_request_query::PullRequestReviewDecision::REVIEW_REQUIRED) => {
-                Some(ReviewStatus::Requested)
-            }
-            _ => None,
+        // GitHub reports a Pull Request that doesn't exist as an error next
+        // to the data for the others
+        let Some(data) = response_body.data else {
+            let error = Err(Error::new("fetching Pull Requests failed"));
+            return response_body
+                .errors
+                .into_iter()
+                .flatten()
+                .fold(error, |err, e| err.context(e.to_string()));
         };
-
-        let requested_reviewers: Vec<String> = pr.review_requests
-            .iter()
-            .flat_map(|x| &x.nodes)
-            .flatten()
+        let pull_requests: Vec<_> = data
+            .repository
+            .ok_or_else(|| Error::new("failed to find repository"))?
+            .into_values()
             .flatten()
-            .flat_map(|x| &x.requested_reviewer)
-            .flat_map(|reviewer| {
-              type UserType = pull_request_query::PullRequestQueryRepositoryPullRequestReviewRequestsNodesRequestedReviewer;
-              match reviewer {
-                UserType::User(user) => Some(user.login.clone()),
-                UserType::Team(team) => Some(format!("#{}", team.slug)),
-                _ => None,
-              }
-            })
-            .chain(reviewers.keys().cloned())
-            .collect::<HashSet<String>>() // de-duplicate
-            .into_iter()
             .collect();
 
-        sections.insert(
-            MessageSection::Reviewers,
-            requested_reviewers.iter().fold(String::new(), |out, slug| {
-                if out.is_empty() {
-                    slug.to_string()
-                } else {
-                    format!("{}, {}", out, slug)
-                }
-            }),
-        );
+        let mut branches = Vec::new();
+        for pr in pull_requests {
+            let base = config.new_github_branch_from_ref(&pr.base_ref_name)?;
+            let head = config.new_github_branch_from_ref(&pr.head_ref_name)?;
+            branches.push((pr, base, head));
+        }
+        fetch_branches(
+            config,
+            &branches
+                .iter()
+                .flat_map(|(_, base, head)| [base, head])
+                .collect::<Vec<_>>(),
+        )
+        .await;
 
-        if review_status == Some(ReviewStatus::Approved) {
-            sections.insert(
-                MessageSection::ReviewedBy,
-                reviewers
-                    .iter()
-                    .filter_map(|(k, v)| {
-                        if v == &ReviewStatus::Approved {
-                            Some(k)
-                        } else {
-                            None
-                        }
-                    })
-                    .fold(String::new(), |out, slug| {
-                        if out.is_empty() {
-                            slug.to_string()
-                        } else {
-                            format!("{}, {}", out, slug)
-                        }
-                    }),
-            );
-        }
-
-        Ok::<_, Error>(PullRequest {
-            number: pr.number as u64,
-            state: match pr.state {
-                pull_request_query::PullRequestState::OPEN => PullRequestState::Open,
-                _ => PullRequestState::Closed,
-            },
-            title: pr.title,
-            body: Some(pr.body),
-            sections,
-            base,
-            head,
-            base_oid,
-            head_oid,
-            reviewers,
-            review_status,
-            merge_commit: pr
-                .merge_commit
-                .and_then(|sha| git2::Oid::from_str(&sha.oid).ok()),
-        })
+        let mut result = HashMap::new();
+        for (pr, base, head) in branches {
+            let (pull_request, _) = pull_request_from_fields(config, pr, base, head).await?;
+            result.insert(pull_request.number, pull_request);
+        }
+        Ok(result)
     }
 
     pub async fn create_pull_request(
@@ -361,7 +855,7 @@ impl GitHub {
                 head_ref_name,
                 base_ref_name,
             )
-            .body(build_github_body(message))
+            .body(build_github_body(message, &self.config))
             .draft(Some(draft))
             .send()
             .await?
@@ -371,19 +865,41 @@ impl GitHub {
     }
 
     pub async fn update_pull_request(&self, number: u64, updates: PullRequestUpdate) -> Result<()> {
-        octocrab::instance()
-            .patch::<octocrab::models::pulls::PullRequest, _, _>(
-                format!(
-                    "repos/{}/{}/pulls/{}",
-                    self.config.owner, self.config.repo, number
-                ),
-                Some(&updates),
-            )
-            .await?;
+        // Sent with the same client as GraphQL requests (rather than
+        // octocrab), so that it is retried in the same way
+        let request = self
+            .graphql_client
+            .patch(format!(
+                "{}/repos/{}/{}/pulls/{}",
+                self.config.github_api_url, self.config.owner, self.config.repo, number
+            ))
+            .json(&updates);
+        self.send_with_retry(request)
+            .await?
+            .error_for_status()
+            .convert()
+            .context(format!("updating PR #{number} failed"))?;
 
         Ok(())
     }
 
+    /// Close a Pull Request. If a reason is given, it is posted as a comment
+    /// first, so that the Pull Request says why it was closed.
+    pub async fn close_pull_request(&self, number: u64, reason: Option<String>) -> Result<()> {
+        if let Some(reason) = reason {
+            self.post_comment(number, &reason).await?;
+        }
+
+        self.update_pull_request(
+            number,
+            PullRequestUpdate {
+                state: Some(PullRequestState::Closed),
+                ..Default::default()
+            },
+        )
+        .await
+    }
+
     pub async fn request_reviewers(
         &self,
         number: u64,
@@ -404,6 +920,183 @@ impl GitHub {
         Ok(())
     }
 
+    /// Add a comment to the conversation of a Pull Request.
+    pub async fn post_comment(&self, number: u64, body: &str) -> Result<()> {
+        octocrab::instance()
+            .issues(self.config.owner.clone(), self.config.repo.clone())
+            .create_comment(number, body)
+            .await?;
+
+        Ok(())
+    }
+
+    pub async fn add_labels(&self, number: u64, labels: &[String]) -> Result<()> {
+        octocrab::instance()
+            .issues(self.config.owner.clone(), self.config.repo.clone())
+            .add_labels(number, labels)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Replace the assignees of an issue or Pull Request.
+    pub async fn set_assignees(&self, number: u64, assignees: &[String]) -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct Assignees<'a> {
+            assignees: &'a [String],
+        }
+        octocrab::instance()
+            .patch::<octocrab::models::issues::Issue, _, _>(
+                format!(
+                    "repos/{}/{}/issues/{}",
+                    self.config.owner, self.config.repo, number
+                ),
+                Some(&Assignees { assignees }),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The number of the open milestone with the given title, if the
+    /// repository has one.
+    pub async fn find_milestone(&self, title: &str) -> Result<Option<u64>> {
+        #[derive(Deserialize)]
+        struct Milestone {
+            number: u64,
+            title: String,
+        }
+        let milestones: Vec<Milestone> = octocrab::instance()
+            .get(
+                format!(
+                    "repos/{}/{}/milestones",
+                    self.config.owner, self.config.repo
+                ),
+                Some(&[("state", "open"), ("per_page", "100")]),
+            )
+            .await?;
+
+        Ok(milestones
+            .into_iter()
+            .find(|milestone| milestone.title == title)
+            .map(|milestone| milestone.number))
+    }
+
+    /// Set the milestone of an issue or Pull Request.
+    pub async fn set_milestone(&self, number: u64, milestone: u64) -> Result<()> {
+        #[derive(serde::Serialize)]
+        struct Milestone {
+            milestone: u64,
+        }
+        octocrab::instance()
+            .patch::<octocrab::models::issues::Issue, _, _>(
+                format!(
+                    "repos/{}/{}/issues/{}",
+                    self.config.owner, self.config.repo, number
+                ),
+                Some(&Milestone { milestone }),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// The ones among `logins` that can't be assigned to issues and Pull
+    /// Requests in the repository.
+    pub async fn unassignable_users(&self, logins: &[String]) -> Result<Vec<String>> {
+        let mut unassignable = Vec::new();
+        for login in logins {
+            if !octocrab::instance()
+                .issues(self.config.owner.clone(), self.config.repo.clone())
+                .check_assignee(login)
+                .await?
+            {
+                unassignable.push(login.clone());
+            }
+        }
+
+        Ok(unassignable)
+    }
+
+    /// The ones among `labels` that the repository does not have. Adding
+    /// those to a Pull Request would create them.
+    pub async fn missing_labels(&self, labels: &[String]) -> Result<Vec<String>> {
+        let mut missing = Vec::new();
+        for label in labels {
+            match octocrab::instance()
+                .issues(self.config.owner.clone(), self.config.repo.clone())
+                .get_label(label)
+                .await
+            {
+                Ok(_) => (),
+                Err(octocrab::Error::GitHub { .. }) => missing.push(label.clone()),
+                Err(error) => return Err(error.into()),
+            }
+        }
+
+        Ok(missing)
+    }
+
+    pub async fn remove_label(&self, number: u64, label: &str) -> Result<()> {
+        octocrab::instance()
+            .issues(self.config.owner.clone(), self.config.repo.clone())
+            .remove_label(number, label)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Take a Pull Request out of draft state.
+    pub async fn mark_ready_for_review(&self, number: u64) -> Result<()> {
+        let pull_request = octocrab::instance()
+            .pulls(self.config.owner.clone(), self.config.repo.clone())
+            .get(number)
+            .await?;
+        if pull_request.draft != Some(true) {
+            return Ok(());
+        }
+        let pull_request_id = pull_request
+            .node_id
+            .ok_or_else(|| Error::new(format!("failed to find PR #{number}")))?;
+
+        let request_body =
+            MarkReadyForReviewMutation::build_query(mark_ready_for_review_mutation::Variables {
+                pull_request_id,
+            });
+        let response_body: Response<mark_ready_for_review_mutation::ResponseData> =
+            self.graphql(&request_body).await?;
+
+        if let Some(errors) = response_body.errors {
+            let error = Err(Error::new(format!(
+                "marking PR #{number} as ready for review failed"
+            )));
+            return errors
+                .into_iter()
+                .fold(error, |err, e| err.context(e.to_string()));
+        }
+
+        Ok(())
+    }
+
+    /// The number of a Pull Request whose head is the given branch, if there
+    /// is one. Open Pull Requests are preferred over closed ones.
+    pub async fn find_pull_request_for_head(&self, branch: &GitHubBranch) -> Result<Option<u64>> {
+        let pull_requests = octocrab::instance()
+            .pulls(self.config.owner.clone(), self.config.repo.clone())
+            .list()
+            .head(format!("{}:{}", self.config.owner, branch.branch_name()))
+            .state(octocrab::params::State::All)
+            .send()
+            .await?;
+
+        Ok(pull_requests
+            .items
+            .iter()
+            .find(|pr| pr.state == Some(octocrab::models::IssueState::Open))
+            .or(pull_requests.items.first())
+            .map(|pr| pr.number))
+    }
+
     pub async fn get_pull_request_mergeability(
         &self,
         number: u64,
@@ -414,14 +1107,8 @@ impl GitHub {
             number: number as i64,
         };
         let request_body = PullRequestMergeabilityQuery::build_query(variables);
-        let res = self
-            .graphql_client
-            .post("https://api.github.com/graphql")
-            .json(&request_body)
-            .send()
-            .await?;
         let response_body: Response<pull_request_mergeability_query::ResponseData> =
-            res.json().await?;
+            self.graphql(&request_body).await?;
 
         if let Some(errors) = response_body.errors {
             let error = Err(Error::new(format!(
@@ -449,11 +1136,204 @@ impl GitHub {
                 pull_request_mergeability_query::MergeableState::UNKNOWN => None,
                 _ => None,
             },
+            merge_state: match pr.merge_state_status {
+                pull_request_mergeability_query::MergeStateStatus::CLEAN
+                | pull_request_mergeability_query::MergeStateStatus::HAS_HOOKS
+                | pull_request_mergeability_query::MergeStateStatus::UNSTABLE => MergeState::Clean,
+                pull_request_mergeability_query::MergeStateStatus::BEHIND => MergeState::Behind,
+                pull_request_mergeability_query::MergeStateStatus::BLOCKED => MergeState::Blocked,
+                pull_request_mergeability_query::MergeStateStatus::DIRTY => MergeState::Dirty,
+                pull_request_mergeability_query::MergeStateStatus::DRAFT => MergeState::Draft,
+                _ => MergeState::Unknown,
+            },
             merge_commit: pr
                 .merge_commit
                 .and_then(|sha| git2::Oid::from_str(&sha.oid).ok()),
         })
     }
+
+    /// The status checks and check runs on `head_oid`, the head commit of the
+    /// given Pull Request (which decides which checks are required).
+    pub async fn get_check_status(&self, number: u64, head_oid: git2::Oid) -> Result<CheckStatus> {
+        let variables = commit_checks_query::Variables {
+            name: self.config.repo.clone(),
+            owner: self.config.owner.clone(),
+            oid: head_oid.to_string(),
+            number: number as i64,
+        };
+        let request_body = CommitChecksQuery::build_query(variables);
+        let response_body: Response<commit_checks_query::ResponseData> =
+            self.graphql(&request_body).await?;
+
+        if let Some(errors) = response_body.errors {
+            let error = Err(Error::new(format!(
+                "querying the checks of PR #{number} failed"
+            )));
+            return errors
+                .into_iter()
+                .fold(error, |err, e| err.context(e.to_string()));
+        }
+
+        type ObjectType = commit_checks_query::CommitChecksQueryRepositoryObject;
+        type ContextType =
+            commit_checks_query::CommitChecksQueryRepositoryObjectOnCommitStatusCheckRollupContextsNodes;
+        let repository = response_body
+            .data
+            .ok_or_else(|| Error::new("failed to fetch checks"))?
+            .repository
+            .ok_or_else(|| Error::new("failed to find repository"))?;
+        let required_contexts: Vec<String> = repository
+            .pull_request
+            .and_then(|pull_request| pull_request.base_ref)
+            .and_then(|base_ref| base_ref.branch_protection_rule)
+            .and_then(|rule| rule.required_status_check_contexts)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .collect();
+        let commit = match repository.object {
+            Some(ObjectType::Commit(commit)) => commit,
+            _ => return Err(Error::new(format!("failed to find commit {}", head_oid))),
+        };
+
+        let checks = commit
+            .status_check_rollup
+            .and_then(|rollup| rollup.contexts.nodes)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .map(|context| match context {
+                ContextType::CheckRun(run) => Check {
+                    name: run.name,
+                    status: match run.conclusion {
+                        None => ChecksStatus::Pending,
+                        Some(
+                            commit_checks_query::CheckConclusionState::SUCCESS
+                            | commit_checks_query::CheckConclusionState::NEUTRAL
+                            | commit_checks_query::CheckConclusionState::SKIPPED,
+                        ) => ChecksStatus::Success,
+                        Some(_) => ChecksStatus::Failure,
+                    },
+                    required: run.is_required,
+                },
+                ContextType::StatusContext(context) => Check {
+                    name: context.context,
+                    status: match context.state {
+                        commit_checks_query::StatusState::SUCCESS => ChecksStatus::Success,
+                        commit_checks_query::StatusState::ERROR
+                        | commit_checks_query::StatusState::FAILURE => ChecksStatus::Failure,
+                        _ => ChecksStatus::Pending,
+                    },
+                    required: context.is_required,
+                },
+            })
+            .collect();
+
+        let mut status = CheckStatus { checks };
+        status.add_missing_required(required_contexts);
+        Ok(status)
+    }
+
+    /// Add a Pull Request to the merge queue of the master branch. Fails if
+    /// the master branch does not use a merge queue, or if the head of the
+    /// Pull Request is not `head_oid` anymore.
+    pub async fn enqueue_pull_request(
+        &self,
+        number: u64,
+        head_oid: git2::Oid,
+    ) -> Result<MergeQueueEntry> {
+        let variables = merge_queue_query::Variables {
+            name: self.config.repo.clone(),
+            owner: self.config.owner.clone(),
+            number: number as i64,
+            branch: self.config.master_ref.branch_name().to_string(),
+        };
+        let request_body = MergeQueueQuery::build_query(variables);
+        let response_body: Response<merge_queue_query::ResponseData> =
+            self.graphql(&request_body).await?;
+
+        if let Some(errors) = response_body.errors {
+            let error = Err(Error::new(format!(
+                "querying the merge queue for PR #{number} failed"
+            )));
+            return errors
+                .into_iter()
+                .fold(error, |err, e| err.context(e.to_string()));
+        }
+
+        let repository = response_body
+            .data
+            .ok_or_else(|| Error::new("failed to query merge queue"))?
+            .repository
+            .ok_or_else(|| Error::new("failed to find repository"))?;
+        if repository.merge_queue.is_none() {
+            return Err(Error::new(format!(
+                "The '{}' branch of {}/{} does not use a merge queue. Run \
+                 `spr land` without --queue to merge directly.",
+                self.config.master_ref.branch_name(),
+                self.config.owner,
+                self.config.repo,
+            )));
+        }
+        let pull_request_id = repository
+            .pull_request
+            .ok_or_else(|| Error::new("failed to find PR"))?
+            .id;
+
+        let request_body = enqueue_pull_request_body(pull_request_id, head_oid);
+        let response_body: Response<enqueue_pull_request_mutation::ResponseData> =
+            self.graphql(&request_body).await?;
+
+        if let Some(errors) = response_body.errors {
+            let error = Err(Error::new(format!(
+                "adding PR #{number} to the merge queue failed"
+            )));
+            return errors
+                .into_iter()
+                .fold(error, |err, e| err.context(e.to_string()));
+        }
+
+        merge_queue_entry_from_response(
+            response_body
+                .data
+                .ok_or_else(|| Error::new("failed to add PR to the merge queue"))?,
+        )
+    }
+}
+
+fn enqueue_pull_request_body(
+    pull_request_id: String,
+    head_oid: git2::Oid,
+) -> graphql_client::QueryBody<enqueue_pull_request_mutation::Variables> {
+    EnqueuePullRequestMutation::build_query(enqueue_pull_request_mutation::Variables {
+        pull_request_id,
+        expected_head_oid: Some(head_oid.to_string()),
+    })
+}
+
+fn merge_queue_entry_from_response(
+    data: enqueue_pull_request_mutation::ResponseData,
+) -> Result<MergeQueueEntry> {
+    use enqueue_pull_request_mutation::MergeQueueEntryState;
+
+    let entry = data
+        .enqueue_pull_request
+        .and_then(|payload| payload.merge_queue_entry)
+        .ok_or_else(|| Error::new("GitHub did not return a merge queue entry"))?;
+
+    Ok(MergeQueueEntry {
+        position: entry.position,
+        state: match entry.state {
+            MergeQueueEntryState::AWAITING_CHECKS => "awaiting checks",
+            MergeQueueEntryState::LOCKED => "locked",
+            MergeQueueEntryState::MERGEABLE => "mergeable",
+            MergeQueueEntryState::QUEUED => "queued",
+            MergeQueueEntryState::UNMERGEABLE => "unmergeable",
+            _ => "unknown",
+        }
+        .to_string(),
+        estimated_time_to_merge: entry.estimated_time_to_merge,
+    })
 }
 
 #[derive(Debug, Clone)]
@@ -524,6 +1404,114 @@ mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
 
+    #[test]
+    fn test_retry() {
+        use reqwest::{StatusCode, header::HeaderMap};
+
+        let mut retry_after = HeaderMap::new();
+        retry_after.insert(reqwest::header::RETRY_AFTER, "7".parse().unwrap());
+
+        assert!(is_retryable(StatusCode::BAD_GATEWAY, &HeaderMap::new()));
+        assert!(is_retryable(
+            StatusCode::TOO_MANY_REQUESTS,
+            &HeaderMap::new()
+        ));
+        assert!(is_retryable(StatusCode::FORBIDDEN, &retry_after));
+        assert!(!is_retryable(StatusCode::FORBIDDEN, &HeaderMap::new()));
+        assert!(!is_retryable(StatusCode::NOT_FOUND, &HeaderMap::new()));
+
+        assert_eq!(retry_delay(&HeaderMap::new(), 1), Duration::from_secs(1));
+        assert_eq!(retry_delay(&HeaderMap::new(), 3), Duration::from_secs(4));
+        assert_eq!(retry_delay(&retry_after, 3), Duration::from_secs(7));
+    }
+
+    #[test]
+    fn test_rate_limit_error() {
+        use reqwest::{StatusCode, header::HeaderMap};
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000700".parse().unwrap());
+
+        let error = rate_limit_error(StatusCode::FORBIDDEN, &headers, 1_700_000_000).unwrap();
+        assert_eq!(
+            error.messages(),
+            &["GitHub API rate limited, resets at 22:25 UTC (in 12 minutes)"]
+        );
+        assert!(rate_limit_error(StatusCode::OK, &headers, 1_700_000_000).is_none());
+
+        headers.insert("x-ratelimit-remaining", "12".parse().unwrap());
+        assert!(rate_limit_error(StatusCode::FORBIDDEN, &headers, 1_700_000_000).is_none());
+    }
+
+    #[test]
+    fn test_github_web_url() {
+        assert_eq!(github_web_url(DEFAULT_API_URL), "https://github.com");
+        assert_eq!(
+            github_web_url("https://github.example.com/api/v3/"),
+            "https://github.example.com"
+        );
+        assert_eq!(
+            github_web_url("https://api.acme.ghe.com"),
+            "https://acme.ghe.com"
+        );
+    }
+
+    #[test]
+    fn test_github_endpoint() {
+        assert_eq!(
+            github_endpoint(None, DEFAULT_GRAPHQL_URL),
+            "https://api.github.com/graphql"
+        );
+        assert_eq!(
+            github_endpoint(
+                Some("https://github.example.com/api/graphql".into()),
+                DEFAULT_GRAPHQL_URL
+            ),
+            "https://github.example.com/api/graphql"
+        );
+        assert_eq!(
+            github_endpoint(Some("github.example.com/api/v3/".into()), DEFAULT_API_URL),
+            "https://github.example.com/api/v3"
+        );
+        assert!(octocrab_builder("token".into(), DEFAULT_API_VERSION, DEFAULT_API_URL).is_ok());
+        assert!(octocrab_builder("token".into(), DEFAULT_API_VERSION, "not a url").is_err());
+    }
+
+    #[test]
+    fn test_pull_requests_query() {
+        let query = pull_requests_query(&[12, 34]);
+        assert!(query.starts_with("query PullRequestsQuery("));
+        assert!(
+            query.contains("    pr12: pullRequest(number: 12) {\n      ...PullRequestFields\n")
+        );
+        assert!(query.contains("    pr34: pullRequest(number: 34) {"));
+        assert_eq!(
+            query
+                .matches("fragment PullRequestFields on PullRequest")
+                .count(),
+            1
+        );
+        assert!(!query.contains("$number"));
+
+        // A Pull Request that doesn't exist comes back as null
+        let data: PullRequestsResponseData =
+            serde_json::from_str(r#"{"repository": {"pr12": null}}"#).unwrap();
+        assert!(data.repository.unwrap()["pr12"].is_none());
+    }
+
+    #[test]
+    fn test_default_headers_pin_api_version() {
+        let headers = default_headers("secret", DEFAULT_API_VERSION).unwrap();
+        assert_eq!(headers["x-github-api-version"], "2022-11-28");
+        assert_eq!(headers[reqwest::header::AUTHORIZATION], "Bearer secret");
+
+        let headers = default_headers("secret", "2026-03-10").unwrap();
+        assert_eq!(headers["X-GitHub-Api-Version"], "2026-03-10");
+
+        assert!(default_headers("secret", "bad\nversion").is_err());
+    }
+
     #[test]
     fn test_new_from_ref_with_branch_name() {
         let r = GitHubBranch::new_from_ref("foo", "github-remote", "masterbranch").unwrap();
@@ -596,6 +1584,53 @@ mod tests {
         assert!(!r.is_master_branch());
     }
 
+    #[test]
+    fn test_enqueue_pull_request_body() {
+        let head_oid = git2::Oid::from_str("0123456789abcdef0123456789abcdef01234567").unwrap();
+        let body = enqueue_pull_request_body("PR_kwDOABCDEF".to_string(), head_oid);
+
+        assert_eq!(body.operation_name, "EnqueuePullRequestMutation");
+        assert!(body.query.contains("enqueuePullRequest("));
+        assert_eq!(body.variables.pull_request_id, "PR_kwDOABCDEF");
+        assert_eq!(
+            body.variables.expected_head_oid.as_deref(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+    }
+
+    #[test]
+    fn test_merge_queue_entry_from_response() {
+        use enqueue_pull_request_mutation::*;
+
+        let entry = merge_queue_entry_from_response(ResponseData {
+            enqueue_pull_request: Some(EnqueuePullRequestMutationEnqueuePullRequest {
+                merge_queue_entry: Some(
+                    EnqueuePullRequestMutationEnqueuePullRequestMergeQueueEntry {
+                        position: 3,
+                        state: MergeQueueEntryState::AWAITING_CHECKS,
+                        estimated_time_to_merge: Some(600),
+                    },
+                ),
+            }),
+        })
+        .unwrap();
+        assert_eq!(
+            entry,
+            MergeQueueEntry {
+                position: 3,
+                state: "awaiting checks".to_string(),
+                estimated_time_to_merge: Some(600),
+            }
+        );
+
+        assert!(
+            merge_queue_entry_from_response(ResponseData {
+                enqueue_pull_request: None
+            })
+            .is_err()
+        );
+    }
+
     #[test]
     fn test_new_from_edge_case_branch_name() {
         let r =
@@ -605,4 +1640,39 @@ mod tests {
         assert_eq!(r.branch_name(), "refs/heads/foo");
         assert!(!r.is_master_branch());
     }
+
+    #[test]
+    fn test_check_status_only_counts_required_checks() {
+        let check = |name: &str, status, required| Check {
+            name: name.to_string(),
+            status,
+            required,
+        };
+
+        let mut checks = CheckStatus {
+            checks: vec![
+                check("build", ChecksStatus::Success, true),
+                check("lint", ChecksStatus::Failure, false),
+                check("test", ChecksStatus::Pending, true),
+            ],
+        };
+        assert_eq!(checks.status(), ChecksStatus::Pending);
+        assert_eq!(checks.names(ChecksStatus::Pending), vec!["test"]);
+        assert!(checks.names(ChecksStatus::Failure).is_empty());
+
+        // Without any required checks, all of them count
+        for check in &mut checks.checks {
+            check.required = false;
+        }
+        assert_eq!(checks.status(), ChecksStatus::Failure);
+        assert_eq!(checks.names(ChecksStatus::Failure), vec!["lint"]);
+
+        assert_eq!(CheckStatus::default().status(), ChecksStatus::Success);
+
+        // Required checks that haven't reported yet are pending
+        checks.add_missing_required(["lint".to_string(), "deploy".to_string()]);
+        assert_eq!(checks.checks.len(), 4);
+        assert_eq!(checks.status(), ChecksStatus::Pending);
+        assert_eq!(checks.names(ChecksStatus::Pending), vec!["deploy"]);
+    }
 }
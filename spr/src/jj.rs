@@ -6,15 +6,19 @@
  */
 
 use std::{
+    collections::{HashMap, HashSet},
     ffi::OsStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
 };
 
 use crate::{
     config::Config,
     error::{Error, Result, ResultExt},
+    git::CommitSign,
     message::{MessageSection, MessageSectionsMap, build_commit_message, parse_message},
+    output::{debug_command, debug_exit},
+    utils::LoggedOutput,
 };
 use git2::Oid;
 
@@ -23,6 +27,9 @@ pub struct PreparedCommit {
     pub oid: Oid,
     pub short_id: String,
     pub parent_oid: Oid,
+    /// The parent commit, if it has a Pull Request of its own, i.e. if this
+    /// commit's Pull Request is stacked on top of another one
+    pub base_oid: Option<Oid>,
     pub message: MessageSectionsMap,
     pub pull_request_number: Option<u64>,
     pub message_changed: bool,
@@ -32,6 +39,10 @@ pub struct Jujutsu {
     repo_path: PathBuf,
     jj_bin: PathBuf,
     pub git_repo: git2::Repository,
+    /// Whether to sign commits, see `with_commit_signing`
+    sign_commits: Option<bool>,
+    /// Set up when the first commit is created
+    sign: std::sync::OnceLock<CommitSign>,
 }
 
 impl Jujutsu {
@@ -56,9 +67,26 @@ impl Jujutsu {
             repo_path,
             jj_bin,
             git_repo,
+            sign_commits: None,
+            sign: std::sync::OnceLock::new(),
         })
     }
 
+    /// Sign the commits spr creates (e.g. for Pull Request branches) if
+    /// `enabled` is true. `None` follows the repository's `commit.gpgsign`.
+    pub fn with_commit_signing(mut self, enabled: Option<bool>) -> Self {
+        self.sign_commits = enabled;
+        self
+    }
+
+    fn commit_sign(&self) -> Result<&CommitSign> {
+        if let Some(sign) = self.sign.get() {
+            return Ok(sign);
+        }
+        let sign = CommitSign::new(&self.git_repo, &self.git_repo.config()?, self.sign_commits);
+        Ok(self.sign.get_or_init(|| sign))
+    }
+
     pub fn get_prepared_commit_for_revision(
         &self,
         config: &Config,
@@ -69,12 +97,114 @@ impl Jujutsu {
     }
 
     pub fn get_master_base_for_commit(&self, config: &Config, commit_oid: Oid) -> Result<Oid> {
+        if let Some(revset) = &config.master_revset {
+            // The nearest ancestor of the commit that is on any of the trunks
+            let output = self.run_captured_with_args([
+                "log",
+                "--no-graph",
+                "-r",
+                &format!("latest(heads(::{} & ::({})))", commit_oid, revset),
+                "--template",
+                "commit_id",
+            ])?;
+            let commit_id = output.trim();
+            if commit_id.is_empty() {
+                return Err(Error::new(format!(
+                    "Commit {} is not based on any commit in spr.githubMasterBranch ({})",
+                    commit_oid, revset
+                )));
+            }
+            return Oid::from_str(commit_id).map_err(|e| {
+                Error::new(format!(
+                    "Failed to parse commit ID '{}' from jj output: {}",
+                    commit_id, e
+                ))
+            });
+        }
+
         // Find the merge base between the commit and master
         let master_oid = self.resolve_revision_to_commit_id(config.master_ref.local())?;
         let merge_base = self.git_repo.merge_base(commit_oid, master_oid)?;
         Ok(merge_base)
     }
 
+    /// With a jj revset in `spr.githubMasterBranch`, the branch on the remote
+    /// that the stack ending at `commit_oid` is based on: a bookmark on the
+    /// newest trunk commit at or after the one the stack branches off. Pull
+    /// Requests of the stack are opened against, and land on, that branch.
+    pub fn get_trunk_branch_for_commit(
+        &self,
+        config: &Config,
+        commit_oid: Oid,
+    ) -> Result<Option<String>> {
+        let Some(revset) = &config.master_revset else {
+            return Ok(None);
+        };
+        let base_oid = self.get_master_base_for_commit(config, commit_oid)?;
+        let output = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &format!(
+                "latest(({}) & {}:: & remote_bookmarks(remote=exact:\"{}\"))",
+                revset, base_oid, config.remote_name
+            ),
+            "--template",
+            "remote_bookmarks",
+        ])?;
+
+        match parse_remote_bookmark_names(&output, &config.remote_name)
+            .into_iter()
+            .next()
+        {
+            Some(branch) => Ok(Some(branch)),
+            None => Err(Error::new(format!(
+                "Commit {} is based on {}, which no branch on '{}' in \
+                 spr.githubMasterBranch ({}) points at or after. Pass \
+                 --base-branch to say which branch to use.",
+                commit_oid, base_oid, config.remote_name, revset
+            ))),
+        }
+    }
+
+    /// The commits that a command's `--revision`, `--all` and `--base`
+    /// options select, and whether that is a range rather than a single
+    /// revision. `--all` on its own selects the stack below the revision.
+    pub fn get_prepared_commits_for_options(
+        &self,
+        config: &Config,
+        revision: Option<&str>,
+        all: bool,
+        base: Option<&str>,
+    ) -> Result<(bool, Vec<PreparedCommit>)> {
+        let (use_range_mode, base_rev, target_rev, is_inclusive) =
+            crate::revision_utils::parse_revision_and_range(revision, all, base)?;
+
+        let prepared_commits = if !use_range_mode {
+            vec![self.get_prepared_commit_for_revision(config, &target_rev)?]
+        } else if crate::revision_utils::is_default_stack(revision, all, base) {
+            self.get_stack(config, &target_rev)?
+        } else {
+            self.get_prepared_commits_from_to(config, &base_rev, &target_rev, is_inclusive)?
+        };
+
+        Ok((use_range_mode, prepared_commits))
+    }
+
+    /// The stack ending at `revision`: the commits after the point where it
+    /// branched off master, up to and including `revision`. This is what
+    /// `--all` works on without `--base`, and what `spr status` shows.
+    pub fn get_stack(&self, config: &Config, revision: &str) -> Result<Vec<PreparedCommit>> {
+        let head_oid = self.resolve_revision_to_commit_id(revision)?;
+        let master_base_oid = self.get_master_base_for_commit(config, head_oid)?;
+        self.get_prepared_commits_from_to(
+            config,
+            &master_base_oid.to_string(),
+            &head_oid.to_string(),
+            false,
+        )
+    }
+
     pub fn get_prepared_commits_from_to(
         &self,
         config: &Config,
@@ -110,19 +240,49 @@ impl Jujutsu {
     }
 
     pub fn check_no_uncommitted_changes(&self) -> Result<()> {
-        let output = self.run_captured_with_args(["status"])?;
+        // The working copy is a commit of its own, which is clean if it
+        // doesn't change anything. Ask jj directly rather than parsing the
+        // wording of `jj status`, which changes between versions.
+        let empty = self.run_captured_with_args(["log", "--no-graph", "-r", "@", "-T", "empty"])?;
 
-        // Check if there are any changes
-        // Jujutsu reports "The working copy has no changes" when clean
-        if output.trim().is_empty()
-            || output.contains("No changes.")
-            || output.contains("The working copy has no changes")
-        {
+        if empty.trim() == "true" {
             Ok(())
         } else {
+            let stat = self.run_captured_with_args(["diff", "--stat", "-r", "@"])?;
             Err(Error::new(format!(
                 "You have uncommitted changes:\n{}",
-                output
+                stat
+            )))
+        }
+    }
+
+    /// Check that git sees the same commits as jj. In a colocated repository,
+    /// jj keeps git's HEAD at the parent of the working copy commit. If HEAD
+    /// is somewhere else, jj has not exported its latest state to git, and
+    /// spr would push what git has instead.
+    pub fn check_git_in_sync(&self) -> Result<()> {
+        let parents = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            "@",
+            "-T",
+            r#"parents.map(|c| c.commit_id()).join(" ")"#,
+        ])?;
+        let Some(jj_head) = parents.split_whitespace().next() else {
+            return Ok(());
+        };
+        let git_head = self.git_repo.head().ok().and_then(|head| head.target());
+
+        if git_head_matches(jj_head, git_head) {
+            Ok(())
+        } else {
+            Err(Error::new(format!(
+                "Git and jj disagree about the current commit (git's HEAD is {git}, \
+                 jj's is {jj:.12}). Run `jj git export` to update git, then try \
+                 again.",
+                git = git_head.map_or_else(|| "unset".to_string(), |oid| format!("{:.12}", oid)),
+                jj = jj_head,
             )))
         }
     }
@@ -160,6 +320,7 @@ impl Jujutsu {
         message: &str,
         tree_oid: Oid,
         parent_oids: &[Oid],
+        preserve_author_date: bool,
     ) -> Result<Oid> {
         let original_commit = self.git_repo.find_commit(original_commit_oid)?;
         let tree = self.git_repo.find_tree(tree_oid)?;
@@ -177,16 +338,27 @@ impl Jujutsu {
             String::from_utf8_lossy(original_commit.committer().email_bytes()).as_ref(),
         )?;
 
-        // The author signature should reference the same user as the original commit, but we set
-        // the timestamp to now, so this commit shows up in GitHub's timeline in the right place.
-        let author = git2::Signature::now(
-            String::from_utf8_lossy(original_commit.author().name_bytes()).as_ref(),
-            String::from_utf8_lossy(original_commit.author().email_bytes()).as_ref(),
-        )?;
+        // The author signature should reference the same user as the original commit. Unless
+        // asked to keep the original date, we set the timestamp to now, so this commit shows up
+        // in GitHub's timeline in the right place.
+        let original_author = original_commit.author();
+        let author_name = String::from_utf8_lossy(original_author.name_bytes());
+        let author_email = String::from_utf8_lossy(original_author.email_bytes());
+        let author = if preserve_author_date {
+            git2::Signature::new(&author_name, &author_email, &original_author.when())?
+        } else {
+            git2::Signature::now(&author_name, &author_email)?
+        };
 
-        Ok(self
-            .git_repo
-            .commit(None, &author, &committer, message, &tree, &parent_refs)?)
+        Ok(git2_ext::ops::commit(
+            &self.git_repo,
+            &author,
+            &committer,
+            message,
+            &tree,
+            &parent_refs,
+            self.commit_sign()?.as_dyn_sign()?,
+        )?)
     }
 
     pub fn cherrypick(&self, commit_oid: Oid, onto_oid: Oid) -> Result<git2::Index> {
@@ -215,11 +387,56 @@ impl Jujutsu {
         Ok(index.write_tree_to(&self.git_repo)?)
     }
 
-    pub fn rewrite_commit_messages(&self, commits: &mut [PreparedCommit]) -> Result<()> {
+    pub fn rewrite_commit_messages(
+        &self,
+        config: &Config,
+        commits: &mut [PreparedCommit],
+    ) -> Result<()> {
         if commits.is_empty() {
             return Ok(());
         }
 
+        // Describing rewrites commits, which drops their signatures unless
+        // jj's signing.behavior re-signs them. Note which commits are signed
+        // now, so that we can sign them again afterwards.
+        let signed_change_ids = if self.signing_configured() {
+            let signed: Vec<Oid> = commits
+                .iter()
+                .map(|commit| commit.oid)
+                .filter(|&oid| self.is_signed(oid))
+                .collect();
+            self.get_change_ids_for_range(&signed)?
+                .into_values()
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        self.describe_commits(config, commits)?;
+        self.sign_unsigned(&signed_change_ids)
+    }
+
+    fn describe_commits(&self, config: &Config, commits: &mut [PreparedCommit]) -> Result<()> {
+        // Every `jj describe` rewrites all descendants of the described
+        // commit. Describing all changed commits at once means descendants are
+        // only rewritten once, instead of once per changed ancestor.
+        let changed: Vec<&PreparedCommit> = commits.iter().filter(|c| c.message_changed).collect();
+        if config.minimize_rebase_on_describe
+            && changed.len() > 1
+            && self.describe_at_once(config, &changed)?
+        {
+            for prepared_commit in commits.iter_mut() {
+                prepared_commit.message_changed = false;
+            }
+            return Ok(());
+        }
+
+        // Look up all change IDs with a single `jj log`. Change IDs stay the
+        // same when a commit is described, so they are still valid after the
+        // first `jj describe` below rewrote the later commits.
+        let changed_oids: Vec<Oid> = changed.iter().map(|commit| commit.oid).collect();
+        let change_ids = self.get_change_ids_for_range(&changed_oids)?;
+
         // Use jj describe to update commit messages, but only for commits that actually changed
         for prepared_commit in commits.iter_mut() {
             // Only update commits whose messages were actually modified
@@ -227,19 +444,23 @@ impl Jujutsu {
                 continue;
             }
 
-            let new_message = build_commit_message(&prepared_commit.message);
+            let new_message = build_commit_message(&prepared_commit.message, config);
 
-            // Get the change ID for this commit
-            let change_id = self.get_change_id_for_commit(prepared_commit.oid)?;
+            let change_id = change_ids.get(&prepared_commit.oid).ok_or_else(|| {
+                Error::new(format!(
+                    "Could not find the change ID of commit {}",
+                    prepared_commit.short_id
+                ))
+            })?;
 
             // Update the commit message using jj describe
             let mut cmd = Command::new(&self.jj_bin);
-            cmd.args(["describe", "-r", &change_id, "-m", &new_message])
+            cmd.args(["describe", "-r", change_id, "-m", &new_message])
                 .current_dir(&self.repo_path)
                 .stdout(Stdio::piped())
                 .stderr(Stdio::piped());
 
-            let output = cmd.output()?;
+            let output = cmd.logged_output()?;
             if !output.status.success() {
                 return Err(Error::new(format!(
                     "Failed to update commit message: {}",
@@ -254,6 +475,264 @@ impl Jujutsu {
         Ok(())
     }
 
+    /// Whether jj is set up to sign commits.
+    fn signing_configured(&self) -> bool {
+        self.run_captured_with_args(["config", "get", "signing.backend"])
+            .is_ok_and(|backend| !matches!(backend.trim(), "" | "none"))
+    }
+
+    fn is_signed(&self, commit_oid: Oid) -> bool {
+        self.git_repo.extract_signature(&commit_oid, None).is_ok()
+    }
+
+    /// Sign those of the given changes whose current commit is not signed.
+    fn sign_unsigned(&self, change_ids: &[String]) -> Result<()> {
+        let mut unsigned = Vec::new();
+        for change_id in change_ids {
+            if !self.is_signed(self.resolve_revision_to_commit_id(change_id)?) {
+                unsigned.push(change_id.as_str());
+            }
+        }
+        if unsigned.is_empty() {
+            return Ok(());
+        }
+
+        self.run_captured_with_args(["sign", "-r", &unsigned.join(" | ")])
+            .map_err(|error| {
+                Error::new(format!(
+                    "Failed to sign rewritten commits again with `jj sign`: {}",
+                    error
+                ))
+            })?;
+        Ok(())
+    }
+
+    /// The paths changed by a commit, compared to its first parent.
+    pub fn get_commit_diff(&self, commit_oid: Oid) -> Result<Vec<PathBuf>> {
+        let commit = self.git_repo.find_commit(commit_oid)?;
+        let parent_tree = if commit.parent_count() > 0 {
+            Some(commit.parent(0)?.tree()?)
+        } else {
+            None
+        };
+        let diff =
+            self.git_repo
+                .diff_tree_to_tree(parent_tree.as_ref(), Some(&commit.tree()?), None)?;
+
+        Ok(diff
+            .deltas()
+            .filter_map(|delta| {
+                delta
+                    .new_file()
+                    .path()
+                    .or_else(|| delta.old_file().path())
+                    .map(Path::to_path_buf)
+            })
+            .collect())
+    }
+
+    /// Split a commit into a stack of commits with `jj split`. Each group of
+    /// paths is split off, in order, into a commit with the given message.
+    /// Whatever is left stays in the topmost commit, which keeps the original
+    /// message. Returns the commits of the new stack, bottom to top.
+    pub fn split_commit_by_paths(
+        &self,
+        commit_oid: Oid,
+        groups: &[(String, Vec<PathBuf>)],
+    ) -> Result<Vec<Oid>> {
+        let mut stack = Vec::new();
+        let mut remaining = commit_oid;
+
+        for (message, paths) in groups {
+            let parent_oid = self.git_repo.find_commit(remaining)?.parent_id(0)?;
+            let children_before = self.get_children(parent_oid)?;
+
+            let remaining_revision = remaining.to_string();
+            let filesets: Vec<String> = paths
+                .iter()
+                .map(|path| format!("root-file:{:?}", path.to_string_lossy()))
+                .collect();
+            let mut args = vec!["split", "-r", &remaining_revision, "-m", message];
+            args.extend(filesets.iter().map(String::as_str));
+            self.run_captured_with_args(args)?;
+
+            // The split-off part is the new child of the parent, and what's
+            // left is the child of that
+            let mut new_children = self
+                .get_children(parent_oid)?
+                .into_iter()
+                .filter(|oid| !children_before.contains(oid));
+            let split_off = match (new_children.next(), new_children.next()) {
+                (Some(oid), None) => oid,
+                _ => return Err(Error::new("Could not find the result of jj split")),
+            };
+            remaining = self
+                .get_children(split_off)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| Error::new("Could not find the result of jj split"))?;
+
+            stack.push(split_off);
+        }
+
+        stack.push(remaining);
+        Ok(stack)
+    }
+
+    fn get_children(&self, commit_oid: Oid) -> Result<HashSet<Oid>> {
+        let output = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &format!("children({})", commit_oid),
+            "--template",
+            "commit_id ++ \"\\n\"",
+        ])?;
+
+        output
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| {
+                Oid::from_str(line)
+                    .map_err(|e| Error::new(format!("Failed to parse commit ID '{}': {}", line, e)))
+            })
+            .collect()
+    }
+
+    /// Clean up after a commit has landed: rebase its descendants onto
+    /// `onto_oid` (if given) and abandon the landed commit. If no destination
+    /// is given, abandoning the commit makes Jujutsu rebase the descendants
+    /// onto its parent. Returns the number of rebased descendants.
+    pub fn abandon_landed_commit(&self, landed_oid: Oid, onto_oid: Option<Oid>) -> Result<usize> {
+        let landed_change_id = self.get_change_id_for_commit(landed_oid)?;
+
+        let children = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &format!("children({})", landed_change_id),
+            "--template",
+            "change_id ++ \"\\n\"",
+        ])?;
+        let children: Vec<&str> = children
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+
+        if let Some(onto_oid) = onto_oid {
+            let onto = onto_oid.to_string();
+
+            if !children.is_empty() {
+                let mut args = vec!["rebase"];
+                for child in &children {
+                    args.extend(["-s", child]);
+                }
+                args.extend(["-d", &onto]);
+                self.run_captured_with_args(args)?;
+            }
+
+            // If the landed commit is the working copy itself, start a new
+            // working copy on the destination instead of leaving it on the
+            // landed commit's parent.
+            let working_copy = self.run_captured_with_args([
+                "log",
+                "--no-graph",
+                "-r",
+                "@",
+                "--template",
+                "change_id",
+            ])?;
+            if working_copy.trim() == landed_change_id {
+                self.run_captured_with_args(["new", &onto])?;
+            }
+        }
+
+        self.run_captured_with_args(["abandon", &landed_change_id])?;
+
+        Ok(children.len())
+    }
+
+    /// Whether there is a local bookmark called `name`.
+    pub fn bookmark_exists(&self, name: &str) -> Result<bool> {
+        let output = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &format!("bookmarks(exact:\"{}\")", name),
+            "--template",
+            "commit_id",
+        ])?;
+        Ok(!output.trim().is_empty())
+    }
+
+    /// Point the bookmark `name` at a commit that was made with git2. An
+    /// existing bookmark is only moved if `force` is true. The commit is
+    /// unknown to Jujutsu until it has been imported, so the bookmark is set
+    /// as a git branch and imported from there.
+    pub fn set_bookmark(&self, name: &str, commit_oid: Oid, force: bool) -> Result<()> {
+        let commit = self.git_repo.find_commit(commit_oid)?;
+        self.git_repo
+            .branch(name, &commit, force)
+            .context(format!("could not set bookmark '{}'", name))?;
+        self.run_captured_with_args(["git", "import"])?;
+        Ok(())
+    }
+
+    /// Start a new working copy change on top of the given revision, like
+    /// `jj new`.
+    pub fn new_change_on(&self, revision: &str) -> Result<()> {
+        self.run_captured_with_args(["new", revision])?;
+        Ok(())
+    }
+
+    /// Update the messages of several commits with a single `jj describe`.
+    /// That takes the new messages through the editor, so we point the editor
+    /// at a file with all of them, in the format `jj describe` uses for
+    /// editing several descriptions. Returns false (without doing anything)
+    /// if that file can't be passed as an editor argument.
+    fn describe_at_once(&self, config: &Config, commits: &[&PreparedCommit]) -> Result<bool> {
+        let message_file = self.git_repo.path().join("SPR_DESCRIBE_MSG");
+        let message_file_path = message_file.to_string_lossy();
+        if message_file_path.contains(char::is_whitespace) {
+            return Ok(false);
+        }
+
+        let mut text = String::new();
+        let mut revisions = Vec::new();
+        for prepared_commit in commits {
+            let commit_id = prepared_commit.oid.to_string();
+            text.push_str(&format!(
+                "JJ: describe {} -------\n{}\n\n",
+                &commit_id[..12],
+                build_commit_message(&prepared_commit.message, config)
+            ));
+            revisions.push(commit_id);
+        }
+        std::fs::write(&message_file, text)?;
+
+        let output = Command::new(&self.jj_bin)
+            .arg("describe")
+            .args(&revisions)
+            .env("JJ_EDITOR", format!("cp {}", message_file_path))
+            .current_dir(&self.repo_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .logged_output();
+        let _ = std::fs::remove_file(&message_file);
+
+        let output = output?;
+        if !output.status.success() {
+            return Err(Error::new(format!(
+                "Failed to update commit messages: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(true)
+    }
+
     fn prepare_commit(&self, config: &Config, commit_oid: Oid) -> Result<PreparedCommit> {
         let commit = self.git_repo.find_commit(commit_oid)?;
         let short_id = format!("{:.7}", commit_oid);
@@ -266,23 +745,51 @@ impl Jujutsu {
         };
 
         let message_text = commit.message().unwrap_or("").to_string();
-        let message = parse_message(&message_text, MessageSection::Title);
+        let message = parse_message(&message_text, MessageSection::Title, config);
 
         let pull_request_number = message
             .get(&MessageSection::PullRequest)
             .and_then(|url| config.parse_pull_request_field(url));
 
+        let base_oid = if commit.parent_count() > 0
+            && self
+                .get_pull_request_number_for_commit(config, parent_oid)?
+                .is_some()
+        {
+            Some(parent_oid)
+        } else {
+            None
+        };
+
         Ok(PreparedCommit {
             oid: commit_oid,
             short_id,
             parent_oid,
+            base_oid,
             message,
             pull_request_number,
             message_changed: false,
         })
     }
 
-    fn resolve_revision_to_commit_id(&self, revision: &str) -> Result<Oid> {
+    /// The number of the Pull Request named in a commit's message, if any.
+    pub fn get_pull_request_number_for_commit(
+        &self,
+        config: &Config,
+        commit_oid: Oid,
+    ) -> Result<Option<u64>> {
+        let commit = self.git_repo.find_commit(commit_oid)?;
+        let message = parse_message(
+            commit.message().unwrap_or(""),
+            MessageSection::Title,
+            config,
+        );
+        Ok(message
+            .get(&MessageSection::PullRequest)
+            .and_then(|url| config.parse_pull_request_field(url)))
+    }
+
+    pub(crate) fn resolve_revision_to_commit_id(&self, revision: &str) -> Result<Oid> {
         let output = self.run_captured_with_args([
             "log",
             "--no-graph",
@@ -301,7 +808,7 @@ impl Jujutsu {
         })
     }
 
-    fn get_change_id_for_commit(&self, commit_oid: Oid) -> Result<String> {
+    pub(crate) fn get_change_id_for_commit(&self, commit_oid: Oid) -> Result<String> {
         // Get the change ID for a given commit OID
         let output = self.run_captured_with_args([
             "log",
@@ -315,6 +822,47 @@ impl Jujutsu {
         Ok(output.trim().to_string())
     }
 
+    /// The names of the local bookmarks pointing at a commit.
+    pub fn get_bookmarks_for_commit(&self, commit_oid: Oid) -> Result<Vec<String>> {
+        let output = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &commit_oid.to_string(),
+            "--template",
+            "bookmarks",
+        ])?;
+
+        Ok(parse_bookmark_names(&output))
+    }
+
+    /// The change IDs of the given commits, looked up with a single `jj log`
+    /// instead of one per commit.
+    pub(crate) fn get_change_ids_for_range(
+        &self,
+        commit_oids: &[Oid],
+    ) -> Result<HashMap<Oid, String>> {
+        if commit_oids.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let revset = commit_oids
+            .iter()
+            .map(|oid| oid.to_string())
+            .collect::<Vec<_>>()
+            .join(" | ");
+        let output = self.run_captured_with_args([
+            "log",
+            "--no-graph",
+            "-r",
+            &revset,
+            "--template",
+            r#"commit_id ++ " " ++ change_id ++ "\n""#,
+        ])?;
+
+        parse_change_ids(&output)
+    }
+
     fn run_captured_with_args<I, S>(&self, args: I) -> Result<String>
     where
         I: IntoIterator<Item = S>,
@@ -325,10 +873,12 @@ impl Jujutsu {
         command.current_dir(&self.repo_path);
         command.stdout(Stdio::piped());
 
+        debug_command(&command);
         let child = command.spawn().context("jj failed to spawn".to_string())?;
         let output = child
             .wait_with_output()
             .context("failed to wait for jj to exit".to_string())?;
+        debug_exit(&command, output.status);
 
         if output.status.success() {
             let output = String::from_utf8(output.stdout)
@@ -347,6 +897,66 @@ impl Jujutsu {
     }
 }
 
+/// Whether git's HEAD is `jj_head`, the first parent of jj's working copy
+/// commit. On jj's root commit, git has no HEAD.
+fn git_head_matches(jj_head: &str, git_head: Option<Oid>) -> bool {
+    match git_head {
+        Some(oid) => oid.to_string() == jj_head,
+        None => jj_head.chars().all(|c| c == '0'),
+    }
+}
+
+/// Parse the `<commit id> <change id>` lines printed by
+/// [`Jujutsu::get_change_ids_for_range`].
+fn parse_change_ids(output: &str) -> Result<HashMap<Oid, String>> {
+    output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (commit_id, change_id) = line
+                .trim()
+                .split_once(' ')
+                .ok_or_else(|| Error::new(format!("Unexpected jj log output: '{}'", line)))?;
+            let oid = Oid::from_str(commit_id).map_err(|e| {
+                Error::new(format!(
+                    "Failed to parse commit ID '{}' from jj output: {}",
+                    commit_id, e
+                ))
+            })?;
+            Ok((oid, change_id.to_string()))
+        })
+        .collect()
+}
+
+/// Parse the output of the `bookmarks` template: local bookmark names, with a
+/// `*` when they differ from the remote and `?` when they are conflicted, and
+/// remote bookmarks as `name@remote`, which are left out.
+fn parse_bookmark_names(output: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in output.split_whitespace() {
+        let name = name.trim_end_matches(['*', '?']);
+        if !name.is_empty() && !name.contains('@') && !names.iter().any(|n| n == name) {
+            names.push(name.to_string());
+        }
+    }
+    names
+}
+
+/// The names of the bookmarks on `remote` in the output of the
+/// `remote_bookmarks` template, which lists them as `name@remote`.
+fn parse_remote_bookmark_names(output: &str, remote: &str) -> Vec<String> {
+    output
+        .split_whitespace()
+        .filter_map(|name| {
+            name.trim_end_matches(['*', '?'])
+                .strip_suffix(remote)?
+                .strip_suffix('@')
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+        })
+        .collect()
+}
+
 fn get_jj_bin() -> PathBuf {
     std::env::var_os("JJ").map_or_else(|| "jj".into(), |v| v.into())
 }
@@ -354,24 +964,10 @@ fn get_jj_bin() -> PathBuf {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::config::test_config;
     use std::{fs, path::Path};
     use tempfile::TempDir;
 
-    fn create_test_config() -> Config {
-        Config::new(
-            "test_owner".into(),
-            "test_repo".into(),
-            "origin".into(),
-            "main".into(),
-            "spr/test/".into(),
-            false,
-            false,
-            false,
-            false,
-            false
-        )
-    }
-
     fn create_jujutsu_test_repo() -> (TempDir, PathBuf) {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
         let repo_path = temp_dir.path().to_path_buf();
@@ -449,7 +1045,7 @@ mod tests {
     #[test]
     fn test_revision_resolution() {
         let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-        let config = create_test_config();
+        let config = test_config();
 
         // Create some commits
         let _commit1 = create_jujutsu_commit(&repo_path, "First commit", "content1");
@@ -478,7 +1074,7 @@ mod tests {
     #[test]
     fn test_commit_range() {
         let (_temp_dir, repo_path) = create_jujutsu_test_repo();
-        let config = create_test_config();
+        let config = test_config();
 
         // Create multiple commits
         let _commit1 = create_jujutsu_commit(&repo_path, "First commit", "content1");
@@ -537,6 +1133,178 @@ mod tests {
             "Status check should pass for clean repo: {:?}",
             result.err()
         );
+
+        // A change in the working copy commit counts as uncommitted
+        fs::write(repo_path.join("dirty.txt"), "dirty").expect("Failed to write file");
+        assert!(jj.check_no_uncommitted_changes().is_err());
+    }
+
+    #[test]
+    fn test_rewrite_messages_at_once_preserves_descendant_change_ids() {
+        let (_temp_dir, repo_path) = create_jujutsu_test_repo();
+        let mut config = test_config();
+        config.minimize_rebase_on_describe = true;
+
+        let _first = create_jujutsu_commit(&repo_path, "First commit", "content1");
+        let _second = create_jujutsu_commit(&repo_path, "Second commit", "content2");
+        let third = create_jujutsu_commit(&repo_path, "Third commit", "content3");
+
+        let git_repo = git2::Repository::open(&repo_path).expect("Failed to open git repository");
+        let jj = Jujutsu::new(git_repo).expect("Failed to create Jujutsu instance");
+
+        // Change the messages of the two ancestors of the third commit
+        let mut commits = jj
+            .get_prepared_commits_from_to(&config, "@----", "@--", false)
+            .expect("Failed to get commit range");
+        assert_eq!(commits.len(), 2);
+        for (commit, title) in commits.iter_mut().zip(["First, edited", "Second, edited"]) {
+            commit
+                .message
+                .insert(MessageSection::Title, title.to_string());
+            commit.message_changed = true;
+        }
+
+        jj.rewrite_commit_messages(&config, &mut commits)
+            .expect("Failed to rewrite commit messages");
+        assert!(commits.iter().all(|commit| !commit.message_changed));
+
+        let descriptions = jj
+            .run_captured_with_args([
+                "log",
+                "--no-graph",
+                "-r",
+                "@----..@-",
+                "--template",
+                "change_id ++ \" \" ++ description.first_line() ++ \"\\n\"",
+            ])
+            .unwrap();
+        let lines: Vec<&str> = descriptions.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // The descendant was rebased, but is still the same change
+        assert_eq!(lines[0], format!("{} Third commit", third));
+        assert!(lines[1].ends_with(" Second, edited"));
+        assert!(lines[2].ends_with(" First, edited"));
+    }
+
+    #[test]
+    fn test_get_commit_diff() {
+        // Only uses git, so a plain git repository with a .jj directory will do
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let repo = git2::Repository::init(temp_dir.path()).expect("Failed to init git repo");
+        fs::create_dir(temp_dir.path().join(".jj")).unwrap();
+
+        let signature = git2::Signature::now("Test User", "test@example.com").unwrap();
+        let commit_with = |files: &[(&str, &str)], parents: &[&git2::Commit]| {
+            let mut builder = repo.treebuilder(None).unwrap();
+            for (name, content) in files {
+                let blob = repo.blob(content.as_bytes()).unwrap();
+                builder.insert(name, blob, 0o100644).unwrap();
+            }
+            let tree = repo.find_tree(builder.write().unwrap()).unwrap();
+            repo.commit(None, &signature, &signature, "commit", &tree, parents)
+                .unwrap()
+        };
+
+        let parent = commit_with(&[("kept", "1"), ("changed", "1"), ("removed", "1")], &[]);
+        let parent_commit = repo.find_commit(parent).unwrap();
+        let child = commit_with(
+            &[("kept", "1"), ("changed", "2"), ("added", "1")],
+            &[&parent_commit],
+        );
+
+        let jj = Jujutsu::new(git2::Repository::open(temp_dir.path()).unwrap()).unwrap();
+        let mut paths = jj.get_commit_diff(child).unwrap();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                PathBuf::from("added"),
+                PathBuf::from("changed"),
+                PathBuf::from("removed")
+            ]
+        );
+
+        // A root commit is compared to the empty tree
+        assert_eq!(jj.get_commit_diff(parent).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_abandon_landed_commit_rebases_descendants() {
+        let (_temp_dir, repo_path) = create_jujutsu_test_repo();
+
+        let landed = create_jujutsu_commit(&repo_path, "Landed commit", "landed");
+        let child = create_jujutsu_commit(&repo_path, "Child commit", "child");
+
+        // Simulate master moving on independently of the local stack
+        let output = std::process::Command::new("jj")
+            .args(["new", "root()"])
+            .current_dir(&repo_path)
+            .output()
+            .expect("Failed to run jj new");
+        assert!(output.status.success());
+        let master = create_jujutsu_commit(&repo_path, "Master commit", "master");
+
+        let git_repo = git2::Repository::open(&repo_path).expect("Failed to open git repository");
+        let jj = Jujutsu::new(git_repo).expect("Failed to create Jujutsu instance");
+
+        let landed_oid = jj.resolve_revision_to_commit_id(&landed).unwrap();
+        let master_oid = jj.resolve_revision_to_commit_id(&master).unwrap();
+
+        let rebased = jj
+            .abandon_landed_commit(landed_oid, Some(master_oid))
+            .expect("Failed to clean up landed commit");
+        assert_eq!(rebased, 1);
+
+        let parent = jj
+            .run_captured_with_args([
+                "log",
+                "--no-graph",
+                "-r",
+                &format!("parents({})", child),
+                "--template",
+                "change_id",
+            ])
+            .unwrap();
+        assert_eq!(parent.trim(), master);
+
+        let visible = jj
+            .run_captured_with_args([
+                "log",
+                "--no-graph",
+                "-r",
+                "all()",
+                "--template",
+                "change_id ++ \"\\n\"",
+            ])
+            .unwrap();
+        assert!(!visible.lines().any(|line| line.trim() == landed));
+    }
+
+    #[test]
+    fn test_abandon_landed_commit_without_descendants() {
+        let (_temp_dir, repo_path) = create_jujutsu_test_repo();
+
+        let landed = create_jujutsu_commit(&repo_path, "Landed commit", "landed");
+
+        let git_repo = git2::Repository::open(&repo_path).expect("Failed to open git repository");
+        let jj = Jujutsu::new(git_repo).expect("Failed to create Jujutsu instance");
+
+        // The empty working copy on top of the landed commit gets rebased
+        let landed_oid = jj.resolve_revision_to_commit_id(&landed).unwrap();
+        let root_oid = jj.resolve_revision_to_commit_id("root()").unwrap();
+        assert_eq!(
+            jj.abandon_landed_commit(landed_oid, Some(root_oid))
+                .unwrap(),
+            1
+        );
+
+        // Landing the working copy itself leaves nothing to rebase
+        let working_copy_oid = jj.resolve_revision_to_commit_id("@").unwrap();
+        assert_eq!(
+            jj.abandon_landed_commit(working_copy_oid, Some(root_oid))
+                .unwrap(),
+            0
+        );
     }
 
     #[test]
@@ -580,6 +1348,7 @@ mod tests {
                 "Derived commit message",
                 tree_oid,
                 &parent_oids,
+                false,
             )
             .expect("Failed to create derived commit");
 
@@ -605,4 +1374,139 @@ mod tests {
             "Derived commit committer timestamp should be newer than original"
         );
     }
+
+    #[test]
+    fn test_create_derived_commit_preserves_author_date() {
+        let (_temp_dir, repo_path) = create_jujutsu_test_repo();
+        create_jujutsu_commit(&repo_path, "Original commit", "original content");
+
+        let git_repo = git2::Repository::open(&repo_path).expect("Failed to open git repository");
+        let jj = Jujutsu::new(git_repo).expect("Failed to create Jujutsu instance");
+
+        let original_commit_oid = jj
+            .resolve_revision_to_commit_id("@-")
+            .expect("Failed to resolve @- revision");
+        let original_commit = jj
+            .git_repo
+            .find_commit(original_commit_oid)
+            .expect("Failed to find original commit");
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+
+        let derived_commit_oid = jj
+            .create_derived_commit(
+                original_commit_oid,
+                "Derived commit message",
+                original_commit.tree_id(),
+                &original_commit.parent_ids().collect::<Vec<_>>(),
+                true,
+            )
+            .expect("Failed to create derived commit");
+        let derived_commit = jj
+            .git_repo
+            .find_commit(derived_commit_oid)
+            .expect("Failed to find derived commit");
+
+        assert_eq!(
+            derived_commit.author().when(),
+            original_commit.author().when()
+        );
+        assert_eq!(
+            derived_commit.author().name(),
+            original_commit.author().name()
+        );
+        assert!(
+            derived_commit.committer().when().seconds()
+                > original_commit.committer().when().seconds(),
+            "Derived commit committer timestamp should be newer than original"
+        );
+    }
+
+    #[test]
+    fn test_rewritten_commits_stay_signed() {
+        let (_temp_dir, repo_path) = create_jujutsu_test_repo();
+        let config = test_config();
+
+        let jj_config = |key: &str, value: &str| {
+            let output = std::process::Command::new("jj")
+                .args(["config", "set", "--repo", key, value])
+                .current_dir(&repo_path)
+                .output()
+                .expect("Failed to run jj config set");
+            assert!(output.status.success());
+        };
+
+        // Sign the commits when they are made, then stop signing on rewrite
+        jj_config("signing.backend", "test");
+        jj_config("signing.behavior", "own");
+        create_jujutsu_commit(&repo_path, "First commit", "content1");
+        create_jujutsu_commit(&repo_path, "Second commit", "content2");
+        jj_config("signing.behavior", "drop");
+
+        let git_repo = git2::Repository::open(&repo_path).expect("Failed to open git repository");
+        let jj = Jujutsu::new(git_repo).expect("Failed to create Jujutsu instance");
+
+        let mut commits = jj
+            .get_prepared_commits_from_to(&config, "@---", "@-", false)
+            .unwrap();
+        assert_eq!(commits.len(), 2);
+        assert!(commits.iter().all(|commit| jj.is_signed(commit.oid)));
+
+        commits[0]
+            .message
+            .insert(MessageSection::Summary, "New summary".into());
+        commits[0].message_changed = true;
+        jj.rewrite_commit_messages(&config, &mut commits).unwrap();
+
+        for revision in ["@--", "@-"] {
+            let oid = jj.resolve_revision_to_commit_id(revision).unwrap();
+            assert!(jj.is_signed(oid), "{} is not signed", revision);
+        }
+    }
+
+    #[test]
+    fn test_git_head_matches() {
+        let head = "0123456789abcdef0123456789abcdef01234567";
+        let oid = Oid::from_str(head).unwrap();
+        let root = "0000000000000000000000000000000000000000";
+
+        assert!(git_head_matches(head, Some(oid)));
+        assert!(!git_head_matches(root, Some(oid)));
+        assert!(git_head_matches(root, None));
+        assert!(!git_head_matches(head, None));
+    }
+
+    #[test]
+    fn test_parse_remote_bookmark_names() {
+        assert_eq!(
+            parse_remote_bookmark_names("main@origin main@upstream release@origin?\n", "origin"),
+            vec!["main", "release"]
+        );
+        assert!(parse_remote_bookmark_names("main@upstream", "origin").is_empty());
+        assert!(parse_remote_bookmark_names("", "origin").is_empty());
+    }
+
+    #[test]
+    fn test_parse_bookmark_names() {
+        assert_eq!(
+            parse_bookmark_names("feature* spr/fix-typo fix@upstream conflicted??\n"),
+            vec!["feature", "spr/fix-typo", "conflicted"]
+        );
+        assert!(parse_bookmark_names("").is_empty());
+    }
+
+    #[test]
+    fn test_parse_change_ids() {
+        let first = "0123456789abcdef0123456789abcdef01234567";
+        let second = "89abcdef0123456789abcdef0123456789abcdef";
+        let output = format!("{} qpvuntsm\n{} kkmpptxz\n", first, second);
+
+        let change_ids = parse_change_ids(&output).unwrap();
+        assert_eq!(change_ids.len(), 2);
+        assert_eq!(change_ids[&Oid::from_str(first).unwrap()], "qpvuntsm");
+        assert_eq!(change_ids[&Oid::from_str(second).unwrap()], "kkmpptxz");
+
+        assert!(parse_change_ids("").unwrap().is_empty());
+        assert!(parse_change_ids("not-a-commit-id qpvuntsm").is_err());
+    }
 }
@@ -5,6 +5,7 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+pub mod assets;
 pub mod commands;
 pub mod config;
 pub mod error;
@@ -14,4 +15,5 @@ pub mod jj;
 pub mod message;
 pub mod output;
 pub mod revision_utils;
+pub mod store;
 pub mod utils;
@@ -12,11 +12,18 @@
 use clap::{Parser, Subcommand};
 use jj_spr::{
     commands,
-    config::{get_auth_token, get_config_bool, get_config_value},
+    config::{
+        find_directory_config, get_auth_token, get_auth_token_from_env, get_config_bool,
+        get_config_value, parse_config_bool, parse_label_list, parse_land_message_format,
+        parse_merge_method, read_directory_config,
+    },
     error::{Error, Result, ResultExt},
-    output::output,
+    output::{Outcome, OutputFormat, output, write_next_steps},
+};
+use std::{
+    collections::HashMap,
+    num::{NonZeroU32, NonZeroUsize},
 };
-use reqwest::{self, header};
 
 #[derive(Parser, Debug)]
 #[clap(
@@ -25,8 +32,9 @@ use reqwest::{self, header};
     about = "Jujutsu subcommand: Submit pull requests for individual, amendable, rebaseable commits to GitHub"
 )]
 pub struct Cli {
-    /// GitHub personal access token (if not given taken from jj config
-    /// spr.githubAuthToken)
+    /// GitHub personal access token (if not given taken from the environment
+    /// variables GH_TOKEN or GITHUB_TOKEN, then from config
+    /// spr.githubAuthToken, then from `gh auth token`)
     #[clap(long)]
     github_auth_token: Option<String>,
 
@@ -41,6 +49,33 @@ pub struct Cli {
     #[clap(long)]
     branch_prefix: Option<String>,
 
+    /// Name of the git remote that corresponds to GitHub, for this run only
+    /// (if not given taken from config spr.githubRemoteName, defaulting to
+    /// 'origin')
+    #[clap(long)]
+    remote_name: Option<String>,
+
+    /// Branch that Pull Requests are merged into, for this run only (if not
+    /// given taken from config spr.githubMasterBranch, defaulting to 'main')
+    #[clap(long)]
+    master_branch: Option<String>,
+
+    /// Only print warnings and errors: no progress lines and no suggestions
+    /// for what to do next (also set with config spr.quiet)
+    #[clap(long, short = 'q', global = true)]
+    quiet: bool,
+
+    /// Log every jj and git command spr runs, with its exit status, and the
+    /// requests spr sends to GitHub and their responses (also enabled by
+    /// RUST_LOG=debug)
+    #[clap(long, short = 'v', global = true)]
+    verbose: bool,
+
+    /// Print progress as lines for people to read, or as one JSON object per
+    /// line (with "step" and "message") for tools
+    #[clap(long, value_enum, global = true, default_value_t = OutputFormat::Human)]
+    output_format: OutputFormat,
+
     #[clap(subcommand)]
     command: Commands,
 }
@@ -49,11 +84,11 @@ pub struct Cli {
 enum Commands {
     /// Interactive assistant for configuring spr in a local GitHub-backed Git
     /// repository
-    Init,
+    Init(commands::init::InitOptions),
 
     /// Create a new or update an existing Pull Request on GitHub from the
     /// current HEAD commit
-    Diff(commands::diff::DiffOptions),
+    Diff(Box<commands::diff::DiffOptions>),
 
     /// Reformat commit message
     Format(commands::format::FormatOptions),
@@ -65,26 +100,80 @@ enum Commands {
     Amend(commands::amend::AmendOptions),
 
     /// List open Pull Requests on GitHub and their review decision
-    List,
+    List(commands::list::ListOptions),
 
     /// Create a new branch with the contents of an existing Pull Request
     Patch(commands::patch::PatchOptions),
 
     /// Close a Pull request
     Close(commands::close::CloseOptions),
+
+    /// Delete branches on GitHub that spr created for Pull Requests that
+    /// have since been closed or merged
+    Cleanup(commands::cleanup::CleanupOptions),
+
+    /// Reopen a closed Pull Request
+    Reopen(commands::reopen::ReopenOptions),
+
+    /// Show the commits between master and the working copy, with the
+    /// review and CI state of their Pull Requests
+    Status,
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum OptionsError {
     #[error("GitHub repository must be given as 'OWNER/REPO', but given value was '{0}'")]
     InvalidRepository(String),
+    #[error(
+        "spr.landMergeMethod must be one of 'squash', 'merge' or 'rebase', but given value was '{0}'"
+    )]
+    InvalidMergeMethod(String),
+    #[error(
+        "spr.landMessageFormat must be one of 'full', 'title-only' or 'title-and-link', but given value was '{0}'"
+    )]
+    InvalidLandMessageFormat(String),
+    #[error("{0} must be a positive number, but given value was '{1}'")]
+    InvalidNumber(&'static str, String),
+}
+
+/// A setting given on the command line, or else from config, or else the
+/// default.
+fn cli_or_config(
+    cli_value: Option<String>,
+    key: &str,
+    default: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> String {
+    cli_value
+        .or_else(|| lookup(key))
+        .unwrap_or_else(|| default.to_string())
+}
+
+/// Read a numeric config value, failing if it is set but not a valid number.
+fn get_config_number<T: std::str::FromStr>(
+    key: &'static str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Option<T>> {
+    lookup(key)
+        .map(|value| {
+            value
+                .parse()
+                .map_err(|_| OptionsError::InvalidNumber(key, value).into())
+        })
+        .transpose()
 }
 
 pub async fn spr() -> Result<()> {
     let cli = Cli::parse();
+    jj_spr::output::set_output_format(cli.output_format);
+    jj_spr::output::set_verbose(
+        cli.verbose
+            || std::env::var("RUST_LOG")
+                .is_ok_and(|value| jj_spr::output::rust_log_enables_debug(&value)),
+    );
 
-    if let Commands::Init = cli.command {
-        return commands::init::init().await;
+    if let Commands::Init(opts) = cli.command {
+        return commands::init::init(opts).await;
     }
 
     // Discover the Jujutsu repository and get the colocated Git repo
@@ -106,15 +195,39 @@ pub async fn spr() -> Result<()> {
 
     let git_config = repo.config()?;
 
+    // Settings in the nearest .spr.toml override those in jj and git config
+    let directory_config = match find_directory_config(&current_dir, &repo_path) {
+        Some(path) => {
+            read_directory_config(&path).context(format!("could not read {}", path.display()))?
+        }
+        None => HashMap::new(),
+    };
+    let config_value = |key: &str| {
+        directory_config
+            .get(&key.to_lowercase())
+            .cloned()
+            .or_else(|| get_config_value(key, &git_config))
+    };
+    let config_bool = |key: &str| {
+        directory_config
+            .get(&key.to_lowercase())
+            .and_then(|value| parse_config_bool(value))
+            .or_else(|| get_config_bool(key, &git_config))
+    };
+
+    let quiet = cli.quiet || config_bool("spr.quiet").unwrap_or(false);
+    jj_spr::output::set_quiet(quiet);
+
     // Try to get config from jj first, fall back to git config
     let github_repository = match cli.github_repository {
         Some(v) => Ok(v),
         None => {
             // Try jj config first
-            if let Ok(output) = std::process::Command::new("jj")
-                .args(["config", "get", "spr.githubRepository"])
-                .output()
-            {
+            let mut command = std::process::Command::new("jj");
+            command.args(["config", "get", "spr.githubRepository"]);
+            jj_spr::output::debug_command(&command);
+            if let Ok(output) = command.output() {
+                jj_spr::output::debug_exit(&command, output.status);
                 if output.status.success() {
                     Ok(String::from_utf8(output.stdout)?.trim().to_string())
                 } else {
@@ -126,44 +239,131 @@ pub async fn spr() -> Result<()> {
         }
     }?;
 
-    let (github_owner, github_repo) = {
-        let captures = lazy_regex::regex!(r#"^([\w\-\.]+)/([\w\-\.]+)$"#)
-            .captures(&github_repository)
+    let (github_owner, github_repo) =
+        jj_spr::config::parse_github_repository(&github_repository)
             .ok_or_else(|| OptionsError::InvalidRepository(github_repository.clone()))?;
-        (
-            captures.get(1).unwrap().as_str().to_string(),
-            captures.get(2).unwrap().as_str().to_string(),
-        )
-    };
 
-    let github_remote_name = get_config_value("spr.githubRemoteName", &git_config)
-        .unwrap_or_else(|| "origin".to_string());
-    let github_master_branch = get_config_value("spr.githubMasterBranch", &git_config)
-        .unwrap_or_else(|| "main".to_string());
-    let branch_prefix = get_config_value("spr.branchPrefix", &git_config)
+    let github_remote_name = cli_or_config(
+        cli.remote_name,
+        "spr.githubRemoteName",
+        "origin",
+        config_value,
+    );
+    let github_master_branch = cli_or_config(
+        cli.master_branch,
+        "spr.githubMasterBranch",
+        "main",
+        config_value,
+    );
+    let branch_prefix = config_value("spr.branchPrefix")
         .ok_or_else(|| Error::new("spr.branchPrefix must be configured".to_string()))?;
-    let require_approval = get_config_bool("spr.requireApproval", &git_config).unwrap_or(false);
-    let require_test_plan = get_config_bool("spr.requireTestPlan", &git_config).unwrap_or(true);
-    let add_reviewed_by = get_config_bool("spr.addReviewedBy", &git_config).unwrap_or(false);
-    let add_spr_banner_commit =
-        get_config_bool("spr.addSprBannerComment", &git_config).unwrap_or(true);
-    let add_skip_ci_comment = get_config_bool("spr.addSkipCiComment", &git_config).unwrap_or(false);
-
-    let config = jj_spr::config::Config::new(
-        github_owner,
-        github_repo,
-        github_remote_name,
-        github_master_branch,
-        branch_prefix,
+    let require_approval = config_bool("spr.requireApproval").unwrap_or(false);
+    let require_test_plan = config_bool("spr.requireTestPlan").unwrap_or(true);
+    let add_reviewed_by = config_bool("spr.addReviewedBy").unwrap_or(false);
+    let add_spr_banner_comment = config_bool("spr.addSprBannerComment").unwrap_or(true);
+    let add_skip_ci_comment = config_bool("spr.addSkipCiComment").unwrap_or(false);
+    let re_request_on_update = config_bool("spr.reRequestOnUpdate").unwrap_or(false);
+    let merge_method = match config_value("spr.landMergeMethod") {
+        Some(value) => {
+            parse_merge_method(&value).ok_or(OptionsError::InvalidMergeMethod(value.clone()))?
+        }
+        None => octocrab::params::pulls::MergeMethod::Squash,
+    };
+    let record_provenance = config_bool("spr.recordProvenance").unwrap_or(false);
+    let minimize_rebase_on_describe = config_bool("spr.minimizeRebaseOnDescribe").unwrap_or(false);
+    let keep_branch_after_land = config_bool("spr.keepBranchAfterLand").unwrap_or(false);
+    let upload_assets = config_bool("spr.uploadAssets").unwrap_or(false);
+    let land_poll_attempts = get_config_number::<NonZeroU32>("spr.landPollAttempts", config_value)?
+        .map_or(10, NonZeroU32::get);
+    let land_poll_interval_ms =
+        get_config_number("spr.landPollIntervalMs", config_value)?.unwrap_or(1000);
+    let abandon_after_land = config_bool("spr.abandonAfterLand").unwrap_or(true);
+    let github_api_url = jj_spr::github::github_endpoint(
+        config_value("spr.githubApiHost"),
+        jj_spr::github::DEFAULT_API_URL,
+    );
+    let github_graphql_url = jj_spr::github::github_endpoint(
+        config_value("spr.githubGraphqlHost"),
+        jj_spr::github::DEFAULT_GRAPHQL_URL,
+    );
+    let draft_label = config_value("spr.draftLabel").filter(|label| !label.is_empty());
+    let default_labels = config_value("spr.defaultLabels")
+        .map(|labels| parse_label_list(&labels))
+        .unwrap_or_default();
+    let authoritative_labels = config_bool("spr.authoritativeLabels").unwrap_or(false);
+    let push_no_verify = config_bool("spr.pushNoVerify").unwrap_or(true);
+    let test_plan_section_name = config_value("spr.testPlanSectionName")
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| "Test Plan".to_string());
+    let github_request_attempts =
+        get_config_number::<NonZeroU32>("spr.githubRequestAttempts", config_value)?
+            .map_or(3, NonZeroU32::get);
+    let open_after_diff = config_bool("spr.openAfterDiff").unwrap_or(false);
+    let require_summary = config_bool("spr.requireSummary").unwrap_or(true);
+    let branch_name_template = config_value("spr.branchNameTemplate");
+    if let Some(template) = &branch_name_template {
+        jj_spr::config::validate_branch_name_template(template)?;
+    }
+    let use_pull_request_template = config_bool("spr.usePullRequestTemplate").unwrap_or(false);
+    let land_message_format = match config_value("spr.landMessageFormat") {
+        Some(value) => parse_land_message_format(&value)
+            .ok_or(OptionsError::InvalidLandMessageFormat(value.clone()))?,
+        None => jj_spr::config::LandMessageFormat::Full,
+    };
+    let max_title_length = get_config_number::<NonZeroUsize>("spr.maxTitleLength", config_value)?
+        .map_or(72, NonZeroUsize::get);
+    let require_title_length = config_bool("spr.requireTitleLength").unwrap_or(false);
+    let test_plan_exempt_paths = match config_value("spr.testPlanExemptPaths") {
+        Some(value) => jj_spr::config::parse_path_globs(&value)?,
+        None => Vec::new(),
+    };
+    let prefer_existing_bookmark = config_bool("spr.preferExistingBookmark").unwrap_or(false);
+
+    let config = jj_spr::config::Config {
         require_approval,
         require_test_plan,
         add_reviewed_by,
-        add_spr_banner_commit,
+        add_spr_banner_comment,
         add_skip_ci_comment,
-    );
+        re_request_on_update,
+        merge_method,
+        record_provenance,
+        minimize_rebase_on_describe,
+        keep_branch_after_land,
+        upload_assets,
+        land_poll_attempts,
+        land_poll_interval_ms,
+        abandon_after_land,
+        github_api_url,
+        github_graphql_url,
+        draft_label,
+        default_labels,
+        authoritative_labels,
+        push_no_verify,
+        test_plan_section_name,
+        github_request_attempts,
+        open_after_diff,
+        require_summary,
+        branch_name_template,
+        use_pull_request_template,
+        land_message_format,
+        max_title_length,
+        require_title_length,
+        test_plan_exempt_paths,
+        prefer_existing_bookmark,
+        ..jj_spr::config::Config::new(
+            github_owner,
+            github_repo,
+            github_remote_name,
+            github_master_branch,
+            branch_prefix,
+        )
+    };
 
     let jj = jj_spr::jj::Jujutsu::new(repo)
-        .context("could not initialize Jujutsu backend".to_owned())?;
+        .context("could not initialize Jujutsu backend".to_owned())?
+        .with_commit_signing(config_bool("spr.signCommits"));
 
     let repo_git = git2::Repository::discover(std::env::current_dir()?)?;
     let git = jj_spr::git::Git::new(repo_git)
@@ -173,24 +373,44 @@ pub async fn spr() -> Result<()> {
         return commands::format::format(opts, &jj, &config).await;
     }
 
+    // Make sure the remote is the configured GitHub repository before talking
+    // to either of them. spr.githubRemoteUrl stands in for the remote's URL.
+    let remote_url = config_value("spr.githubRemoteUrl").or_else(|| {
+        jj.git_repo
+            .find_remote(&config.remote_name)
+            .ok()
+            .and_then(|remote| remote.url().map(String::from))
+    });
+    jj_spr::config::validate_remote_url(
+        &config.remote_name,
+        remote_url.as_deref(),
+        &config.owner,
+        &config.repo,
+    )?;
+
     let github_auth_token = match cli.github_auth_token {
         Some(v) => v,
-        None => get_auth_token(&git_config)
-            .ok_or_else(|| Error::new("GitHub auth token must be configured".to_string()))?,
+        None => get_auth_token_from_env()
+            .or_else(|| get_auth_token(&git_config))
+            .ok_or_else(|| {
+                Error::new(
+                    "GitHub auth token must be configured (set spr.githubAuthToken, or log in \
+                     with `gh auth login`)"
+                        .to_string(),
+                )
+            })?,
     };
 
-    octocrab::initialise(octocrab::Octocrab::builder().personal_token(github_auth_token.clone()))?;
+    let github_api_version = config_value("spr.githubApiVersion")
+        .unwrap_or_else(|| jj_spr::github::DEFAULT_API_VERSION.to_string());
 
-    let mut headers = header::HeaderMap::new();
-    headers.insert(header::ACCEPT, "application/json".parse()?);
-    headers.insert(
-        header::USER_AGENT,
-        format!("spr/{}", env!("CARGO_PKG_VERSION")).try_into()?,
-    );
-    headers.insert(
-        header::AUTHORIZATION,
-        format!("Bearer {}", github_auth_token).parse()?,
-    );
+    octocrab::initialise(jj_spr::github::octocrab_builder(
+        github_auth_token.clone(),
+        &github_api_version,
+        &config.github_api_url,
+    )?)?;
+
+    let headers = jj_spr::github::default_headers(&github_auth_token, &github_api_version)?;
 
     let graphql_client = reqwest::Client::builder()
         .default_headers(headers)
@@ -198,18 +418,59 @@ pub async fn spr() -> Result<()> {
 
     let mut gh = jj_spr::github::GitHub::new(config.clone(), graphql_client.clone());
 
-    match cli.command {
-        Commands::Diff(opts) => commands::diff::diff(opts, &jj, &mut gh, &config).await?,
-        Commands::Land(opts) => commands::land::land(opts, &git, &jj, &mut gh, &config).await?,
-        Commands::Amend(opts) => commands::amend::amend(opts, &jj, &mut gh, &config).await?,
-        Commands::List => commands::list::list(graphql_client, &config).await?,
-        Commands::Patch(opts) => commands::patch::patch(opts, &jj, &mut gh, &config).await?,
-        Commands::Close(opts) => commands::close::close(opts, &jj, &mut gh, &config).await?,
+    // Commands that push local commits must see them the way jj does
+    if matches!(cli.command, Commands::Diff(_) | Commands::Land(_)) {
+        jj.check_git_in_sync()?;
+    }
+
+    let outcome = match cli.command {
+        Commands::Diff(opts) => {
+            commands::diff::diff(*opts, &jj, &mut gh, &config).await?;
+            Some(Outcome::Submitted)
+        }
+        Commands::Land(opts) => {
+            commands::land::land(opts, &git, &jj, &mut gh, &config).await?;
+            Some(Outcome::Landed)
+        }
+        Commands::Amend(opts) => {
+            commands::amend::amend(opts, &jj, &mut gh, &config).await?;
+            None
+        }
+        Commands::List(opts) => {
+            commands::list::list(opts, graphql_client, &config).await?;
+            None
+        }
+        Commands::Patch(opts) => {
+            commands::patch::patch(opts, &jj, &mut gh, &config).await?;
+            None
+        }
+        Commands::Close(opts) => {
+            commands::close::close(opts, &jj, &mut gh, &config).await?;
+            Some(Outcome::Closed)
+        }
+        Commands::Cleanup(opts) => {
+            commands::cleanup::cleanup(opts, &jj, &mut gh, &config).await?;
+            None
+        }
+        Commands::Reopen(opts) => {
+            commands::reopen::reopen(opts, &jj, &mut gh, &config).await?;
+            None
+        }
+        Commands::Status => {
+            commands::status::status(&jj, &mut gh, &config).await?;
+            None
+        }
         // The following commands are executed above and return from this
         // function before it reaches this match.
-        Commands::Init | Commands::Format(_) => (),
+        Commands::Init(_) | Commands::Format(_) => None,
     };
 
+    if let Some(outcome) = outcome
+        && !quiet
+    {
+        write_next_steps(outcome)?;
+    }
+
     Ok::<_, Error>(())
 }
 
@@ -224,3 +485,49 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cli_overrides_config() {
+        // Only spr.githubRemoteName is configured
+        let lookup = |key: &str| (key == "spr.githubRemoteName").then(|| "upstream".to_string());
+
+        let cli = Cli::try_parse_from([
+            "jj-spr",
+            "--remote-name",
+            "fork",
+            "--master-branch",
+            "develop",
+            "list",
+        ])
+        .unwrap();
+        let remote_name = cli_or_config(cli.remote_name, "spr.githubRemoteName", "origin", lookup);
+        let master_branch =
+            cli_or_config(cli.master_branch, "spr.githubMasterBranch", "main", lookup);
+
+        let config = jj_spr::config::Config::new(
+            "acme".into(),
+            "codez".into(),
+            remote_name,
+            master_branch,
+            "spr/foo/".into(),
+        );
+        assert_eq!(config.remote_name, "fork");
+        assert_eq!(config.master_ref.branch_name(), "develop");
+        assert_eq!(config.master_ref.local(), "refs/remotes/fork/develop");
+
+        // Without the flags, config and then the default apply
+        let cli = Cli::try_parse_from(["jj-spr", "list"]).unwrap();
+        assert_eq!(
+            cli_or_config(cli.remote_name, "spr.githubRemoteName", "origin", lookup),
+            "upstream"
+        );
+        assert_eq!(
+            cli_or_config(cli.master_branch, "spr.githubMasterBranch", "main", lookup),
+            "main"
+        );
+    }
+}
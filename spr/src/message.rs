@@ -6,6 +6,7 @@
  */
 
 use crate::{
+    config::parse_label_list,
     error::{Error, Result},
     output::output,
 };
@@ -17,41 +18,82 @@ pub enum MessageSection {
     Title,
     Summary,
     TestPlan,
+    Fixes,
+    CoAuthors,
     Reviewers,
+    Assignees,
+    Labels,
+    Milestone,
     ReviewedBy,
     PullRequest,
 }
 
-pub fn message_section_label(section: &MessageSection) -> &'static str {
+/// The heading of a section in commit messages. The test plan section is
+/// headed by `spr.testPlanSectionName`.
+pub fn message_section_label<'a>(
+    section: &MessageSection,
+    config: &'a crate::config::Config,
+) -> &'a str {
     use MessageSection::*;
 
     match section {
         Title => "Title",
         Summary => "Summary",
-        TestPlan => "Test Plan",
+        TestPlan => &config.test_plan_section_name,
+        Fixes => "Fixes",
+        CoAuthors => "Co-Authors",
         Reviewers => "Reviewers",
+        Assignees => "Assignees",
+        Labels => "Labels",
+        Milestone => "Milestone",
         ReviewedBy => "Reviewed By",
         PullRequest => "Pull Request",
     }
 }
 
-pub fn message_section_by_label(label: &str) -> Option<MessageSection> {
+/// The section with the given heading. `spr.testPlanSectionName` is
+/// recognised in addition to the default "Test Plan".
+pub fn message_section_by_label(
+    label: &str,
+    config: &crate::config::Config,
+) -> Option<MessageSection> {
+    section_by_label(label, &config.test_plan_section_name)
+}
+
+fn section_by_label(label: &str, test_plan_label: &str) -> Option<MessageSection> {
     use MessageSection::*;
 
+    if label.eq_ignore_ascii_case(test_plan_label) {
+        return Some(TestPlan);
+    }
+
     match &label.to_ascii_lowercase()[..] {
         "title" => Some(Title),
         "summary" => Some(Summary),
         "test plan" => Some(TestPlan),
+        "fixes" => Some(Fixes),
+        "co-author" => Some(CoAuthors),
+        "co-authors" => Some(CoAuthors),
+        "co-authored-by" => Some(CoAuthors),
         "reviewer" => Some(Reviewers),
         "reviewers" => Some(Reviewers),
+        "assignee" => Some(Assignees),
+        "assignees" => Some(Assignees),
+        "label" => Some(Labels),
+        "labels" => Some(Labels),
+        "milestone" => Some(Milestone),
         "reviewed by" => Some(ReviewedBy),
         "pull request" => Some(PullRequest),
         _ => None,
     }
 }
 
-pub fn parse_message(msg: &str, top_section: MessageSection) -> MessageSectionsMap {
-    let regex = lazy_regex::regex!(r#"^\s*([\w\s]+?)\s*:\s*(.*)$"#);
+pub fn parse_message(
+    msg: &str,
+    top_section: MessageSection,
+    config: &crate::config::Config,
+) -> MessageSectionsMap {
+    let regex = lazy_regex::regex!(r#"^\s*([\w\s-]+?)\s*:\s*(.*)$"#);
 
     let mut section = top_section;
     let mut lines_in_section = Vec::<&str>::new();
@@ -67,7 +109,7 @@ pub fn parse_message(msg: &str, top_section: MessageSection) -> MessageSectionsM
             let label = caps.get(1).unwrap().as_str();
             let payload = caps.get(2).unwrap().as_str();
 
-            if let Some(new_section) = message_section_by_label(label) {
+            if let Some(new_section) = message_section_by_label(label, config) {
                 append_to_message_section(
                     sections.entry(section),
                     lines_in_section.join("\n").trim(),
@@ -90,6 +132,16 @@ pub fn parse_message(msg: &str, top_section: MessageSection) -> MessageSectionsM
         append_to_message_section(sections.entry(section), lines_in_section.join("\n").trim());
     }
 
+    if let Some(labels) = sections.get_mut(&MessageSection::Labels) {
+        *labels = parse_label_list(labels).join(", ");
+    }
+    if let Some(fixes) = sections.get_mut(&MessageSection::Fixes) {
+        *fixes = parse_issue_references(fixes).join(", ");
+    }
+    if let Some(co_authors) = sections.get_mut(&MessageSection::CoAuthors) {
+        *co_authors = parse_co_authors(co_authors).join("\n");
+    }
+
     sections
 }
 
@@ -112,7 +164,68 @@ fn append_to_message_section(
     }
 }
 
-pub fn build_message(section_texts: &MessageSectionsMap, sections: &[MessageSection]) -> String {
+/// The keyword that makes GitHub close an issue when a Pull Request that
+/// refers to it is merged.
+const CLOSING_KEYWORD: &str = "Closes";
+
+/// The issues in a Fixes section, like `#123` or `org/repo#45`. The section
+/// may be written as a comma separated list, or as lines with a closing
+/// keyword as found in a Pull Request body.
+fn parse_issue_references(text: &str) -> Vec<String> {
+    let mut references: Vec<String> = Vec::new();
+    for word in text.split(|c: char| c == ',' || c.is_whitespace()) {
+        let word = word.trim();
+        let is_keyword =
+            lazy_regex::regex_is_match!(r"^(close[sd]?|fix(e[sd])?|resolve[sd]?):?$"i, word);
+        if !word.is_empty() && !is_keyword && !references.iter().any(|r| r == word) {
+            references.push(word.to_string());
+        }
+    }
+    references
+}
+
+/// The entries of a Co-Authors section, one per line or separated by commas,
+/// in the canonical `Name <email>` form where they have it.
+fn parse_co_authors(text: &str) -> Vec<String> {
+    let mut co_authors: Vec<String> = Vec::new();
+    for entry in text.split(['\n', ',']).map(str::trim) {
+        let entry = match lazy_regex::regex_captures!(r"^(.*?)\s*<\s*(\S+?)\s*>$", entry) {
+            Some((_, name, email)) => format!("{} <{}>", name, email),
+            None => entry.to_string(),
+        };
+        if !entry.is_empty() && !co_authors.contains(&entry) {
+            co_authors.push(entry);
+        }
+    }
+    co_authors
+}
+
+/// Whether a co-author is given as `Name <email>`, which is what GitHub needs
+/// to attribute the commit.
+fn is_valid_co_author(co_author: &str) -> bool {
+    lazy_regex::regex_is_match!(r"^[^<>]*\S[^<>]* <[^<>\s]+@[^<>\s]+>$", co_author)
+}
+
+/// The sections with the Fixes section turned into one line with a closing
+/// keyword per issue, so that GitHub links the issues and closes them on
+/// merge.
+fn with_closing_keywords(section_texts: &MessageSectionsMap) -> MessageSectionsMap {
+    let mut section_texts = section_texts.clone();
+    if let Some(fixes) = section_texts.get_mut(&MessageSection::Fixes) {
+        *fixes = parse_issue_references(fixes)
+            .iter()
+            .map(|reference| format!("{} {}", CLOSING_KEYWORD, reference))
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    section_texts
+}
+
+pub fn build_message(
+    section_texts: &MessageSectionsMap,
+    sections: &[MessageSection],
+    config: &crate::config::Config,
+) -> String {
     let mut result = String::new();
     let mut display_label = false;
 
@@ -130,13 +243,17 @@ pub fn build_message(section_texts: &MessageSectionsMap, sections: &[MessageSect
             }
 
             if display_label {
-                let label = message_section_label(section);
+                let label = message_section_label(section, config);
                 result.push_str(label);
-                result.push_str(if label.len() + text.len() > 76 || text.contains('\n') {
-                    ":\n"
-                } else {
-                    ": "
-                });
+                let closing_lines =
+                    section == &MessageSection::Fixes && text.starts_with(CLOSING_KEYWORD);
+                result.push_str(
+                    if label.len() + text.len() > 76 || text.contains('\n') || closing_lines {
+                        ":\n"
+                    } else {
+                        ": "
+                    },
+                );
             }
 
             result.push_str(text);
@@ -147,70 +264,274 @@ pub fn build_message(section_texts: &MessageSectionsMap, sections: &[MessageSect
     result
 }
 
-pub fn build_commit_message(section_texts: &MessageSectionsMap) -> String {
+pub fn build_commit_message(
+    section_texts: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> String {
     build_message(
         section_texts,
         &[
             MessageSection::Title,
             MessageSection::Summary,
             MessageSection::TestPlan,
+            MessageSection::Fixes,
+            MessageSection::CoAuthors,
             MessageSection::Reviewers,
+            MessageSection::Assignees,
+            MessageSection::Labels,
+            MessageSection::Milestone,
             MessageSection::ReviewedBy,
             MessageSection::PullRequest,
         ],
+        config,
     )
 }
 
-pub fn build_github_body(section_texts: &MessageSectionsMap) -> String {
+/// Sections that `spr diff` applies to a Pull Request, but that are not part
+/// of its title or body, so `spr amend` can't get them back from GitHub.
+pub const LOCAL_SECTIONS: [MessageSection; 3] = [
+    MessageSection::Assignees,
+    MessageSection::Labels,
+    MessageSection::Milestone,
+];
+
+pub fn build_github_body(
+    section_texts: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> String {
     build_message(
-        section_texts,
-        &[MessageSection::Summary, MessageSection::TestPlan],
+        &with_closing_keywords(section_texts),
+        &[
+            MessageSection::Summary,
+            MessageSection::TestPlan,
+            MessageSection::Fixes,
+            MessageSection::CoAuthors,
+        ],
+        config,
     )
 }
 
-pub fn build_github_body_for_merging(section_texts: &MessageSectionsMap) -> String {
-    build_message(
-        section_texts,
+/// The sections of a commit message that `spr diff` copies to the title and
+/// body of the Pull Request, and `spr amend` copies back.
+const PULL_REQUEST_SECTIONS: [MessageSection; 5] = [
+    MessageSection::Title,
+    MessageSection::Summary,
+    MessageSection::TestPlan,
+    MessageSection::Fixes,
+    MessageSection::CoAuthors,
+];
+
+/// The parts of a commit message that `spr diff` copies to the title and
+/// body of the Pull Request, and `spr amend` copies back.
+pub fn build_pull_request_message(
+    section_texts: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> String {
+    build_message(section_texts, &PULL_REQUEST_SECTIONS, config)
+}
+
+/// Take the sections of the Pull Request that were changed on GitHub since
+/// `base`, the message of the last sync, into `local`. Returns the sections
+/// that differ and were changed locally as well (or can't be told apart,
+/// without a `base`). Those keep the local text.
+pub fn merge_remote_sections(
+    base: Option<&MessageSectionsMap>,
+    local: &mut MessageSectionsMap,
+    remote: &MessageSectionsMap,
+) -> Vec<MessageSection> {
+    PULL_REQUEST_SECTIONS
+        .into_iter()
+        .filter(|&section| {
+            merge_remote_section(base, local, remote, section) == SectionMerge::Conflict
+        })
+        .collect()
+}
+
+/// What [`merge_remote_section`] did with a section.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SectionMerge {
+    /// Local and remote agree, or only the local text was changed
+    Unchanged,
+    /// The section was only changed on GitHub, and now has the remote text
+    TookRemote,
+    /// Both changed the section (or there is no `base` to tell), so it keeps
+    /// the local text
+    Conflict,
+}
+
+/// Take `section` of the Pull Request into `local` if it was only changed
+/// on GitHub since `base`, the message of the last sync. An empty section
+/// counts as a missing one.
+pub fn merge_remote_section(
+    base: Option<&MessageSectionsMap>,
+    local: &mut MessageSectionsMap,
+    remote: &MessageSectionsMap,
+    section: MessageSection,
+) -> SectionMerge {
+    let text = |message: &MessageSectionsMap| {
+        message
+            .get(&section)
+            .filter(|text| !text.is_empty())
+            .cloned()
+    };
+    let remote_text = text(remote);
+    let local_text = text(local);
+    if local_text == remote_text {
+        return SectionMerge::Unchanged;
+    }
+    match base.map(text) {
+        Some(base_text) if base_text == local_text => {
+            match remote_text {
+                Some(text) => local.insert(section, text),
+                None => local.remove(&section),
+            };
+            SectionMerge::TookRemote
+        }
+        Some(base_text) if base_text == remote_text => SectionMerge::Unchanged,
+        _ => SectionMerge::Conflict,
+    }
+}
+
+/// The message of the squash commit when landing a Pull Request. Co-authors
+/// go last, as `Co-authored-by:` trailers, so that GitHub attributes them.
+pub fn build_github_body_for_merging(
+    section_texts: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> String {
+    let mut message = build_message(
+        &with_closing_keywords(section_texts),
         &[
             MessageSection::Summary,
             MessageSection::TestPlan,
+            MessageSection::Fixes,
             MessageSection::Reviewers,
             MessageSection::ReviewedBy,
             MessageSection::PullRequest,
         ],
-    )
+        config,
+    );
+    append_co_author_trailers(&mut message, section_texts);
+    message
+}
+
+/// Add the co-authors in `section_texts` to `message` as `Co-authored-by:`
+/// trailers, after an empty line.
+pub fn append_co_author_trailers(message: &mut String, section_texts: &MessageSectionsMap) {
+    let co_authors: Vec<String> = section_texts
+        .get(&MessageSection::CoAuthors)
+        .map(|text| parse_co_authors(text))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|co_author| is_valid_co_author(co_author))
+        .collect();
+    if !co_authors.is_empty() && !message.is_empty() {
+        if !message.ends_with('\n') {
+            message.push('\n');
+        }
+        message.push('\n');
+    }
+    for co_author in co_authors {
+        message.push_str(&format!("Co-authored-by: {}\n", co_author));
+    }
+}
+
+/// What is missing from a commit message before it can be submitted, with
+/// the exact section to add.
+pub fn commit_message_problems(
+    message: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> Vec<String> {
+    let is_empty = |section| {
+        message
+            .get(&section)
+            .is_none_or(|text| text.trim().is_empty())
+    };
+    let mut problems = Vec::new();
+
+    if is_empty(MessageSection::Title) {
+        problems.push("Commit message does not have a title!".to_string());
+    }
+    if config.require_title_length
+        && let Some(problem) = title_length_problem(message, config)
+    {
+        problems.push(problem);
+    }
+    if config.require_summary && is_empty(MessageSection::Summary) {
+        problems.push(
+            "Commit message does not have a summary! Add a paragraph after the \
+             title, separated by an empty line (or a 'Summary:' section)."
+                .to_string(),
+        );
+    }
+    if config.require_test_plan && is_empty(MessageSection::TestPlan) {
+        problems.push(format!(
+            "Commit message does not have a {name} section! Add a '{name}:' \
+             section saying how the change was tested.",
+            name = config.test_plan_section_name
+        ));
+    }
+    if let Some(co_authors) = message.get(&MessageSection::CoAuthors) {
+        for co_author in parse_co_authors(co_authors) {
+            if !is_valid_co_author(&co_author) {
+                problems.push(format!(
+                    "Co-author '{}' is not of the form 'Name <email>'!",
+                    co_author
+                ));
+            }
+        }
+    }
+
+    problems
+}
+
+/// A title longer than `spr.maxTitleLength`, which GitHub cuts off in lists
+/// and notifications. Only a warning, unless `spr.requireTitleLength` is set.
+fn title_length_problem(
+    message: &MessageSectionsMap,
+    config: &crate::config::Config,
+) -> Option<String> {
+    let length = message.get(&MessageSection::Title)?.chars().count();
+    (length > config.max_title_length).then(|| {
+        format!(
+            "Commit message title is {} characters long, more than the {} allowed by \
+             spr.maxTitleLength!",
+            length, config.max_title_length
+        )
+    })
 }
 
 pub fn validate_commit_message(
     message: &MessageSectionsMap,
     config: &crate::config::Config,
 ) -> Result<()> {
-    if config.require_test_plan && !message.contains_key(&MessageSection::TestPlan) {
-        output("💔", "Commit message does not have a Test Plan!")?;
-        return Err(Error::empty());
+    if !config.require_title_length
+        && let Some(warning) = title_length_problem(message, config)
+    {
+        output("⚠️", &warning)?;
     }
 
-    let title_missing_or_empty = match message.get(&MessageSection::Title) {
-        None => true,
-        Some(title) => title.is_empty(),
-    };
-    if title_missing_or_empty {
-        output("💔", "Commit message does not have a title!")?;
-        return Err(Error::empty());
+    let problems = commit_message_problems(message, config);
+    for problem in &problems {
+        output("💔", problem)?;
     }
 
-    Ok(())
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::empty())
+    }
 }
 
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
     use super::*;
+    use crate::config::test_config;
 
     #[test]
     fn test_parse_empty() {
         assert_eq!(
-            parse_message("", MessageSection::Title),
+            parse_message("", MessageSection::Title, &test_config()),
             [(MessageSection::Title, "".to_string())].into()
         );
     }
@@ -218,15 +539,15 @@ mod tests {
     #[test]
     fn test_parse_title() {
         assert_eq!(
-            parse_message("Hello", MessageSection::Title),
+            parse_message("Hello", MessageSection::Title, &test_config()),
             [(MessageSection::Title, "Hello".to_string())].into()
         );
         assert_eq!(
-            parse_message("Hello\n", MessageSection::Title),
+            parse_message("Hello\n", MessageSection::Title, &test_config()),
             [(MessageSection::Title, "Hello".to_string())].into()
         );
         assert_eq!(
-            parse_message("\n\nHello\n\n", MessageSection::Title),
+            parse_message("\n\nHello\n\n", MessageSection::Title, &test_config()),
             [(MessageSection::Title, "Hello".to_string())].into()
         );
     }
@@ -234,7 +555,7 @@ mod tests {
     #[test]
     fn test_parse_title_and_summary() {
         assert_eq!(
-            parse_message("Hello\nFoo Bar", MessageSection::Title),
+            parse_message("Hello\nFoo Bar", MessageSection::Title, &test_config()),
             [
                 (MessageSection::Title, "Hello".to_string()),
                 (MessageSection::Summary, "Foo Bar".to_string())
@@ -242,7 +563,7 @@ mod tests {
             .into()
         );
         assert_eq!(
-            parse_message("Hello\n\nFoo Bar", MessageSection::Title),
+            parse_message("Hello\n\nFoo Bar", MessageSection::Title, &test_config()),
             [
                 (MessageSection::Title, "Hello".to_string()),
                 (MessageSection::Summary, "Foo Bar".to_string())
@@ -250,7 +571,7 @@ mod tests {
             .into()
         );
         assert_eq!(
-            parse_message("Hello\n\n\nFoo Bar", MessageSection::Title),
+            parse_message("Hello\n\n\nFoo Bar", MessageSection::Title, &test_config()),
             [
                 (MessageSection::Title, "Hello".to_string()),
                 (MessageSection::Summary, "Foo Bar".to_string())
@@ -258,7 +579,11 @@ mod tests {
             .into()
         );
         assert_eq!(
-            parse_message("Hello\n\nSummary:\nFoo Bar", MessageSection::Title),
+            parse_message(
+                "Hello\n\nSummary:\nFoo Bar",
+                MessageSection::Title,
+                &test_config()
+            ),
             [
                 (MessageSection::Title, "Hello".to_string()),
                 (MessageSection::Summary, "Foo Bar".to_string())
@@ -281,7 +606,8 @@ the
 summary (it's not a "Test plan:"!)
 
 Reviewer:    a, b, c"#,
-                MessageSection::Title
+                MessageSection::Title,
+                &test_config()
             ),
             [
                 (MessageSection::Title, "Hello".to_string()),
@@ -295,4 +621,257 @@ Reviewer:    a, b, c"#,
             .into()
         );
     }
+
+    #[test]
+    fn test_local_sections_round_trip() {
+        let message = parse_message(
+            "Hello\n\nSummary text\n\nTest Plan: none\n\nLabels:  bug,p1 ,, bug\nReviewers: a\nAssignee: b\nMilestone: v2.0",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(message[&MessageSection::Labels], "bug, p1");
+        assert_eq!(
+            build_commit_message(&message, &test_config()),
+            "Hello\n\nSummary text\n\nTest Plan: none\n\nReviewers: a\n\nAssignees: b\n\nLabels: bug, p1\n\nMilestone: v2.0\n"
+        );
+        assert_eq!(
+            parse_message(
+                &build_commit_message(&message, &test_config()),
+                MessageSection::Title,
+                &test_config()
+            ),
+            message
+        );
+    }
+
+    #[test]
+    fn test_custom_test_plan_label() {
+        let config = crate::config::Config {
+            test_plan_section_name: "QA".into(),
+            ..test_config()
+        };
+        assert_eq!(
+            message_section_by_label("qa", &config),
+            Some(MessageSection::TestPlan)
+        );
+        // The default heading keeps working
+        assert_eq!(
+            message_section_by_label("Test plan", &config),
+            Some(MessageSection::TestPlan)
+        );
+        assert_eq!(message_section_by_label("QA", &test_config()), None);
+
+        let text = "Title\n\nSummary\n\nQA: ran it\n";
+        let message = parse_message(text, MessageSection::Title, &config);
+        assert_eq!(message[&MessageSection::TestPlan], "ran it");
+        assert_eq!(build_commit_message(&message, &config), text);
+    }
+
+    #[test]
+    fn test_fixes_section() {
+        let message = parse_message(
+            "Hello\n\nSummary text\n\nFixes: #123, org/other#45,#123",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(message[&MessageSection::Fixes], "#123, org/other#45");
+
+        let body = build_github_body(&message, &test_config());
+        assert_eq!(
+            body,
+            "Summary text\n\nFixes:\nCloses #123\nCloses org/other#45\n"
+        );
+        // Reading the Pull Request body back gives the same section
+        assert_eq!(
+            parse_message(&body, MessageSection::Summary, &test_config())[&MessageSection::Fixes],
+            "#123, org/other#45"
+        );
+
+        let mut single = MessageSectionsMap::new();
+        single.insert(MessageSection::Fixes, "#7".into());
+        assert_eq!(
+            build_github_body(&single, &test_config()),
+            "Fixes:\nCloses #7\n"
+        );
+        assert_eq!(build_commit_message(&single, &test_config()), "Fixes: #7\n");
+    }
+
+    #[test]
+    fn test_co_authors_section() {
+        let message = parse_message(
+            "Hello\n\nSummary text\n\nCo-authored-by: Jane Doe<jane@example.com>\nCo-authored-by: Joe <joe@example.com>, Jane Doe <jane@example.com>",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(
+            message[&MessageSection::CoAuthors],
+            "Jane Doe <jane@example.com>\nJoe <joe@example.com>"
+        );
+        assert_eq!(
+            build_commit_message(&message, &test_config()),
+            "Hello\n\nSummary text\n\nCo-Authors:\nJane Doe <jane@example.com>\nJoe <joe@example.com>\n"
+        );
+        assert_eq!(
+            build_github_body_for_merging(&message, &test_config()),
+            "Summary text\n\nCo-authored-by: Jane Doe <jane@example.com>\nCo-authored-by: Joe <joe@example.com>\n"
+        );
+
+        let invalid = parse_message(
+            "Hello\n\nCo-Authors: Jane Doe",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(invalid[&MessageSection::CoAuthors], "Jane Doe");
+        assert!(
+            !build_github_body_for_merging(&invalid, &test_config()).contains("Co-authored-by")
+        );
+    }
+
+    #[test]
+    fn test_merge_remote_sections() {
+        let base = parse_message(
+            "Title\n\nSummary\n\nTest Plan: none",
+            MessageSection::Title,
+            &test_config(),
+        );
+        let mut local = parse_message(
+            "Title\n\nSummary\n\nTest Plan: ran it locally",
+            MessageSection::Title,
+            &test_config(),
+        );
+        let remote = parse_message(
+            "Retitled on GitHub\n\nSummary\n\nTest Plan: ran it on CI",
+            MessageSection::Title,
+            &test_config(),
+        );
+
+        let conflicts = merge_remote_sections(Some(&base), &mut local, &remote);
+        assert_eq!(conflicts, vec![MessageSection::TestPlan]);
+        assert_eq!(local[&MessageSection::Title], "Retitled on GitHub");
+        assert_eq!(local[&MessageSection::TestPlan], "ran it locally");
+
+        // Without a base, every difference is kept local and reported
+        let mut local = parse_message("Title\n\nSummary", MessageSection::Title, &test_config());
+        let conflicts = merge_remote_sections(None, &mut local, &remote);
+        assert_eq!(
+            conflicts,
+            vec![MessageSection::Title, MessageSection::TestPlan]
+        );
+        assert_eq!(local[&MessageSection::Title], "Title");
+    }
+
+    #[test]
+    fn test_merge_remote_section() {
+        let base = parse_message(
+            "Title\n\nSummary of the last sync",
+            MessageSection::Title,
+            &test_config(),
+        );
+        let remote = parse_message(
+            "Title\n\nEdited on GitHub\n\nTest Plan: remote",
+            MessageSection::Title,
+            &test_config(),
+        );
+
+        // Only changed on GitHub: take it
+        let mut local = base.clone();
+        assert_eq!(
+            merge_remote_section(Some(&base), &mut local, &remote, MessageSection::Summary),
+            SectionMerge::TookRemote
+        );
+        assert_eq!(local[&MessageSection::Summary], "Edited on GitHub");
+        assert_eq!(
+            merge_remote_section(Some(&base), &mut local, &remote, MessageSection::Summary),
+            SectionMerge::Unchanged
+        );
+
+        // Changed on both sides, or no base to tell: keep the local text
+        let mut local = parse_message(
+            "Title\n\nEdited locally",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(
+            merge_remote_section(Some(&base), &mut local, &remote, MessageSection::Summary),
+            SectionMerge::Conflict
+        );
+        assert_eq!(
+            merge_remote_section(None, &mut local, &remote, MessageSection::Summary),
+            SectionMerge::Conflict
+        );
+        assert_eq!(local[&MessageSection::Summary], "Edited locally");
+
+        // Only changed locally: keep it
+        let mut local = parse_message(
+            "Title\n\nEdited locally",
+            MessageSection::Title,
+            &test_config(),
+        );
+        let unchanged_remote = base.clone();
+        assert_eq!(
+            merge_remote_section(
+                Some(&base),
+                &mut local,
+                &unchanged_remote,
+                MessageSection::Summary
+            ),
+            SectionMerge::Unchanged
+        );
+        assert_eq!(local[&MessageSection::Summary], "Edited locally");
+
+        // Removed on GitHub
+        let mut local = base.clone();
+        let remote = parse_message("Title", MessageSection::Title, &test_config());
+        assert_eq!(
+            merge_remote_section(Some(&base), &mut local, &remote, MessageSection::Summary),
+            SectionMerge::TookRemote
+        );
+        assert!(!local.contains_key(&MessageSection::Summary));
+    }
+
+    #[test]
+    fn test_commit_message_problems() {
+        let mut config = crate::config::test_config();
+
+        let complete = parse_message(
+            "Title\n\nSummary\n\nTest Plan: ran it",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert!(commit_message_problems(&complete, &config).is_empty());
+
+        let message = parse_message("Title\n\nTest Plan:", MessageSection::Title, &test_config());
+        let problems = commit_message_problems(&message, &config);
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].contains("summary"));
+        assert!(problems[1].contains("Add a 'Test Plan:' section"));
+
+        let message = parse_message(
+            "Title\n\nSummary\n\nTest Plan: ran it\n\nCo-Authors: Jane Doe",
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert_eq!(
+            commit_message_problems(&message, &config),
+            vec!["Co-author 'Jane Doe' is not of the form 'Name <email>'!"]
+        );
+
+        let message = parse_message(
+            &format!("{}\n\nSummary\n\nTest Plan: ran it", "x".repeat(80)),
+            MessageSection::Title,
+            &test_config(),
+        );
+        assert!(commit_message_problems(&message, &config).is_empty());
+        assert_eq!(
+            title_length_problem(&message, &config).as_deref(),
+            Some(
+                "Commit message title is 80 characters long, more than the 72 allowed by \
+                 spr.maxTitleLength!"
+            )
+        );
+        config.require_title_length = true;
+        assert_eq!(commit_message_problems(&message, &config).len(), 1);
+        config.max_title_length = 80;
+        assert!(commit_message_problems(&message, &config).is_empty());
+    }
 }
@@ -5,10 +5,161 @@
  * LICENSE file in the root directory of this source tree.
  */
 
+use std::sync::{
+    OnceLock,
+    atomic::{AtomicBool, Ordering},
+};
+
 use crate::{error::Result, jj::PreparedCommit, message::MessageSection};
 
+/// How spr reports what it does: lines for people to read, or one JSON
+/// object per line for tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Human,
+    Json,
+}
+
+static OUTPUT_FORMAT: OnceLock<OutputFormat> = OnceLock::new();
+
+/// Set the output format for the rest of the run, from `--output-format`.
+pub fn set_output_format(format: OutputFormat) {
+    let _ = OUTPUT_FORMAT.set(format);
+}
+
+fn output_format() -> OutputFormat {
+    OUTPUT_FORMAT.get().copied().unwrap_or_default()
+}
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Whether the last line given to `output` was printed. Indented lines
+/// (with a blank icon) continue the line before them, so they are shown or
+/// left out with it.
+static LAST_SHOWN: AtomicBool = AtomicBool::new(true);
+
+/// Only print warnings and errors from now on (`--quiet` or `spr.quiet`).
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether a line with the given icon is printed in quiet mode, given
+/// whether the line before it was.
+fn shown_when_quiet(icon: &str, last_shown: bool) -> bool {
+    if icon.trim().is_empty() {
+        return last_shown;
+    }
+    matches!(step_name(icon), "warning" | "error")
+}
+
+static VERBOSE: AtomicBool = AtomicBool::new(false);
+
+/// Log the jj and git commands spr runs, and its requests to GitHub, from
+/// now on (`--verbose`, or `RUST_LOG` asking for debug logging).
+pub fn set_verbose(verbose: bool) {
+    VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+pub fn is_verbose() -> bool {
+    VERBOSE.load(Ordering::Relaxed)
+}
+
+/// Whether a `RUST_LOG` value asks for debug logging of spr: the level debug
+/// or trace, for everything or for the `jj_spr` crate.
+pub fn rust_log_enables_debug(rust_log: &str) -> bool {
+    const LEVELS: [&str; 6] = ["off", "error", "warn", "info", "debug", "trace"];
+
+    rust_log
+        .split(',')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .any(|directive| {
+            let (target, level) = match directive.split_once('=') {
+                Some((target, level)) => (target, level),
+                None if LEVELS.contains(&&directive.to_ascii_lowercase()[..]) => ("", directive),
+                None => (directive, "trace"),
+            };
+            (target.is_empty() || target == "jj_spr" || target.starts_with("jj_spr::"))
+                && (level.eq_ignore_ascii_case("debug") || level.eq_ignore_ascii_case("trace"))
+        })
+}
+
+/// Print a line of `--verbose` logging. It goes to stderr, so that it
+/// doesn't mix with output that is meant for tools.
+pub fn debug(text: &str) {
+    if is_verbose() {
+        eprintln!("[spr] {}", text.trim_end());
+    }
+}
+
+/// Log a command that is about to run.
+pub fn debug_command(command: &std::process::Command) {
+    if is_verbose() {
+        debug(&format!("running {:?}", command));
+    }
+}
+
+/// Log how a command exited.
+pub fn debug_exit(command: &std::process::Command, status: std::process::ExitStatus) {
+    if is_verbose() {
+        debug(&format!(
+            "{} finished with {}",
+            command.get_program().to_string_lossy(),
+            status
+        ));
+    }
+}
+
+/// The name of the step an `output` icon stands for, as reported in JSON
+/// output.
+fn step_name(icon: &str) -> &'static str {
+    match icon.trim() {
+        "#️⃣" => "pull_request",
+        "✨" => "created",
+        "🔁" | "✍" => "updated",
+        "✅" => "done",
+        "🛫" => "landing",
+        "🛬" => "landed",
+        "👋" => "nothing_to_do",
+        "💡" => "hint",
+        "⚠️" => "warning",
+        "❌" | "💥" | "💔" | "🛑" => "error",
+        _ => "info",
+    }
+}
+
+/// One line of JSON output.
+fn json_event(step: &str, text: &str) -> String {
+    serde_json::json!({ "step": step, "message": text.trim() }).to_string()
+}
+
 pub fn output(icon: &str, text: &str) -> Result<()> {
+    let shown = !is_quiet() || shown_when_quiet(icon, LAST_SHOWN.load(Ordering::Relaxed));
+    LAST_SHOWN.store(shown, Ordering::Relaxed);
+    if !shown {
+        return Ok(());
+    }
+    write_output(icon, text)
+}
+
+/// Like `output`, but also printed in quiet mode: for lines that are what
+/// the user asked for rather than progress, like the report of `spr status`.
+pub fn report(icon: &str, text: &str) -> Result<()> {
+    LAST_SHOWN.store(true, Ordering::Relaxed);
+    write_output(icon, text)
+}
+
+fn write_output(icon: &str, text: &str) -> Result<()> {
     let term = console::Term::stdout();
+    if output_format() == OutputFormat::Json {
+        term.write_line(&json_event(step_name(icon), text))?;
+        return Ok(());
+    }
 
     let bullet = format!("  {}  ", icon);
     let indent = console::measure_text_width(&bullet);
@@ -25,18 +176,117 @@ pub fn output(icon: &str, text: &str) -> Result<()> {
 }
 
 pub fn write_commit_title(prepared_commit: &PreparedCommit) -> Result<()> {
+    if is_quiet() {
+        return Ok(());
+    }
     let term = console::Term::stdout();
+    let title = prepared_commit
+        .message
+        .get(&MessageSection::Title)
+        .map(|s| &s[..])
+        .unwrap_or("(untitled)");
+    if output_format() == OutputFormat::Json {
+        term.write_line(&json_event(
+            "commit",
+            &format!("{} {}", prepared_commit.short_id, title),
+        ))?;
+        return Ok(());
+    }
     term.write_line(&format!(
         "{} {}",
         console::style(&prepared_commit.short_id).italic(),
-        console::style(
-            prepared_commit
-                .message
-                .get(&MessageSection::Title)
-                .map(|s| &s[..])
-                .unwrap_or("(untitled)"),
-        )
-        .yellow()
+        console::style(title).yellow()
     ))?;
     Ok(())
 }
+
+/// What a command did, for suggesting what to do next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Submitted,
+    Landed,
+    Closed,
+}
+
+/// Suggestions of commands that usually follow `outcome`.
+pub fn next_steps(outcome: Outcome) -> &'static [&'static str] {
+    match outcome {
+        Outcome::Submitted => &[
+            "Once the Pull Request is approved, merge it with `jj spr land`",
+            "See all your open Pull Requests with `jj spr list`",
+        ],
+        Outcome::Landed => &[
+            "Update Pull Requests stacked on top with `jj spr diff --all`",
+            "See what is left of the stack with `jj spr status`",
+        ],
+        Outcome::Closed => &[
+            "Drop the local change with `jj abandon`, or open a new Pull Request for it with `jj spr diff`",
+        ],
+    }
+}
+
+pub fn write_next_steps(outcome: Outcome) -> Result<()> {
+    for step in next_steps(outcome) {
+        output("💡", step)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_next_steps() {
+        assert!(
+            next_steps(Outcome::Submitted)
+                .iter()
+                .any(|step| step.contains("jj spr land"))
+        );
+        assert!(
+            next_steps(Outcome::Landed)
+                .iter()
+                .any(|step| step.contains("jj spr diff --all"))
+        );
+        assert!(
+            next_steps(Outcome::Closed)
+                .iter()
+                .all(|step| !step.contains("jj spr land"))
+        );
+    }
+
+    #[test]
+    fn test_rust_log_enables_debug() {
+        assert!(rust_log_enables_debug("debug"));
+        assert!(rust_log_enables_debug("TRACE"));
+        assert!(rust_log_enables_debug("jj_spr=debug"));
+        assert!(rust_log_enables_debug("hyper=warn, jj_spr::jj=trace"));
+        assert!(rust_log_enables_debug("jj_spr"));
+        assert!(!rust_log_enables_debug("info"));
+        assert!(!rust_log_enables_debug("hyper=debug"));
+        assert!(!rust_log_enables_debug("jj_spr=info"));
+        assert!(!rust_log_enables_debug(""));
+    }
+
+    #[test]
+    fn test_json_event() {
+        assert_eq!(
+            json_event(step_name("✨"), "Created new Pull Request #12\n"),
+            r#"{"message":"Created new Pull Request #12","step":"created"}"#
+        );
+        assert_eq!(step_name("#️⃣ "), "pull_request");
+        assert_eq!(step_name("🔍"), "info");
+    }
+
+    #[test]
+    fn test_shown_when_quiet() {
+        assert!(shown_when_quiet("⚠️", false));
+        assert!(shown_when_quiet("🛑", false));
+        assert!(!shown_when_quiet("🛫", true));
+        assert!(!shown_when_quiet("#️⃣ ", true));
+
+        // Indented lines go with the line before them
+        assert!(!shown_when_quiet("  ", false));
+        assert!(shown_when_quiet("  ", true));
+    }
+}
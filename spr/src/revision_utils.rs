@@ -48,6 +48,19 @@ pub fn parse_revision_and_range(
     }
 }
 
+/// Whether the options ask for the whole stack below the revision, i.e.
+/// `--all` without `--base` or an explicit range. Commands then use
+/// [`crate::jj::Jujutsu::get_stack`], so that they all agree on what the
+/// current stack is.
+pub fn is_default_stack(
+    revision_opt: Option<&str>,
+    all_mode: bool,
+    base_opt: Option<&str>,
+) -> bool {
+    let revision = revision_opt.unwrap_or("@-");
+    all_mode && base_opt.is_none() && !revision.contains("..") && !revision.contains("::")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -155,4 +168,13 @@ mod tests {
         assert_eq!(target_rev, "@");
         assert!(is_inclusive);
     }
+
+    #[test]
+    fn test_default_stack() {
+        assert!(is_default_stack(None, true, None));
+        assert!(is_default_stack(Some("@"), true, None));
+        assert!(!is_default_stack(None, false, None));
+        assert!(!is_default_stack(None, true, Some("main")));
+        assert!(!is_default_stack(Some("main..@"), true, None));
+    }
 }
@@ -0,0 +1,311 @@
+/*
+ * Copyright (c) Radical HQ Limited
+ *
+ * This source code is licensed under the MIT license found in the
+ * LICENSE file in the root directory of this source tree.
+ */
+
+//! Local record of what spr last wrote to Pull Requests on GitHub.
+//!
+//! The mapping is kept in a file in git config format inside the git
+//! directory (`.git/spr-mapping`), with one subsection per Pull Request
+//! number, e.g.:
+//!
+//! ```text
+//! [pr "123"]
+//!     title = Add frobnicator
+//!     message = 3b18e512dba79e4c8300dd08aeb37f8e728b8dad
+//!     labels = bug\np1
+//!     head = 8f1c2a5e0b7d4c3a9e6f1b2d3c4a5e6f7a8b9c0d
+//!     base = release/2.x
+//!     cherrypick = true
+//! [change "kkmpptxzrspxrzommnulwmwkkqwworpl"]
+//!     pr = 123
+//! ```
+//!
+//! Commit messages are not stored in the mapping itself. Instead, they are
+//! written as blobs to the object database and the mapping holds their id.
+//!
+//! The `change` subsections record the Pull Requests of changes that were
+//! submitted with `spr diff --no-update-message`, whose commit messages don't
+//! name their Pull Request.
+
+use std::path::Path;
+
+use crate::error::Result;
+
+const MAPPING_FILE_NAME: &str = "spr-mapping";
+
+pub struct MappingStore {
+    config: git2::Config,
+}
+
+impl MappingStore {
+    /// Open the mapping store of the given repository. The file is created
+    /// on the first write.
+    pub fn open(git_repo: &git2::Repository) -> Result<Self> {
+        Self::open_path(&git_repo.path().join(MAPPING_FILE_NAME))
+    }
+
+    pub fn open_path(path: &Path) -> Result<Self> {
+        Ok(Self {
+            config: git2::Config::open(path)?,
+        })
+    }
+
+    /// The title spr last set on the given Pull Request, if known.
+    pub fn title(&self, pull_request_number: u64) -> Option<String> {
+        self.get(pull_request_number, "title")
+    }
+
+    pub fn set_title(&mut self, pull_request_number: u64, title: &str) -> Result<()> {
+        self.set(pull_request_number, "title", title)
+    }
+
+    /// The id of the blob holding the commit message that was last in sync
+    /// with the given Pull Request, if known.
+    pub fn message_oid(&self, pull_request_number: u64) -> Option<git2::Oid> {
+        self.get(pull_request_number, "message")
+            .and_then(|oid| git2::Oid::from_str(&oid).ok())
+    }
+
+    /// Record the commit message that is now in sync with the given Pull
+    /// Request. The text is written to the object database of `git_repo`, so
+    /// that it can be shown later with [`MappingStore::message`].
+    pub fn set_message(
+        &mut self,
+        git_repo: &git2::Repository,
+        pull_request_number: u64,
+        message: &str,
+    ) -> Result<()> {
+        let oid = git_repo.blob(message.as_bytes())?;
+        self.set(pull_request_number, "message", &oid.to_string())
+    }
+
+    /// The commit message that was last in sync with the given Pull Request,
+    /// if known and still present in the object database.
+    pub fn message(&self, git_repo: &git2::Repository, pull_request_number: u64) -> Option<String> {
+        let blob = git_repo
+            .find_blob(self.message_oid(pull_request_number)?)
+            .ok()?;
+        Some(String::from_utf8_lossy(blob.content()).into_owned())
+    }
+
+    /// The labels spr added to the given Pull Request, as opposed to labels
+    /// that were added on GitHub.
+    pub fn labels(&self, pull_request_number: u64) -> Vec<String> {
+        self.get(pull_request_number, "labels")
+            .map(|labels| {
+                labels
+                    .lines()
+                    .filter(|label| !label.is_empty())
+                    .map(String::from)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn set_labels(&mut self, pull_request_number: u64, labels: &[String]) -> Result<()> {
+        self.set(pull_request_number, "labels", &labels.join("\n"))
+    }
+
+    /// The commit spr last pushed to the given Pull Request's branch, if
+    /// known.
+    pub fn head(&self, pull_request_number: u64) -> Option<git2::Oid> {
+        self.get(pull_request_number, "head")
+            .and_then(|oid| git2::Oid::from_str(&oid).ok())
+    }
+
+    pub fn set_head(&mut self, pull_request_number: u64, oid: git2::Oid) -> Result<()> {
+        self.set(pull_request_number, "head", &oid.to_string())
+    }
+
+    /// The branch the given Pull Request was opened against with `spr diff
+    /// --base-branch`, if not the master branch.
+    pub fn base_branch(&self, pull_request_number: u64) -> Option<String> {
+        self.get(pull_request_number, "base")
+    }
+
+    pub fn set_base_branch(&mut self, pull_request_number: u64, branch: &str) -> Result<()> {
+        self.set(pull_request_number, "base", branch)
+    }
+
+    /// Whether the given Pull Request was last updated with `spr diff
+    /// --cherry-pick`, i.e. against master rather than the commits below it.
+    pub fn cherry_pick(&self, pull_request_number: u64) -> bool {
+        self.config
+            .get_bool(&Self::config_key(pull_request_number, "cherrypick"))
+            .unwrap_or(false)
+    }
+
+    pub fn set_cherry_pick(&mut self, pull_request_number: u64, cherry_pick: bool) -> Result<()> {
+        self.config.set_bool(
+            &Self::config_key(pull_request_number, "cherrypick"),
+            cherry_pick,
+        )?;
+        Ok(())
+    }
+
+    /// The Pull Request that `spr diff --no-update-message` created for the
+    /// given jj change, if any.
+    pub fn change_pull_request(&self, change_id: &str) -> Option<u64> {
+        self.config
+            .get_i64(&Self::change_key(change_id))
+            .ok()
+            .and_then(|number| u64::try_from(number).ok())
+    }
+
+    pub fn set_change_pull_request(
+        &mut self,
+        change_id: &str,
+        pull_request_number: u64,
+    ) -> Result<()> {
+        self.config
+            .set_i64(&Self::change_key(change_id), pull_request_number as i64)?;
+        Ok(())
+    }
+
+    /// Whether any change has its Pull Request recorded here, so that it's
+    /// worth looking up change ids.
+    pub fn has_change_pull_requests(&self) -> bool {
+        self.config
+            .entries(Some(r"change\..*\.pr"))
+            .is_ok_and(|mut entries| entries.next().is_some())
+    }
+
+    fn get(&self, pull_request_number: u64, key: &str) -> Option<String> {
+        self.config
+            .get_string(&Self::config_key(pull_request_number, key))
+            .ok()
+    }
+
+    fn set(&mut self, pull_request_number: u64, key: &str, value: &str) -> Result<()> {
+        self.config
+            .set_str(&Self::config_key(pull_request_number, key), value)?;
+        Ok(())
+    }
+
+    fn config_key(pull_request_number: u64, key: &str) -> String {
+        format!("pr.{}.{}", pull_request_number, key)
+    }
+
+    fn change_key(change_id: &str) -> String {
+        format!("change.{}.pr", change_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_title_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MAPPING_FILE_NAME);
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert_eq!(store.title(12), None);
+        store.set_title(12, "Fix \"quoted\" # title").unwrap();
+
+        let store = MappingStore::open_path(&path).unwrap();
+        assert_eq!(store.title(12).as_deref(), Some("Fix \"quoted\" # title"));
+        assert_eq!(store.title(13), None);
+    }
+
+    #[test]
+    fn test_head_and_base_branch_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MAPPING_FILE_NAME);
+        let head = git2::Oid::from_str("8f1c2a5e0b7d4c3a9e6f1b2d3c4a5e6f7a8b9c0d").unwrap();
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        store.set_head(12, head).unwrap();
+        store.set_base_branch(12, "release/2.x").unwrap();
+
+        let store = MappingStore::open_path(&path).unwrap();
+        assert_eq!(store.head(12), Some(head));
+        assert_eq!(store.base_branch(12).as_deref(), Some("release/2.x"));
+        assert_eq!(store.head(13), None);
+        assert_eq!(store.base_branch(13), None);
+    }
+
+    #[test]
+    fn test_cherry_pick_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MAPPING_FILE_NAME);
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert!(!store.cherry_pick(12));
+        store.set_cherry_pick(12, true).unwrap();
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert!(store.cherry_pick(12));
+        store.set_cherry_pick(12, false).unwrap();
+        assert!(!store.cherry_pick(12));
+    }
+
+    #[test]
+    fn test_change_pull_request_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MAPPING_FILE_NAME);
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert!(!store.has_change_pull_requests());
+        store.set_title(12, "Title").unwrap();
+        assert!(!store.has_change_pull_requests());
+        store
+            .set_change_pull_request("kkmpptxzrspxrzommnulwmwkkqwworpl", 12)
+            .unwrap();
+
+        let store = MappingStore::open_path(&path).unwrap();
+        assert!(store.has_change_pull_requests());
+        assert_eq!(
+            store.change_pull_request("kkmpptxzrspxrzommnulwmwkkqwworpl"),
+            Some(12)
+        );
+        assert_eq!(
+            store.change_pull_request("zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_labels_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join(MAPPING_FILE_NAME);
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert!(store.labels(12).is_empty());
+        store
+            .set_labels(12, &["bug".to_string(), "team: infra".to_string()])
+            .unwrap();
+
+        let mut store = MappingStore::open_path(&path).unwrap();
+        assert_eq!(store.labels(12), vec!["bug", "team: infra"]);
+        store.set_labels(12, &[]).unwrap();
+        assert!(store.labels(12).is_empty());
+    }
+
+    #[test]
+    fn test_message_round_trip() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let git_repo = git2::Repository::init(temp_dir.path()).unwrap();
+
+        let mut store = MappingStore::open(&git_repo).unwrap();
+        assert_eq!(store.message_oid(12), None);
+        store
+            .set_message(&git_repo, 12, "Title\n\nSummary\n")
+            .unwrap();
+
+        let store = MappingStore::open(&git_repo).unwrap();
+        assert_eq!(
+            store.message_oid(12),
+            Some(git2::Oid::hash_object(git2::ObjectType::Blob, b"Title\n\nSummary\n").unwrap())
+        );
+        assert_eq!(
+            store.message(&git_repo, 12).as_deref(),
+            Some("Title\n\nSummary\n")
+        );
+        assert_eq!(store.message(&git_repo, 13), None);
+    }
+}
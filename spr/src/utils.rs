@@ -5,8 +5,12 @@
  * LICENSE file in the root directory of this source tree.
  */
 
-use crate::error::{Error, Result};
+use crate::{
+    error::{Error, Result},
+    output::{debug_command, debug_exit},
+};
 
+use futures::{StreamExt, stream};
 use std::{io::Write, process::Stdio};
 use unicode_normalization::UnicodeNormalization;
 
@@ -42,12 +46,39 @@ pub fn remove_all_parens(text: &str) -> String {
     lazy_regex::regex!(r#"[()]"#).replace_all(text, "").into()
 }
 
+/// Running a command and waiting for its output, logged with `--verbose`.
+pub(crate) trait LoggedOutput {
+    fn logged_output(&mut self) -> std::io::Result<std::process::Output>;
+}
+
+impl LoggedOutput for std::process::Command {
+    fn logged_output(&mut self) -> std::io::Result<std::process::Output> {
+        debug_command(self);
+        let output = self.output()?;
+        debug_exit(self, output.status);
+        Ok(output)
+    }
+}
+
+/// Like [`LoggedOutput`], for commands that run asynchronously.
+pub(crate) trait AsyncLoggedOutput {
+    async fn logged_output(&mut self) -> std::io::Result<std::process::Output>;
+}
+
+impl AsyncLoggedOutput for tokio::process::Command {
+    async fn logged_output(&mut self) -> std::io::Result<std::process::Output> {
+        debug_command(self.as_std());
+        let output = self.output().await?;
+        debug_exit(self.as_std(), output.status);
+        Ok(output)
+    }
+}
+
 pub async fn run_command(cmd: &mut tokio::process::Command) -> Result<()> {
     let cmd_output = cmd
         .stdout(Stdio::null())
         .stderr(Stdio::piped())
-        .spawn()?
-        .wait_with_output()
+        .logged_output()
         .await?;
 
     if !cmd_output.status.success() {
@@ -58,6 +89,48 @@ pub async fn run_command(cmd: &mut tokio::process::Command) -> Result<()> {
     Ok(())
 }
 
+/// Run `futures` with at most `limit` of them in flight at any time, and
+/// return their outputs in the original order.
+pub async fn run_bounded<F: std::future::Future>(
+    futures: impl IntoIterator<Item = F>,
+    limit: usize,
+) -> Vec<F::Output> {
+    stream::iter(futures).buffered(limit).collect().await
+}
+
+/// Open a URL in the browser, if there is one. Returns false without doing
+/// anything in CI, or on a machine without a display.
+pub fn open_in_browser(url: &str) -> bool {
+    let Some(opener) = browser_opener(std::env::consts::OS, |name| {
+        std::env::var_os(name).is_some_and(|value| !value.is_empty())
+    }) else {
+        return false;
+    };
+
+    std::process::Command::new(opener[0])
+        .args(&opener[1..])
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .is_ok()
+}
+
+/// The command that opens a URL on the given platform.
+fn browser_opener(os: &str, env_is_set: impl Fn(&str) -> bool) -> Option<&'static [&'static str]> {
+    if env_is_set("CI") {
+        return None;
+    }
+
+    match os {
+        "macos" => Some(&["open"]),
+        "windows" => Some(&["cmd", "/C", "start", ""]),
+        _ if env_is_set("DISPLAY") || env_is_set("WAYLAND_DISPLAY") => Some(&["xdg-open"]),
+        _ => None,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     // Note this useful idiom: importing names from outer (for mod tests) scope.
@@ -120,4 +193,18 @@ mod tests {
             expected
         );
     }
+
+    #[test]
+    fn test_browser_opener() {
+        let env = |vars: &'static [&'static str]| move |name: &str| vars.contains(&name);
+
+        assert_eq!(browser_opener("macos", env(&[])), Some(&["open"][..]));
+        assert_eq!(
+            browser_opener("linux", env(&["DISPLAY"])),
+            Some(&["xdg-open"][..])
+        );
+        // Headless
+        assert_eq!(browser_opener("linux", env(&[])), None);
+        assert_eq!(browser_opener("macos", env(&["CI"])), None);
+    }
 }